@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Directory stopword lists are read from when `STOPWORDS_DIR` is unset.
+pub const DEFAULT_STOPWORDS_DIR: &str = "stopwords";
+
+/// Words left out of `/wordfreq` counts, per language.
+#[derive(Debug, Clone, Default)]
+pub struct Stopwords {
+    by_lang: HashMap<String, HashSet<String>>,
+}
+
+impl Stopwords {
+    /// Loads every `<lang>.txt` in `dir`, one stopword per line. A missing
+    /// directory just means no stopwords.
+    pub fn load(dir: &Path) -> Self {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("No stopwords loaded from {}: {}", dir.display(), e);
+                return Self::default();
+            }
+        };
+
+        let mut by_lang = HashMap::new();
+        for path in entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+        {
+            if path.extension().is_none_or(|ext| ext != "txt") {
+                continue;
+            }
+            let Some(lang) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    by_lang.insert(lang.to_string(), parse_stopwords(&contents));
+                }
+                Err(e) => log::warn!("Skipping stopwords in {}: {}", path.display(), e),
+            }
+        }
+        log::info!("Loaded stopwords for {} languages", by_lang.len());
+        Self { by_lang }
+    }
+
+    /// Stopwords for `lang`, trying `pt-BR` before `pt`; empty when neither
+    /// has a file.
+    pub fn for_lang(&self, lang: &str) -> &HashSet<String> {
+        static EMPTY: std::sync::OnceLock<HashSet<String>> = std::sync::OnceLock::new();
+        let primary = lang.split('-').next().unwrap_or(lang);
+        self.by_lang
+            .get(lang)
+            .or_else(|| self.by_lang.get(primary))
+            .unwrap_or_else(|| EMPTY.get_or_init(HashSet::new))
+    }
+}
+
+fn parse_stopwords(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|word| !word.is_empty() && !word.starts_with('#'))
+        .collect()
+}
+
+/// Counts how often each word occurs, ignoring case, numbers, single
+/// letters and `stopwords`. Most frequent first, ties alphabetically.
+pub fn word_frequency(text: &str, stopwords: &HashSet<String>) -> Vec<(String, usize)> {
+    top_words(count_words(text, stopwords), usize::MAX)
+}
+
+/// The per-word counts behind [`word_frequency`], unsorted.
+pub fn count_words(text: &str, stopwords: &HashSet<String>) -> HashMap<String, usize> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+    {
+        if word.chars().count() < 2
+            || word.chars().all(|c| c.is_numeric())
+            || stopwords.contains(&word)
+        {
+            continue;
+        }
+        *counts.entry(word).or_default() += 1;
+    }
+    counts
+}
+
+/// Adds up the counts of several texts, e.g. one per video of a corpus.
+pub fn merge_frequencies(maps: Vec<HashMap<String, usize>>) -> HashMap<String, usize> {
+    let mut merged: HashMap<String, usize> = HashMap::new();
+    for map in maps {
+        for (word, count) in map {
+            *merged.entry(word).or_default() += count;
+        }
+    }
+    merged
+}
+
+/// The `n` most frequent words, ties alphabetically.
+pub fn top_words(counts: HashMap<String, usize>, n: usize) -> Vec<(String, usize)> {
+    let mut frequencies: Vec<(String, usize)> = counts.into_iter().collect();
+    frequencies.sort_by(|(a_word, a_count), (b_word, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_word.cmp(b_word))
+    });
+    frequencies.truncate(n);
+    frequencies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spanish_stopwords_are_excluded() {
+        let dir = std::env::temp_dir().join(format!("tofuboi-stopwords-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("es.txt"), "de\nla\nel\nque\n").unwrap();
+        fs::write(dir.join("README"), "not a list").unwrap();
+
+        let stopwords = Stopwords::load(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let text = "El gato de la casa que come la comida de el gato";
+        let counts = word_frequency(text, stopwords.for_lang("es-MX"));
+
+        assert_eq!(counts[0], ("gato".to_string(), 2));
+        assert!(counts
+            .iter()
+            .all(|(word, _)| !["de", "la", "el", "que"].contains(&word.as_str())));
+        // No file for Japanese, so nothing is filtered
+        assert!(stopwords.for_lang("ja").is_empty());
+    }
+
+    #[test]
+    fn test_word_frequency_counts() {
+        let counts = word_frequency("Bread, bread and BREAD! 42 x don't", &HashSet::new());
+
+        assert_eq!(
+            counts,
+            vec![
+                ("bread".to_string(), 3),
+                ("and".to_string(), 1),
+                ("don't".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_frequencies() {
+        let first = count_words("river boat river bank", &HashSet::new());
+        let second = count_words("boat river canal", &HashSet::new());
+
+        let merged = merge_frequencies(vec![first, second]);
+        assert_eq!(merged["river"], 3);
+        assert_eq!(merged["boat"], 2);
+        assert_eq!(merged["canal"], 1);
+
+        assert_eq!(
+            top_words(merged, 2),
+            vec![("river".to_string(), 3), ("boat".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_shipped_lists_parse() {
+        let stopwords = Stopwords::load(Path::new(DEFAULT_STOPWORDS_DIR));
+        assert!(stopwords.for_lang("en").contains("the"));
+        assert!(stopwords.for_lang("es").contains("de"));
+    }
+}
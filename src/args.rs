@@ -0,0 +1,531 @@
+use crate::delivery::DeliveryMode;
+use crate::export::OutputFormat;
+use unicode_normalization::UnicodeNormalization;
+
+/// Options given after the video ID, e.g. `abc123 es` or `abc123 lang=zh-HK`.
+#[derive(Debug, Default, PartialEq)]
+pub struct FormatArgs {
+    pub lang: Option<String>,
+    /// Fail instead of falling back to another language (`exact`, or a `!`
+    /// after the language as in `es!`)
+    pub exact: bool,
+    /// Part of the video to keep, from `range=1:00-2:30`
+    pub range: Option<TimeRange>,
+    /// Send the unmodified ytranscript output as JSON (`raw`)
+    pub raw: bool,
+    /// Send the transcript as MarkdownV2 messages with bold timestamps (`md`)
+    pub markdown: bool,
+    /// One sentence per line, each with its `[MM:SS]` start (`compact`)
+    pub compact: bool,
+    /// Send the transcript as a PDF document (`pdf`)
+    pub pdf: bool,
+    /// Mask email addresses and phone numbers (`redact`)
+    pub redact: bool,
+    /// Strip `[inaudible]`-style markers (`noinaudible`) or keep them
+    /// (`inaudible`); `None` follows `STRIP_INAUDIBLE`
+    pub strip_inaudible: Option<bool>,
+    /// Drop captions shorter than this many characters (`min_len=3`);
+    /// `None` follows `MIN_CAPTION_LEN`
+    pub min_len: Option<usize>,
+    /// Name the language used even when it is the one asked for (`verbose`)
+    pub verbose: bool,
+    /// Split the transcript into chapters at long pauses (`chapters`)
+    pub chapters: bool,
+    /// Only list captions matching this, from `find=word` or
+    /// `find="two words"`; `find=/regex/` searches by pattern
+    pub find: Option<String>,
+    /// Formats to send the transcript in, from `format=srt,txt`
+    pub formats: Vec<OutputFormat>,
+    /// How to send it, overriding the format's default and `DELIVERY_MODE`
+    /// (`delivery=document`)
+    pub delivery: Option<DeliveryMode>,
+    /// Address to email the transcript to instead (`email=reader@example.com`)
+    pub email: Option<String>,
+    /// Second language shown under each caption (`bilingual:es`)
+    pub bilingual: Option<String>,
+    /// Tokens that matched no option, reported back to the user
+    pub unknown: Vec<String>,
+}
+
+/// A span of the video in milliseconds; `end` is open when `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start_ms: u64,
+    pub end_ms: Option<u64>,
+}
+
+impl TimeRange {
+    /// Everything from `start_ms` to the end of the video
+    pub fn from_start(start_ms: u64) -> Self {
+        Self {
+            start_ms,
+            end_ms: None,
+        }
+    }
+
+    pub fn contains(&self, offset_ms: u64) -> bool {
+        offset_ms >= self.start_ms && self.end_ms.is_none_or(|end| offset_ms < end)
+    }
+
+    /// Parses `START-END`, where either side may be left empty
+    fn parse(spec: &str) -> Option<Self> {
+        let (start, end) = spec.split_once('-')?;
+        let start_ms = if start.is_empty() {
+            0
+        } else {
+            parse_timestamp(start)?
+        };
+        let end_ms = if end.is_empty() {
+            None
+        } else {
+            Some(parse_timestamp(end)?)
+        };
+        Some(Self { start_ms, end_ms })
+    }
+}
+
+/// Parses a timestamp into milliseconds. Accepts plain seconds (`120`),
+/// YouTube's unit form (`2m0s`, `1h2m3s`) and clock form (`1:02:03`).
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+
+    let seconds = if s.contains(':') {
+        let mut total: u64 = 0;
+        for part in s.split(':') {
+            total = total.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+        }
+        total
+    } else if s.chars().all(|c| c.is_ascii_digit()) {
+        s.parse().ok()?
+    } else {
+        let mut total = 0;
+        let mut digits = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+            let value: u64 = digits.parse().ok()?;
+            digits.clear();
+            let unit = match c {
+                'h' => 3600,
+                'm' => 60,
+                's' => 1,
+                _ => return None,
+            };
+            total = value.checked_mul(unit)?.checked_add(total)?;
+        }
+        if !digits.is_empty() {
+            return None;
+        }
+        total
+    };
+
+    seconds.checked_mul(1000)
+}
+
+/// Invisible characters that copy-paste leaves around words.
+const ZERO_WIDTH: &[char] = &['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// Cleans up a typed language code: full-width letters become ASCII (NFKC),
+/// zero-width characters go, and subtags get their usual casing, since
+/// YouTube's codes are case-sensitive: `ZH_hk` becomes `zh-HK` and
+/// `zh-hant` becomes `zh-Hant`.
+pub fn normalize_lang(token: &str) -> String {
+    let cleaned: String = token
+        .nfkc()
+        .filter(|c| !ZERO_WIDTH.contains(c))
+        .collect::<String>()
+        .trim()
+        .replace('_', "-");
+
+    cleaned
+        .split('-')
+        .enumerate()
+        .map(|(i, subtag)| match subtag.len() {
+            _ if i == 0 => subtag.to_lowercase(),
+            // Script, e.g. Hant
+            4 if subtag.chars().all(|c| c.is_ascii_alphabetic()) => {
+                let (first, rest) = subtag.split_at(1);
+                first.to_uppercase() + &rest.to_lowercase()
+            }
+            // Region, e.g. HK or 419
+            2 | 3 => subtag.to_uppercase(),
+            _ => subtag.to_lowercase(),
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// True for tags shaped like `en`, `pt-BR` or `zh-Hant`. Bare words are only
+/// read as a language this way so typos are reported instead of fetched.
+fn looks_like_lang(token: &str) -> bool {
+    let mut subtags = token.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    primary.len() == 2
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags
+            .all(|s| (2..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Splits the `!` that asks for exactly this language off `es!`.
+fn split_exact(lang: &str) -> (&str, bool) {
+    match lang.strip_suffix('!') {
+        Some(lang) => (lang, true),
+        None => (lang, false),
+    }
+}
+
+/// Reads an option value that may be in double quotes. A quoted value goes
+/// on over the following tokens until one ends the quote; `None` when none
+/// does.
+fn read_quoted<'a>(value: &str, rest: &mut impl Iterator<Item = &'a str>) -> Option<String> {
+    let Some(quoted) = value.strip_prefix('"') else {
+        return Some(value.to_string());
+    };
+
+    let mut text = quoted.to_string();
+    loop {
+        if let Some(done) = text.strip_suffix('"') {
+            return Some(done.to_string());
+        }
+        text.push(' ');
+        text.push_str(rest.next()?);
+    }
+}
+
+/// Parses the tokens after the video ID, collecting any it does not recognise.
+pub fn parse_format_args(tokens: &[&str]) -> FormatArgs {
+    let mut args = FormatArgs::default();
+
+    let mut tokens = tokens.iter().copied();
+    while let Some(token) = tokens.next() {
+        match token.split_once('=') {
+            Some(("lang", lang)) if !normalize_lang(lang).is_empty() && args.lang.is_none() => {
+                let (lang, exact) = split_exact(lang);
+                args.lang = Some(normalize_lang(lang));
+                args.exact |= exact;
+            }
+            Some(("range", spec)) if args.range.is_none() => match TimeRange::parse(spec) {
+                Some(range) => args.range = Some(range),
+                None => args.unknown.push(token.to_string()),
+            },
+            Some(("find", value)) if args.find.is_none() => {
+                match read_quoted(value, &mut tokens).filter(|query| !query.is_empty()) {
+                    Some(query) => args.find = Some(query),
+                    None => args.unknown.push(token.to_string()),
+                }
+            }
+            Some(("format", names)) if args.formats.is_empty() => {
+                match names
+                    .split(',')
+                    .map(OutputFormat::parse)
+                    .collect::<Option<Vec<_>>>()
+                {
+                    Some(formats) => {
+                        for format in formats {
+                            if !args.formats.contains(&format) {
+                                args.formats.push(format);
+                            }
+                        }
+                    }
+                    None => args.unknown.push(token.to_string()),
+                }
+            }
+            Some(("delivery", name)) if args.delivery.is_none() => {
+                match DeliveryMode::parse(name) {
+                    Some(mode) => args.delivery = Some(mode),
+                    None => args.unknown.push(token.to_string()),
+                }
+            }
+            Some(("min_len", len)) if args.min_len.is_none() => match len.parse() {
+                Ok(len) => args.min_len = Some(len),
+                Err(_) => args.unknown.push(token.to_string()),
+            },
+            Some(("email", address)) if !address.is_empty() && args.email.is_none() => {
+                args.email = Some(address.to_string());
+            }
+            None if token == "raw" => args.raw = true,
+            None if token == "pdf" => args.pdf = true,
+            None if token == "md" => args.markdown = true,
+            None if token == "compact" => args.compact = true,
+            None if token == "redact" => args.redact = true,
+            None if token == "chapters" => args.chapters = true,
+            None if token == "verbose" => args.verbose = true,
+            None if token == "noinaudible" => args.strip_inaudible = Some(true),
+            None if token == "inaudible" => args.strip_inaudible = Some(false),
+            None if token.starts_with("bilingual:") && args.bilingual.is_none() => {
+                match token.strip_prefix("bilingual:").map(normalize_lang) {
+                    Some(lang) if looks_like_lang(&lang) => args.bilingual = Some(lang),
+                    _ => args.unknown.push(token.to_string()),
+                }
+            }
+            None if token == "exact" => args.exact = true,
+            None if args.lang.is_none()
+                && looks_like_lang(&normalize_lang(split_exact(token).0)) =>
+            {
+                let (lang, exact) = split_exact(token);
+                args.lang = Some(normalize_lang(lang));
+                args.exact |= exact;
+            }
+            _ => args.unknown.push(token.to_string()),
+        }
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(parse_format_args(&[]), FormatArgs::default());
+        assert_eq!(parse_format_args(&["es"]).lang.as_deref(), Some("es"));
+        assert_eq!(parse_format_args(&["zh-HK"]).lang.as_deref(), Some("zh-HK"));
+        assert_eq!(
+            parse_format_args(&["lang=fil"]).lang.as_deref(),
+            Some("fil")
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("120"), Some(120_000));
+        assert_eq!(parse_timestamp("2m0s"), Some(120_000));
+        assert_eq!(parse_timestamp("1h2m3s"), Some(3_723_000));
+        assert_eq!(parse_timestamp("45s"), Some(45_000));
+        assert_eq!(parse_timestamp("1:02:03"), Some(3_723_000));
+        assert_eq!(parse_timestamp("2:30"), Some(150_000));
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("2x"), None);
+        assert_eq!(parse_timestamp("1m30"), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp_overflow() {
+        assert_eq!(parse_timestamp("99999999999999999"), None);
+        assert_eq!(parse_timestamp("9999999999999999h"), None);
+        assert_eq!(parse_timestamp("18446744073709551615s1s"), None);
+        assert_eq!(parse_timestamp("99999999999999999:00:00"), None);
+        assert_eq!(parse_format_args(&["range=99999999999999999-"]).range, None);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let range = parse_format_args(&["range=1:00-2:30"]).range.unwrap();
+        assert_eq!(
+            range,
+            TimeRange {
+                start_ms: 60_000,
+                end_ms: Some(150_000)
+            }
+        );
+        assert!(range.contains(60_000));
+        assert!(!range.contains(150_000));
+
+        assert_eq!(
+            parse_format_args(&["range=90-"]).range,
+            Some(TimeRange::from_start(90_000))
+        );
+        assert_eq!(
+            parse_format_args(&["range=soon"]).unknown,
+            vec!["range=soon"]
+        );
+    }
+
+    #[test]
+    fn test_parse_min_len() {
+        assert_eq!(parse_format_args(&["min_len=3"]).min_len, Some(3));
+        assert_eq!(parse_format_args(&["min_len=0"]).min_len, Some(0));
+        assert_eq!(parse_format_args(&["es"]).min_len, None);
+        assert_eq!(
+            parse_format_args(&["min_len=short"]).unknown,
+            vec!["min_len=short"]
+        );
+    }
+
+    #[test]
+    fn test_parse_raw() {
+        let args = parse_format_args(&["raw", "es"]);
+        assert!(args.raw);
+        assert_eq!(args.lang.as_deref(), Some("es"));
+        assert!(!parse_format_args(&["es"]).raw);
+    }
+
+    #[test]
+    fn test_parse_pdf() {
+        assert!(parse_format_args(&["pdf"]).pdf);
+        assert!(!parse_format_args(&["es"]).pdf);
+    }
+
+    #[test]
+    fn test_parse_redact() {
+        let args = parse_format_args(&["redact", "pdf"]);
+        assert!(args.redact);
+        assert!(args.pdf);
+        assert!(!parse_format_args(&["es"]).redact);
+    }
+
+    #[test]
+    fn test_normalize_lang() {
+        assert_eq!(normalize_lang("ｅｎ"), "en");
+        assert_eq!(normalize_lang("en\u{200B}"), "en");
+        assert_eq!(normalize_lang("\u{FEFF} ZH_hk "), "zh-HK");
+        assert_eq!(normalize_lang("zh-hant"), "zh-Hant");
+        assert_eq!(normalize_lang("es-419"), "es-419");
+    }
+
+    #[test]
+    fn test_parse_normalizes_language() {
+        assert_eq!(parse_format_args(&["ｅｎ"]).lang.as_deref(), Some("en"));
+        assert_eq!(
+            parse_format_args(&["es\u{200B}"]).lang.as_deref(),
+            Some("es")
+        );
+        assert_eq!(
+            parse_format_args(&["lang=zh-hk"]).lang.as_deref(),
+            Some("zh-HK")
+        );
+        assert_eq!(
+            parse_format_args(&["bilingual:ｅｓ"]).bilingual.as_deref(),
+            Some("es")
+        );
+    }
+
+    #[test]
+    fn test_parse_find() {
+        let args = parse_format_args(&["find=\"climate", "change\"", "es"]);
+        assert_eq!(args.find.as_deref(), Some("climate change"));
+        assert_eq!(args.lang.as_deref(), Some("es"));
+
+        let args = parse_format_args(&["find=climate"]);
+        assert_eq!(args.find.as_deref(), Some("climate"));
+
+        let args = parse_format_args(&["find=\"solar\""]);
+        assert_eq!(args.find.as_deref(), Some("solar"));
+
+        // An unclosed quote or an empty query is reported rather than searched
+        let args = parse_format_args(&["find=\"open", "ended"]);
+        assert_eq!(args.find, None);
+        assert_eq!(args.unknown, vec!["find=\"open"]);
+        assert_eq!(parse_format_args(&["find=\"\""]).find, None);
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        assert_eq!(
+            parse_format_args(&["format=srt,TXT,srt"]).formats,
+            vec![OutputFormat::Srt, OutputFormat::Txt]
+        );
+        assert_eq!(
+            parse_format_args(&["format=vtt", "es"]).formats,
+            vec![OutputFormat::Vtt]
+        );
+
+        let args = parse_format_args(&["format=srt,docx"]);
+        assert!(args.formats.is_empty());
+        assert_eq!(args.unknown, vec!["format=srt,docx"]);
+    }
+
+    #[test]
+    fn test_parse_exact() {
+        let args = parse_format_args(&["es!"]);
+        assert_eq!(args.lang.as_deref(), Some("es"));
+        assert!(args.exact);
+
+        let args = parse_format_args(&["lang=pt-br!"]);
+        assert_eq!(args.lang.as_deref(), Some("pt-BR"));
+        assert!(args.exact);
+
+        let args = parse_format_args(&["exact", "fr"]);
+        assert_eq!(args.lang.as_deref(), Some("fr"));
+        assert!(args.exact);
+
+        assert!(!parse_format_args(&["fr"]).exact);
+    }
+
+    #[test]
+    fn test_parse_compact() {
+        let args = parse_format_args(&["compact", "es"]);
+        assert!(args.compact);
+        assert_eq!(args.lang.as_deref(), Some("es"));
+    }
+
+    #[test]
+    fn test_parse_delivery() {
+        let args = parse_format_args(&["format=srt", "delivery=pastebin"]);
+        assert_eq!(args.delivery, Some(DeliveryMode::Pastebin));
+
+        let args = parse_format_args(&["delivery=carrier-pigeon"]);
+        assert_eq!(args.delivery, None);
+        assert_eq!(args.unknown, vec!["delivery=carrier-pigeon"]);
+    }
+
+    #[test]
+    fn test_parse_markdown() {
+        let args = parse_format_args(&["md", "es"]);
+        assert!(args.markdown);
+        assert_eq!(args.lang.as_deref(), Some("es"));
+    }
+
+    #[test]
+    fn test_parse_email() {
+        let args = parse_format_args(&["email=reader@example.com", "es"]);
+        assert_eq!(args.email.as_deref(), Some("reader@example.com"));
+        assert_eq!(args.lang.as_deref(), Some("es"));
+        assert_eq!(parse_format_args(&["email="]).unknown, vec!["email="]);
+    }
+
+    #[test]
+    fn test_parse_inaudible() {
+        assert_eq!(parse_format_args(&["es"]).strip_inaudible, None);
+        assert_eq!(
+            parse_format_args(&["noinaudible"]).strip_inaudible,
+            Some(true)
+        );
+        assert_eq!(
+            parse_format_args(&["inaudible"]).strip_inaudible,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_parse_chapters() {
+        assert!(parse_format_args(&["chapters"]).chapters);
+        assert!(!parse_format_args(&["es"]).chapters);
+    }
+
+    #[test]
+    fn test_parse_verbose() {
+        let args = parse_format_args(&["es", "verbose"]);
+        assert!(args.verbose);
+        assert_eq!(args.lang.as_deref(), Some("es"));
+    }
+
+    #[test]
+    fn test_parse_bilingual() {
+        let args = parse_format_args(&["es", "bilingual:zh-HK"]);
+        assert_eq!(args.lang.as_deref(), Some("es"));
+        assert_eq!(args.bilingual.as_deref(), Some("zh-HK"));
+
+        let args = parse_format_args(&["bilingual:spanish"]);
+        assert_eq!(args.bilingual, None);
+        assert_eq!(args.unknown, vec!["bilingual:spanish"]);
+    }
+
+    #[test]
+    fn test_unknown_tokens_are_collected() {
+        let args = parse_format_args(&["foobar", "baz"]);
+        assert_eq!(args.lang, None);
+        assert_eq!(args.unknown, vec!["foobar", "baz"]);
+
+        let args = parse_format_args(&["de", "fr", "speed=2"]);
+        assert_eq!(args.lang.as_deref(), Some("de"));
+        assert_eq!(args.unknown, vec!["fr", "speed=2"]);
+    }
+}
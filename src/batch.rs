@@ -0,0 +1,189 @@
+use crate::transcript::TranscriptFetcher;
+use crate::upload::fetch_and_upload;
+use crate::video_id::parse_video_input;
+use futures::stream::{self, StreamExt};
+use reqwest::Client;
+use std::error::Error;
+use std::sync::Arc;
+use teloxide::types::Document;
+
+/// Upper bound on the number of links processed from one file.
+pub const MAX_BATCH_ITEMS: usize = 20;
+
+/// Links fetched at the same time when `BATCH_CONCURRENCY` is unset.
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 3;
+
+/// Outcome of one line of a batch request.
+pub struct BatchItem {
+    pub input: String,
+    pub result: Result<String, Box<dyn Error + Send + Sync>>,
+}
+
+/// Returns true for documents that should be read as a list of links.
+pub fn is_text_document(doc: &Document) -> bool {
+    let is_text_mime = doc
+        .mime_type
+        .as_ref()
+        .is_some_and(|mime| mime.type_() == "text");
+    let is_txt_name = doc
+        .file_name
+        .as_deref()
+        .is_some_and(|name| name.to_lowercase().ends_with(".txt"));
+    is_text_mime || is_txt_name
+}
+
+/// Splits a batch file into its non-empty lines, capped at [`MAX_BATCH_ITEMS`].
+pub fn parse_batch_file(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .take(MAX_BATCH_ITEMS)
+        .collect()
+}
+
+/// Fetches and uploads up to `concurrency` lines at a time, collecting one
+/// result per line in the order of `lines`.
+pub async fn process_batch<T: TranscriptFetcher>(
+    fetcher: &T,
+    client: Arc<Client>,
+    lines: &[&str],
+    lang: &str,
+    concurrency: usize,
+) -> Vec<BatchItem> {
+    // Built up front: mapping inside the stream trips the compiler's Send
+    // check on the borrowed lines
+    let jobs: Vec<_> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, &line)| {
+            let client = client.clone();
+            async move {
+                let result = match parse_video_input(line) {
+                    Some(video_id) => fetch_and_upload(fetcher, client, &video_id, lang).await,
+                    None => Err("not a YouTube link or video ID".into()),
+                };
+                let item = BatchItem {
+                    input: line.to_string(),
+                    result,
+                };
+                (i, item)
+            }
+        })
+        .collect();
+    let mut items: Vec<(usize, BatchItem)> = stream::iter(jobs)
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    // Finished out of order, replied in the order they were sent
+    items.sort_by_key(|(i, _)| *i);
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Builds the consolidated reply listing every batch result.
+pub fn format_batch_results(items: &[BatchItem]) -> String {
+    items
+        .iter()
+        .map(|item| match &item.result {
+            Ok(url) => format!("{}: {}", item.input, url),
+            Err(e) => format!("{}: {}", item.input, e),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::{test_entry, MockTranscriptFetcher};
+    use mockito::{mock, Matcher};
+    use std::env;
+
+    #[test]
+    fn test_parse_batch_file() {
+        let text = "https://youtu.be/HQoJMIgNdjo\n\n  dQw4w9WgXcQ  \n";
+        assert_eq!(
+            parse_batch_file(text),
+            vec!["https://youtu.be/HQoJMIgNdjo", "dQw4w9WgXcQ"]
+        );
+
+        let many = "dQw4w9WgXcQ\n".repeat(MAX_BATCH_ITEMS + 5);
+        assert_eq!(parse_batch_file(&many).len(), MAX_BATCH_ITEMS);
+    }
+
+    #[tokio::test]
+    async fn test_process_batch_of_two_urls() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "batch entry".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/batch")
+            .create();
+
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Ok(vec![test_entry("batch entry", 0.0, 1.0)]))
+            .with_response(Ok(vec![test_entry("batch entry", 0.0, 1.0)]));
+        let text =
+            "https://www.youtube.com/watch?v=HQoJMIgNdjo\nhttps://youtu.be/dQw4w9WgXcQ\nnot a link";
+        let lines = parse_batch_file(text);
+
+        let items = process_batch(&fetcher, Arc::new(Client::new()), &lines, "en", 1).await;
+
+        assert_eq!(items.len(), 3);
+        assert!(items[0]
+            .result
+            .as_ref()
+            .unwrap()
+            .contains("pastebin.com/raw/"));
+        assert!(items[1]
+            .result
+            .as_ref()
+            .unwrap()
+            .contains("pastebin.com/raw/"));
+        assert!(items[2].result.is_err());
+        assert_eq!(
+            fetcher.calls(),
+            vec![
+                ("HQoJMIgNdjo".to_string(), "en".to_string()),
+                ("dQw4w9WgXcQ".to_string(), "en".to_string())
+            ]
+        );
+
+        let reply = format_batch_results(&items);
+        assert_eq!(reply.lines().count(), 3);
+        assert!(reply.contains("not a link: not a YouTube link or video ID"));
+    }
+
+    #[tokio::test]
+    async fn test_process_batch_concurrently_keeps_order() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "concurrent entry".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/concurrent")
+            .expect(3)
+            .create();
+
+        let mut fetcher = MockTranscriptFetcher::new();
+        for _ in 0..3 {
+            fetcher = fetcher.with_response(Ok(vec![test_entry("concurrent entry", 0.0, 1.0)]));
+        }
+        let lines = ["HQoJMIgNdjo", "no link here", "dQw4w9WgXcQ", "jNQXAC9IVRw"];
+
+        let items = process_batch(&fetcher, Arc::new(Client::new()), &lines, "en", 2).await;
+
+        let inputs: Vec<&str> = items.iter().map(|item| item.input.as_str()).collect();
+        assert_eq!(inputs, lines);
+        assert!(items[0].result.is_ok());
+        assert!(items[1].result.is_err());
+        assert!(items[2].result.is_ok());
+        assert!(items[3].result.is_ok());
+        assert_eq!(fetcher.calls().len(), 3);
+    }
+}
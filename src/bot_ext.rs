@@ -0,0 +1,141 @@
+use crate::delivery::TELEGRAM_MESSAGE_LIMIT;
+use teloxide::payloads::SendMessage;
+use teloxide::prelude::*;
+use teloxide::requests::JsonRequest;
+use teloxide::types::{ParseMode, Recipient};
+use teloxide::utils::{html, markdown};
+
+/// Sending helpers every message from the bot goes through.
+pub trait BotExt {
+    /// `send_message` in `parse_mode`, with `footer` escaped for it and
+    /// appended to `text` when it fits.
+    fn send_with_footer<C, T>(
+        &self,
+        chat_id: C,
+        text: T,
+        footer: Option<&str>,
+        parse_mode: Option<ParseMode>,
+    ) -> JsonRequest<SendMessage>
+    where
+        C: Into<Recipient>,
+        T: Into<String>;
+}
+
+impl BotExt for Bot {
+    fn send_with_footer<C, T>(
+        &self,
+        chat_id: C,
+        text: T,
+        footer: Option<&str>,
+        parse_mode: Option<ParseMode>,
+    ) -> JsonRequest<SendMessage>
+    where
+        C: Into<Recipient>,
+        T: Into<String>,
+    {
+        let request = self.send_message(chat_id, with_footer(text.into(), footer, parse_mode));
+        match parse_mode {
+            Some(mode) => request.parse_mode(mode),
+            None => request,
+        }
+    }
+}
+
+/// `text` followed by `footer` escaped for `parse_mode`, or `text` alone
+/// when the two together would be longer than Telegram allows.
+pub fn with_footer(
+    mut text: String,
+    footer: Option<&str>,
+    parse_mode: Option<ParseMode>,
+) -> String {
+    let Some(footer) = footer.filter(|footer| !footer.is_empty()) else {
+        return text;
+    };
+    let footer = escape_footer(footer, parse_mode);
+    if text.len() + footer.len() > TELEGRAM_MESSAGE_LIMIT {
+        log::warn!(
+            "Message footer left off a {}-byte message to stay within {} bytes",
+            text.len(),
+            TELEGRAM_MESSAGE_LIMIT
+        );
+        return text;
+    }
+    text.push_str(&footer);
+    text
+}
+
+/// Bytes a message in `parse_mode` can hold besides `footer`, for replies
+/// split over several messages that should each keep it.
+pub fn text_limit(footer: Option<&str>, parse_mode: Option<ParseMode>) -> usize {
+    let footer_len = footer.map_or(0, |footer| escape_footer(footer, parse_mode).len());
+    TELEGRAM_MESSAGE_LIMIT.saturating_sub(footer_len)
+}
+
+/// The footer is plain text, so characters that mean something in the
+/// message's parse mode are escaped.
+fn escape_footer(footer: &str, parse_mode: Option<ParseMode>) -> String {
+    match parse_mode {
+        None => footer.to_string(),
+        Some(ParseMode::MarkdownV2) => markdown::escape(footer),
+        Some(ParseMode::Html) => html::escape(footer),
+        // Legacy Markdown only has these markers to escape
+        Some(_) => footer
+            .chars()
+            .flat_map(|c| match c {
+                '_' | '*' | '`' | '[' => vec!['\\', c],
+                _ => vec![c],
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FOOTER: &str = "\n\n— Powered by TranscriptBot";
+
+    #[test]
+    fn test_footer_is_appended() {
+        assert_eq!(
+            with_footer("Hello".to_string(), Some(FOOTER), None),
+            "Hello\n\n— Powered by TranscriptBot"
+        );
+        assert_eq!(with_footer("Hello".to_string(), None, None), "Hello");
+        assert_eq!(with_footer("Hello".to_string(), Some(""), None), "Hello");
+    }
+
+    #[test]
+    fn test_footer_is_escaped_for_parse_mode() {
+        let footer = Some(" — t.me/my_bot <beta> & more!");
+        assert_eq!(
+            with_footer("*Hi*".to_string(), footer, Some(ParseMode::MarkdownV2)),
+            "*Hi* — t\\.me/my\\_bot <beta\\> & more\\!"
+        );
+        assert_eq!(
+            with_footer("<b>Hi</b>".to_string(), footer, Some(ParseMode::Html)),
+            "<b>Hi</b> — t.me/my_bot &lt;beta&gt; &amp; more!"
+        );
+    }
+
+    #[test]
+    fn test_footer_left_off_when_too_long() {
+        let fits = "a".repeat(TELEGRAM_MESSAGE_LIMIT - FOOTER.len());
+        assert_eq!(
+            with_footer(fits.clone(), Some(FOOTER), None).len(),
+            TELEGRAM_MESSAGE_LIMIT
+        );
+
+        let too_long = format!("{}a", fits);
+        assert_eq!(with_footer(too_long.clone(), Some(FOOTER), None), too_long);
+    }
+
+    #[test]
+    fn test_text_limit_leaves_room_for_footer() {
+        assert_eq!(text_limit(None, None), TELEGRAM_MESSAGE_LIMIT);
+        let limit = text_limit(Some("!"), Some(ParseMode::MarkdownV2));
+        assert_eq!(limit, TELEGRAM_MESSAGE_LIMIT - 2);
+        let text = "a".repeat(limit);
+        assert!(with_footer(text, Some("!"), Some(ParseMode::MarkdownV2)).ends_with("\\!"));
+    }
+}
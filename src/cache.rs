@@ -0,0 +1,294 @@
+use crate::metrics::{self, Counter};
+use crate::transcript::TranscriptFetcher;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use ytranscript::{TranscriptConfig, TranscriptResponse, YoutubeTranscriptError};
+
+/// How long a fetched transcript is served from the cache by default.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Time between background refresh passes by default.
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Background refreshes allowed to run at the same time.
+const MAX_CONCURRENT_REFRESHES: usize = 2;
+
+/// Entries are refreshed once they are in the last tenth of their lifetime.
+const REFRESH_WINDOW_DIVISOR: u32 = 10;
+
+/// `(video_id, lang)` of a cached transcript.
+type CacheKey = (String, String);
+
+struct CacheEntry {
+    transcript: Vec<TranscriptResponse>,
+    fetched_at: Instant,
+}
+
+/// Transcripts keyed by video and exact language, kept for a fixed TTL.
+pub struct TranscriptCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+}
+
+impl TranscriptCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, video_id: &str, lang: &str) -> Option<Vec<TranscriptResponse>> {
+        self.get_at(video_id, lang, Instant::now())
+    }
+
+    fn get_at(&self, video_id: &str, lang: &str, now: Instant) -> Option<Vec<TranscriptResponse>> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(&(video_id.to_string(), lang.to_string()))?;
+        (now.duration_since(entry.fetched_at) < self.ttl).then(|| entry.transcript.clone())
+    }
+
+    pub fn insert(&self, video_id: &str, lang: &str, transcript: Vec<TranscriptResponse>) {
+        self.insert_at(video_id, lang, transcript, Instant::now());
+    }
+
+    fn insert_at(
+        &self,
+        video_id: &str,
+        lang: &str,
+        transcript: Vec<TranscriptResponse>,
+        now: Instant,
+    ) {
+        self.entries.lock().unwrap().insert(
+            (video_id.to_string(), lang.to_string()),
+            CacheEntry {
+                transcript,
+                fetched_at: now,
+            },
+        );
+    }
+
+    /// Entries held, including expired ones not yet evicted
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn remove(&self, video_id: &str, lang: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&(video_id.to_string(), lang.to_string()));
+    }
+
+    /// Keys of live entries in the last tenth of their lifetime
+    fn due_for_refresh_at(&self, now: Instant) -> Vec<CacheKey> {
+        let refresh_after = self.ttl - self.ttl / REFRESH_WINDOW_DIVISOR;
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| {
+                let age = now.duration_since(entry.fetched_at);
+                age >= refresh_after && age < self.ttl
+            })
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn evict_expired_at(&self, now: Instant) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|_, entry| now.duration_since(entry.fetched_at) < self.ttl);
+    }
+}
+
+/// Serves repeated fetches of the same video and language from a
+/// [`TranscriptCache`] instead of asking YouTube again.
+pub struct CachedFetcher<T> {
+    inner: T,
+    cache: TranscriptCache,
+}
+
+impl<T: TranscriptFetcher> CachedFetcher<T> {
+    pub fn new(inner: T, ttl: Duration) -> Self {
+        Self {
+            inner,
+            cache: TranscriptCache::new(ttl),
+        }
+    }
+
+    /// Re-fetches entries that are about to expire. A failed refresh leaves
+    /// the existing entry in place until its TTL runs out.
+    pub async fn refresh_stale(self: &Arc<Self>) {
+        self.refresh_stale_at(Instant::now()).await;
+    }
+
+    async fn refresh_stale_at(self: &Arc<Self>, now: Instant) {
+        self.cache.evict_expired_at(now);
+
+        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_REFRESHES));
+        let mut tasks = Vec::new();
+        for (video_id, lang) in self.cache.due_for_refresh_at(now) {
+            let permit = semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("refresh semaphore is never closed");
+            let this = Arc::clone(self);
+            tasks.push(tokio::spawn(async move {
+                let _permit = permit;
+                match this.inner.fetch(&video_id, &lang).await {
+                    Ok(transcript) => {
+                        log::debug!("Refreshed cached transcript {} ({})", video_id, lang);
+                        this.cache.insert(&video_id, &lang, transcript);
+                    }
+                    Err(e) => log::debug!(
+                        "Keeping cached transcript {} ({}), refresh failed: {}",
+                        video_id,
+                        lang,
+                        e
+                    ),
+                }
+            }));
+        }
+
+        for task in tasks {
+            if let Err(e) = task.await {
+                log::warn!("Cache refresh task failed: {}", e);
+            }
+        }
+    }
+}
+
+impl<T: TranscriptFetcher> TranscriptFetcher for CachedFetcher<T> {
+    async fn fetch_with_config(
+        &self,
+        video_id: &str,
+        config: TranscriptConfig,
+    ) -> Result<Vec<TranscriptResponse>, YoutubeTranscriptError> {
+        // Transcripts are cached by language alone
+        match config.lang {
+            Some(lang) => self.fetch(video_id, &lang).await,
+            None => self.inner.fetch_with_config(video_id, config).await,
+        }
+    }
+
+    async fn fetch(
+        &self,
+        video_id: &str,
+        lang: &str,
+    ) -> Result<Vec<TranscriptResponse>, YoutubeTranscriptError> {
+        if let Some(transcript) = self.cache.get(video_id, lang) {
+            metrics::increment(Counter::CacheHits);
+            return Ok(transcript);
+        }
+        let transcript = self.inner.fetch(video_id, lang).await?;
+        self.cache.insert(video_id, lang, transcript.clone());
+        Ok(transcript)
+    }
+
+    async fn list_languages(&self, video_id: &str) -> Result<Vec<String>, YoutubeTranscriptError> {
+        self.inner.list_languages(video_id).await
+    }
+
+    fn evict(&self, video_id: &str, lang: &str) {
+        self.cache.remove(video_id, lang);
+    }
+
+    fn cached(&self, video_id: &str, lang: &str) -> Option<Vec<TranscriptResponse>> {
+        self.cache.get(video_id, lang)
+    }
+
+    fn cache_len(&self) -> Option<usize> {
+        Some(self.cache.len())
+    }
+}
+
+/// Runs [`CachedFetcher::refresh_stale`] every `interval` for the life of
+/// the process.
+pub fn spawn_refresh_task<T: TranscriptFetcher>(
+    fetcher: Arc<CachedFetcher<T>>,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately, when the cache is still empty
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            fetcher.refresh_stale().await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::{test_entry, MockTranscriptFetcher};
+
+    const TTL: Duration = Duration::from_secs(100);
+
+    #[test]
+    fn test_due_for_refresh_in_last_tenth_of_ttl() {
+        let cache = TranscriptCache::new(TTL);
+        let start = Instant::now();
+        cache.insert_at("abc123", "en", vec![test_entry("hi", 0.0, 1.0)], start);
+
+        assert!(cache
+            .due_for_refresh_at(start + Duration::from_secs(89))
+            .is_empty());
+        assert_eq!(
+            cache.due_for_refresh_at(start + Duration::from_secs(90)),
+            vec![("abc123".to_string(), "en".to_string())]
+        );
+        // Expired entries are evicted, not refreshed
+        assert!(cache.due_for_refresh_at(start + TTL).is_empty());
+        assert!(cache.get_at("abc123", "en", start + TTL).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_keeps_stale_entry() {
+        let inner = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TooManyRequests));
+        let fetcher = Arc::new(CachedFetcher::new(inner, TTL));
+        let start = Instant::now();
+        fetcher
+            .cache
+            .insert_at("abc123", "en", vec![test_entry("stale", 0.0, 1.0)], start);
+
+        let later = start + Duration::from_secs(95);
+        fetcher.refresh_stale_at(later).await;
+
+        assert_eq!(
+            fetcher.inner.calls(),
+            vec![("abc123".to_string(), "en".to_string())]
+        );
+        let kept = fetcher.cache.get_at("abc123", "en", later).unwrap();
+        assert_eq!(kept[0].text, "stale");
+    }
+
+    #[tokio::test]
+    async fn test_successful_refresh_replaces_entry() {
+        let inner =
+            MockTranscriptFetcher::new().with_response(Ok(vec![test_entry("fresh", 0.0, 1.0)]));
+        let fetcher = Arc::new(CachedFetcher::new(inner, TTL));
+        let start = Instant::now();
+        fetcher
+            .cache
+            .insert_at("abc123", "en", vec![test_entry("stale", 0.0, 1.0)], start);
+
+        fetcher
+            .refresh_stale_at(start + Duration::from_secs(95))
+            .await;
+
+        assert_eq!(
+            fetcher.fetch("abc123", "en").await.unwrap()[0].text,
+            "fresh"
+        );
+        // Served from the cache, so the inner fetcher saw only the refresh
+        assert_eq!(fetcher.inner.calls().len(), 1);
+    }
+}
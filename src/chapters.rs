@@ -0,0 +1,124 @@
+use crate::formatter::{format_stamp, gap_between};
+use crate::templates::ReplyFormat;
+use std::ops::Range;
+use teloxide::utils::markdown;
+use ytranscript::TranscriptResponse;
+
+/// Silence, in seconds, that starts a new chapter. Much longer than the
+/// pause that ends a sentence, so chapters follow topic changes.
+const CHAPTER_GAP_SECS: f64 = 5.0;
+
+/// A run of captions between two long pauses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub start_ms: u64,
+    /// Indices of the section's captions in the transcript
+    pub entries: Range<usize>,
+}
+
+/// Splits a transcript into sections wherever the silence between two
+/// captions reaches [`CHAPTER_GAP_SECS`].
+pub fn detect_sections(entries: &[TranscriptResponse]) -> Vec<Section> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+
+    for i in 1..=entries.len() {
+        let ends_section =
+            i == entries.len() || gap_between(&entries[i - 1], &entries[i]) >= CHAPTER_GAP_SECS;
+        if ends_section {
+            sections.push(Section {
+                start_ms: (entries[start].offset * 1000.0).max(0.0) as u64,
+                entries: start..i,
+            });
+            start = i;
+        }
+    }
+
+    sections
+}
+
+/// Title of the `number`th chapter (counting from 1) in headings and the TOC.
+pub fn chapter_title(number: usize, section: &Section) -> String {
+    format!("Chapter {} [{}]", number, format_stamp(section.start_ms))
+}
+
+/// Lists every section with its start time. HTML and Markdown entries link
+/// to that moment of the video; plain text gets `[MM:SS]` markers.
+pub fn build_toc(sections: &[Section], video_id: &str, format: ReplyFormat) -> String {
+    let mut toc = String::from("Contents");
+
+    for (i, section) in sections.iter().enumerate() {
+        let stamp = format_stamp(section.start_ms);
+        let url = format!(
+            "https://youtu.be/{}?t={}",
+            video_id,
+            section.start_ms / 1000
+        );
+        let line = match format {
+            ReplyFormat::Plain => format!("[{}] Chapter {}", stamp, i + 1),
+            ReplyFormat::Html => format!("<a href=\"{}\">{}</a> Chapter {}", url, stamp, i + 1),
+            ReplyFormat::MarkdownV2 => format!(
+                "[{}]({}) Chapter {}",
+                markdown::escape(&stamp),
+                markdown::escape_link_url(&url),
+                i + 1
+            ),
+        };
+        toc.push('\n');
+        toc.push_str(&line);
+    }
+
+    toc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::test_entry;
+
+    /// Three chapters: two captions, a 10s pause, one caption, a 6s pause,
+    /// then two more with only a short pause between them
+    fn transcript() -> Vec<TranscriptResponse> {
+        vec![
+            test_entry("intro", 0.0, 2.0),
+            test_entry("more intro", 2.5, 2.0),
+            test_entry("topic one", 14.5, 3.0),
+            test_entry("topic two", 23.5, 2.0),
+            test_entry("still two", 27.0, 2.0),
+        ]
+    }
+
+    #[test]
+    fn test_detect_sections_on_long_gaps() {
+        let sections = detect_sections(&transcript());
+
+        let ranges: Vec<Range<usize>> = sections.iter().map(|s| s.entries.clone()).collect();
+        assert_eq!(ranges, vec![0..2, 2..3, 3..5]);
+        assert_eq!(sections[2].start_ms, 23_500);
+        assert!(detect_sections(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_toc_has_one_entry_per_chapter() {
+        let sections = detect_sections(&transcript());
+
+        for format in [
+            ReplyFormat::Plain,
+            ReplyFormat::Html,
+            ReplyFormat::MarkdownV2,
+        ] {
+            let toc = build_toc(&sections, "abc123", format);
+            assert_eq!(toc.lines().count(), sections.len() + 1);
+        }
+
+        let plain = build_toc(&sections, "abc123", ReplyFormat::Plain);
+        assert_eq!(
+            plain,
+            "Contents\n[00:00] Chapter 1\n[00:14] Chapter 2\n[00:23] Chapter 3"
+        );
+        let html = build_toc(&sections, "abc123", ReplyFormat::Html);
+        assert!(html.ends_with("<a href=\"https://youtu.be/abc123?t=23\">00:23</a> Chapter 3"));
+        let markdown = build_toc(&sections, "abc123", ReplyFormat::MarkdownV2);
+        assert!(markdown.ends_with("[00:23](https://youtu.be/abc123?t=23) Chapter 3"));
+    }
+}
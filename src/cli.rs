@@ -0,0 +1,144 @@
+use crate::config::AppConfig;
+use crate::formatter;
+use crate::transcript::{TranscriptService, YoutubeTranscriptFetcher};
+use crate::upload;
+use crate::video_id::parse_video_input;
+use std::sync::Arc;
+
+/// Name of the only upload backend, as shown by `--dry-run`.
+const BACKEND_NAME: &str = "Pastebin";
+
+const USAGE: &str = "Usage: tofuboi fetch <video_id> [lang] [--dry-run]";
+
+/// A one-off fetch from the command line instead of running the bot.
+#[derive(Debug, PartialEq)]
+pub struct CliArgs {
+    pub video_id: String,
+    pub lang: Option<String>,
+    /// Print the transcript instead of uploading it
+    pub dry_run: bool,
+}
+
+/// Reads the process arguments after the program name. `None` means no
+/// subcommand was given and the bot should start as usual.
+pub fn parse_args<I>(args: I) -> Option<Result<CliArgs, String>>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    match args.next()?.as_str() {
+        "fetch" => {}
+        other => return Some(Err(format!("Unknown command '{}'\n{}", other, USAGE))),
+    }
+
+    let mut dry_run = false;
+    let mut positional = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            flag if flag.starts_with("--") => {
+                return Some(Err(format!("Unknown flag '{}'\n{}", flag, USAGE)));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let (Some(video), lang, None) = (positional.next(), positional.next(), positional.next())
+    else {
+        return Some(Err(USAGE.to_string()));
+    };
+    Some(Ok(CliArgs {
+        video_id: parse_video_input(&video).unwrap_or(video),
+        lang,
+        dry_run,
+    }))
+}
+
+/// Fetches the transcript and uploads it, printing the paste URL. With
+/// `--dry-run` the transcript is printed instead and no uploader is built,
+/// so it works without `PASTEBIN_KEY`.
+#[tracing::instrument(skip_all, fields(video_id = %args.video_id, lang))]
+pub async fn run(args: CliArgs, config: &AppConfig) -> Result<(), String> {
+    let fetcher = YoutubeTranscriptFetcher::new(config.yt_user_agent.as_deref());
+    let lang = args.lang.as_deref().unwrap_or(config.default_lang());
+    tracing::Span::current().record("lang", lang);
+    let fetched = TranscriptService::fetch(&fetcher, &args.video_id, lang)
+        .await
+        .map_err(|e| format!("Error fetching transcript: {}", e))?;
+    if let Some(info) = &fetched.info {
+        eprintln!("{}", info);
+    }
+    if fetched.is_blank() {
+        return Err("Transcript could not be retrieved or is empty.".to_string());
+    }
+
+    let content = formatter::format_plain(&fetched.entries);
+    if args.dry_run {
+        println!("{}", dry_run_header(content.len()));
+        println!("{}", content);
+        return Ok(());
+    }
+
+    let client = upload::build_http_client(config.outbound_proxy.as_deref())?;
+    let title = upload::paste_title(&args.video_id, &fetched.lang);
+    let url = upload::upload_from_env(Arc::new(client), &content, &title)
+        .await
+        .map_err(|e| format!("Error uploading transcript: {}", e))?;
+    println!("{}", url);
+    Ok(())
+}
+
+fn dry_run_header(bytes: usize) -> String {
+    format!("[DRY RUN] Would upload {} bytes to {}", bytes, BACKEND_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Option<Result<CliArgs, String>> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_no_arguments_runs_the_bot() {
+        assert_eq!(parse(&[]), None);
+    }
+
+    #[test]
+    fn test_parse_fetch() {
+        assert_eq!(
+            parse(&["fetch", "https://youtu.be/HQoJMIgNdjo", "es", "--dry-run"]),
+            Some(Ok(CliArgs {
+                video_id: "HQoJMIgNdjo".to_string(),
+                lang: Some("es".to_string()),
+                dry_run: true,
+            }))
+        );
+        assert_eq!(
+            parse(&["fetch", "--dry-run", "HQoJMIgNdjo"]),
+            Some(Ok(CliArgs {
+                video_id: "HQoJMIgNdjo".to_string(),
+                lang: None,
+                dry_run: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(parse(&["serve"]), Some(Err(_))));
+        assert!(matches!(parse(&["fetch"]), Some(Err(_))));
+        assert!(matches!(parse(&["fetch", "a", "b", "c"]), Some(Err(_))));
+        assert!(matches!(parse(&["fetch", "a", "--force"]), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_dry_run_header() {
+        assert_eq!(
+            dry_run_header(42),
+            "[DRY RUN] Would upload 42 bytes to Pastebin"
+        );
+    }
+}
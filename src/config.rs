@@ -0,0 +1,412 @@
+use crate::analysis::{Stopwords, DEFAULT_STOPWORDS_DIR};
+use crate::batch::DEFAULT_BATCH_CONCURRENCY;
+use crate::cache::{DEFAULT_CACHE_TTL, DEFAULT_REFRESH_INTERVAL};
+use crate::delivery::{self, DeliveryMode};
+#[cfg(feature = "email")]
+use crate::email::EmailConfig;
+use crate::filename::FilenameTemplate;
+use crate::history::History;
+use crate::ratelimit::{RateLimitScope, DEFAULT_RATE_LIMIT_REQUESTS, DEFAULT_RATE_LIMIT_WINDOW};
+use crate::summarize::SummarizeApi;
+use crate::templates::{MessageTemplates, SharedTemplates};
+use crate::transcript::RetryPolicy;
+use crate::translation::deepl::DeepLBackend;
+use crate::upload::GistIds;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use teloxide::types::ChatId;
+
+const DEFAULT_FILLER_WORDS: &str = "en:uh,um,uhm,erm,er,hmm";
+
+/// Language fetched when a request names none and `DEFAULT_LANG` is unset.
+const FALLBACK_DEFAULT_LANG: &str = "en";
+
+/// Handler time limit when `HANDLER_TIMEOUT_SECS` is unset.
+const DEFAULT_HANDLER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Settings read once from the environment at startup.
+#[derive(Debug, Clone, Default)]
+pub struct AppConfig {
+    /// Chat that receives `/feedback` messages (`FEEDBACK_CHAT_ID`)
+    pub feedback_chat_id: Option<ChatId>,
+    /// Chat paged about errors only the operator can fix (`ADMIN_CHAT_ID`)
+    pub admin_chat_id: Option<ChatId>,
+    /// Strip filler words and stutters from transcripts (`DEFILLER=1`)
+    pub defiller: bool,
+    /// Filler words per language, from `FILLER_WORDS` (`en:uh,um;de:äh`)
+    pub filler_words: HashMap<String, Vec<String>>,
+    /// Video fetched and uploaded once at startup (`SELFTEST_VIDEO_ID`)
+    pub selftest_video_id: Option<String>,
+    /// Abort startup when the self-test fails (`SELFTEST_STRICT=1`)
+    pub selftest_strict: bool,
+    /// Parse mode used for each reply type, reloaded when `templates_path` changes
+    pub templates: SharedTemplates,
+    /// TOML file overriding the `PARSE_MODE_*` settings (`TEMPLATES_PATH`)
+    pub templates_path: Option<PathBuf>,
+    /// Append an LLM token estimate to statistics (`SHOW_TOKEN_ESTIMATE=1`)
+    pub show_token_estimate: bool,
+    /// User agent wanted for YouTube requests (`YT_USER_AGENT`)
+    pub yt_user_agent: Option<String>,
+    /// Trigger group messages must start with, e.g. `!t ` (`GROUP_REQUIRE_PREFIX`)
+    pub group_prefix: Option<String>,
+    /// How long fetched transcripts are cached (`CACHE_TTL_SECS`)
+    pub cache_ttl: Duration,
+    /// Time between proactive cache refreshes (`CACHE_REFRESH_INTERVAL_SECS`)
+    pub cache_refresh_interval: Duration,
+    /// Name of transcript documents (`TRANSCRIPT_FILENAME_TEMPLATE`)
+    pub filename_template: FilenameTemplate,
+    /// Restore sentence punctuation in transcripts (`PUNCTUATE=1`)
+    pub punctuate: bool,
+    /// Punctuation service used instead of the heuristic (`PUNCTUATE_API_URL`)
+    pub punctuate_api_url: Option<String>,
+    /// Requests each user may make per window, 0 disables (`RATE_LIMIT_REQUESTS`)
+    pub rate_limit_requests: usize,
+    /// Length of the rate limit window (`RATE_LIMIT_WINDOW_SECS`)
+    pub rate_limit_window: Duration,
+    /// Whether limits apply per user, chat or both (`RATE_LIMIT_SCOPE`)
+    pub rate_limit_scope: RateLimitScope,
+    /// Answer each request directly in groups, off with `REPLY_IN_THREAD=0`
+    pub reply_in_thread: bool,
+    /// How transcripts are sent unless a request says otherwise; each
+    /// format has its own default when unset (`DELIVERY_MODE`)
+    pub delivery_mode: Option<DeliveryMode>,
+    /// Modes tried in turn when a document cannot be sent
+    /// (`DELIVERY_FALLBACK`, e.g. `messages,pastebin`)
+    pub delivery_fallbacks: Vec<DeliveryMode>,
+    /// Always name the language a transcript was fetched in (`VERBOSE=1`)
+    pub verbose: bool,
+    /// Remove `[inaudible]`-style markers unless a request says otherwise
+    /// (`STRIP_INAUDIBLE=1`)
+    pub strip_inaudible: bool,
+    /// Drop captions shorter than this many characters unless a request
+    /// says otherwise, 0 keeps them all (`MIN_CAPTION_LEN`)
+    pub min_caption_len: usize,
+    /// Drop captions that repeat the one before them (`DEDUP_TRANSCRIPTS=1`)
+    pub dedup_transcripts: bool,
+    /// Retrying of fetches YouTube rate-limits
+    pub youtube_retry: RetryPolicy,
+    /// Proxy for the bot's own HTTP client (`OUTBOUND_PROXY`). YouTube is
+    /// fetched by ytranscript's client, which follows `HTTPS_PROXY` instead
+    pub outbound_proxy: Option<String>,
+    /// Stopwords per language for `/wordfreq`, from `<lang>.txt` files in
+    /// `STOPWORDS_DIR`
+    pub stopwords: Stopwords,
+    /// Plain text appended to every message the bot sends, escaped for the
+    /// message's parse mode (`TELEGRAM_MESSAGE_FOOTER`)
+    pub message_footer: Option<String>,
+    /// Line put before every transcript (`TRANSCRIPT_PREFIX`)
+    pub transcript_prefix: String,
+    /// Line put after every transcript (`TRANSCRIPT_SUFFIX`)
+    pub transcript_suffix: String,
+    /// Language fetched when a request names none (`DEFAULT_LANG`), see
+    /// [`AppConfig::default_lang`]
+    pub default_lang: Option<String>,
+    /// Longest a message may take to handle, 0 disables (`HANDLER_TIMEOUT_SECS`)
+    pub handler_timeout: Option<Duration>,
+    /// Database behind `/history` and `/languages`, off when
+    /// `HISTORY_DB_PATH` is unset
+    pub history: Option<Arc<History>>,
+    /// Gists made per chat and video, updated when the video is fetched again
+    pub gists: Arc<GistIds>,
+    /// Links of a batch file fetched at the same time (`BATCH_CONCURRENCY`)
+    pub batch_concurrency: usize,
+    /// Warn when a requested video is live right now (`DETECT_LIVE=1`)
+    pub detect_live: bool,
+    /// Users allowed to run `/admin_*` commands (`ADMIN_USER_IDS`, comma separated)
+    pub admin_ids: Vec<u64>,
+    /// Address of the `/health` and `/metrics` server, off when unset (`HEALTH_ADDR`)
+    pub health_addr: Option<SocketAddr>,
+    /// Service behind `/summarize`, off when `SUMMARIZE_API_URL` is unset
+    pub summarize: Option<SummarizeApi>,
+    /// DeepL account behind `/translate`, off when `DEEPL_API_KEY` is unset
+    pub translation: Option<DeepLBackend>,
+    /// SMTP server for `email=<addr>`, off unless `EMAIL_BACKEND=smtp`
+    #[cfg(feature = "email")]
+    pub email: Option<EmailConfig>,
+}
+
+impl AppConfig {
+    pub fn from_env() -> Self {
+        let templates_path = env::var("TEMPLATES_PATH").ok().map(PathBuf::from);
+        let templates = match &templates_path {
+            Some(path) => MessageTemplates::from_file_or_env(path),
+            None => MessageTemplates::from_env(),
+        };
+
+        Self {
+            feedback_chat_id: parse_env("FEEDBACK_CHAT_ID").map(ChatId),
+            admin_chat_id: parse_env("ADMIN_CHAT_ID").map(ChatId),
+            defiller: env_flag("DEFILLER"),
+            filler_words: parse_filler_words(
+                &env::var("FILLER_WORDS").unwrap_or_else(|_| DEFAULT_FILLER_WORDS.to_string()),
+            ),
+            selftest_video_id: env::var("SELFTEST_VIDEO_ID").ok(),
+            selftest_strict: env_flag("SELFTEST_STRICT"),
+            templates: Arc::new(RwLock::new(templates)),
+            templates_path,
+            show_token_estimate: env_flag("SHOW_TOKEN_ESTIMATE"),
+            yt_user_agent: env::var("YT_USER_AGENT")
+                .ok()
+                .filter(|ua| !ua.trim().is_empty()),
+            group_prefix: env::var("GROUP_REQUIRE_PREFIX")
+                .ok()
+                .filter(|prefix| !prefix.trim().is_empty()),
+            cache_ttl: parse_env("CACHE_TTL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_CACHE_TTL),
+            cache_refresh_interval: parse_env("CACHE_REFRESH_INTERVAL_SECS")
+                .filter(|&secs| secs > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_REFRESH_INTERVAL),
+            filename_template: FilenameTemplate::from_env(),
+            punctuate: env_flag("PUNCTUATE"),
+            punctuate_api_url: env::var("PUNCTUATE_API_URL").ok(),
+            rate_limit_requests: parse_env("RATE_LIMIT_REQUESTS")
+                .unwrap_or(DEFAULT_RATE_LIMIT_REQUESTS),
+            rate_limit_window: parse_env("RATE_LIMIT_WINDOW_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_RATE_LIMIT_WINDOW),
+            rate_limit_scope: RateLimitScope::from_env(),
+            reply_in_thread: !matches!(
+                env::var("REPLY_IN_THREAD").as_deref(),
+                Ok("0") | Ok("false")
+            ),
+            delivery_mode: DeliveryMode::from_env(),
+            delivery_fallbacks: delivery::fallbacks_from_env(),
+            verbose: env_flag("VERBOSE"),
+            strip_inaudible: env_flag("STRIP_INAUDIBLE"),
+            min_caption_len: parse_env("MIN_CAPTION_LEN").unwrap_or(0),
+            dedup_transcripts: env_flag("DEDUP_TRANSCRIPTS"),
+            youtube_retry: RetryPolicy {
+                max_retries: parse_env("YOUTUBE_MAX_RETRIES")
+                    .unwrap_or(RetryPolicy::default().max_retries),
+                delay: parse_env("YOUTUBE_RETRY_DELAY_SECS")
+                    .map(Duration::from_secs)
+                    .unwrap_or(RetryPolicy::default().delay),
+            },
+            outbound_proxy: env::var("OUTBOUND_PROXY")
+                .ok()
+                .filter(|proxy| !proxy.trim().is_empty()),
+            stopwords: Stopwords::load(Path::new(
+                &env::var("STOPWORDS_DIR").unwrap_or_else(|_| DEFAULT_STOPWORDS_DIR.to_string()),
+            )),
+            message_footer: env::var("TELEGRAM_MESSAGE_FOOTER")
+                .ok()
+                .filter(|footer| !footer.is_empty()),
+            transcript_prefix: env::var("TRANSCRIPT_PREFIX").unwrap_or_default(),
+            transcript_suffix: env::var("TRANSCRIPT_SUFFIX").unwrap_or_default(),
+            default_lang: env::var("DEFAULT_LANG")
+                .ok()
+                .map(|lang| lang.trim().to_string())
+                .filter(|lang| !lang.is_empty()),
+            handler_timeout: match parse_env("HANDLER_TIMEOUT_SECS") {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => Some(DEFAULT_HANDLER_TIMEOUT),
+            },
+            history: env::var("HISTORY_DB_PATH").ok().and_then(|path| {
+                match History::open(Path::new(&path)) {
+                    Ok(history) => Some(Arc::new(history)),
+                    Err(e) => {
+                        log::error!("Cannot open history database {}: {}", path, e);
+                        None
+                    }
+                }
+            }),
+            gists: Arc::default(),
+            detect_live: env_flag("DETECT_LIVE"),
+            batch_concurrency: parse_env("BATCH_CONCURRENCY")
+                .filter(|&n| n > 0)
+                .unwrap_or(DEFAULT_BATCH_CONCURRENCY),
+            admin_ids: env::var("ADMIN_USER_IDS")
+                .map(|ids| parse_admin_ids(&ids))
+                .unwrap_or_default(),
+            health_addr: parse_env("HEALTH_ADDR"),
+            summarize: SummarizeApi::from_env(),
+            translation: DeepLBackend::from_env(),
+            #[cfg(feature = "email")]
+            email: EmailConfig::from_env().unwrap_or_else(|e| {
+                log::warn!("Email delivery disabled: {}", e);
+                None
+            }),
+        }
+    }
+
+    /// A snapshot of the current templates
+    pub fn templates(&self) -> MessageTemplates {
+        self.templates.read().unwrap().clone()
+    }
+
+    /// Language for requests that name none: `DEFAULT_LANG`, else English.
+    /// The fallback order used when it is missing is separate from this
+    pub fn default_lang(&self) -> &str {
+        self.default_lang
+            .as_deref()
+            .unwrap_or(FALLBACK_DEFAULT_LANG)
+    }
+
+    /// True when the user is listed in `ADMIN_USER_IDS`
+    pub fn is_admin(&self, user_id: u64) -> bool {
+        self.admin_ids.contains(&user_id)
+    }
+
+    /// Filler words for a language, trying `en-US` before falling back to `en`
+    pub fn fillers_for(&self, lang: &str) -> Option<&[String]> {
+        let primary = lang.split('-').next().unwrap_or(lang);
+        self.filler_words
+            .get(lang)
+            .or_else(|| self.filler_words.get(primary))
+            .map(Vec::as_slice)
+    }
+}
+
+/// A setting the bot needs is missing, which only the operator can fix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError(pub String);
+
+impl ConfigError {
+    /// The environment variable `name` is not set
+    pub fn missing(name: &str) -> Self {
+        Self(format!("{} environment variable not set", name))
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Reads the bot token from `TELOXIDE_TOKEN`, with an actionable message
+/// when it is missing instead of the panic `Bot::from_env` gives.
+pub fn bot_token() -> Result<String, ConfigError> {
+    env::var("TELOXIDE_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+        .ok_or_else(|| {
+            ConfigError("Set TELOXIDE_TOKEN to your bot token from @BotFather".to_string())
+        })
+}
+
+/// True when the variable is set to `1` or `true`.
+pub fn env_flag(name: &str) -> bool {
+    matches!(env::var(name).as_deref(), Ok("1") | Ok("true"))
+}
+
+fn parse_filler_words(spec: &str) -> HashMap<String, Vec<String>> {
+    spec.split(';')
+        .filter_map(|group| group.split_once(':'))
+        .map(|(lang, words)| {
+            let words = words
+                .split(',')
+                .map(|w| w.trim().to_lowercase())
+                .filter(|w| !w.is_empty())
+                .collect();
+            (lang.trim().to_string(), words)
+        })
+        .collect()
+}
+
+fn parse_admin_ids(ids: &str) -> Vec<u64> {
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .filter_map(|id| match id.parse() {
+            Ok(id) => Some(id),
+            Err(_) => {
+                log::warn!("Ignoring invalid admin user ID: {:?}", id);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Reads and parses an optional environment variable, warning on bad values.
+fn parse_env<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            log::warn!("Ignoring invalid value for {}: {:?}", name, value);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fillers_for_language() {
+        let config = AppConfig {
+            filler_words: parse_filler_words("en:uh, Um;de:äh"),
+            ..AppConfig::default()
+        };
+
+        assert_eq!(
+            config.fillers_for("en-GB"),
+            Some(&["uh".to_string(), "um".to_string()][..])
+        );
+        assert_eq!(config.fillers_for("de"), Some(&["äh".to_string()][..]));
+        assert_eq!(config.fillers_for("ja"), None);
+    }
+
+    #[test]
+    fn test_yt_user_agent_from_env() {
+        env::set_var("YT_USER_AGENT", "Mozilla/5.0 (X11; Linux x86_64)");
+        assert_eq!(
+            AppConfig::from_env().yt_user_agent.as_deref(),
+            Some("Mozilla/5.0 (X11; Linux x86_64)")
+        );
+
+        env::set_var("YT_USER_AGENT", " ");
+        assert_eq!(AppConfig::from_env().yt_user_agent, None);
+        env::remove_var("YT_USER_AGENT");
+    }
+
+    #[test]
+    fn test_missing_bot_token() {
+        env::remove_var("TELOXIDE_TOKEN");
+        assert_eq!(
+            bot_token().unwrap_err().to_string(),
+            "Set TELOXIDE_TOKEN to your bot token from @BotFather"
+        );
+        env::set_var("TELOXIDE_TOKEN", " ");
+        assert!(bot_token().is_err());
+
+        env::set_var("TELOXIDE_TOKEN", "123:abc");
+        assert_eq!(bot_token().unwrap(), "123:abc");
+        env::remove_var("TELOXIDE_TOKEN");
+    }
+
+    #[test]
+    fn test_admin_ids() {
+        let config = AppConfig {
+            admin_ids: parse_admin_ids("12, 34,oops,"),
+            ..AppConfig::default()
+        };
+
+        assert_eq!(config.admin_ids, vec![12, 34]);
+        assert!(config.is_admin(34));
+        assert!(!config.is_admin(56));
+    }
+
+    #[test]
+    fn test_default_lang() {
+        assert_eq!(AppConfig::default().default_lang(), "en");
+
+        env::set_var("DEFAULT_LANG", "es");
+        assert_eq!(AppConfig::from_env().default_lang(), "es");
+        env::set_var("DEFAULT_LANG", "");
+        assert_eq!(AppConfig::from_env().default_lang(), "en");
+        env::remove_var("DEFAULT_LANG");
+    }
+}
@@ -0,0 +1,98 @@
+use crate::history::HistoryEntry;
+use crate::ratelimit::{RateLimitKey, RateLimiter};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::time::Duration;
+use teloxide::types::UserId;
+
+/// How often a user may run `/export`.
+const EXPORT_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Everything stored about one user, as sent by `/export`. The bot keeps no
+/// preferences or per-user statistics, so the upload history is all there is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DataExport {
+    pub user_id: u64,
+    /// Seconds since the Unix epoch
+    pub exported_at: i64,
+    /// Transcripts the user uploaded, oldest first
+    pub history: Vec<HistoryEntry>,
+}
+
+impl DataExport {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// `my_data_<user_id>_<date>.json`
+pub fn file_name(user_id: u64, date: NaiveDate) -> String {
+    format!("my_data_{}_{}.json", user_id, date)
+}
+
+/// Allows each user one export a day.
+pub struct ExportLimiter(RateLimiter);
+
+impl Default for ExportLimiter {
+    fn default() -> Self {
+        Self(RateLimiter::new(1, EXPORT_WINDOW))
+    }
+}
+
+impl ExportLimiter {
+    /// Records an export, or returns how long the user has to wait
+    pub fn check(&self, user_id: UserId) -> Result<(), Duration> {
+        self.0.check(RateLimitKey::User(user_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_json() {
+        let export = DataExport {
+            user_id: 42,
+            exported_at: 1_700_000_100,
+            history: vec![HistoryEntry {
+                user_id: 42,
+                video_id: "abc123".to_string(),
+                lang: "en".to_string(),
+                upload_url: "https://pastebin.com/raw/xyz".to_string(),
+                timestamp: 1_700_000_000,
+            }],
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&export.to_json().unwrap()).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "user_id": 42,
+                "exported_at": 1_700_000_100,
+                "history": [{
+                    "user_id": 42,
+                    "video_id": "abc123",
+                    "lang": "en",
+                    "upload_url": "https://pastebin.com/raw/xyz",
+                    "timestamp": 1_700_000_000,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_file_name() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        assert_eq!(file_name(42, date), "my_data_42_2024-03-09.json");
+    }
+
+    #[test]
+    fn test_one_export_a_day() {
+        let limiter = ExportLimiter::default();
+        assert!(limiter.check(UserId(42)).is_ok());
+        assert!(limiter.check(UserId(42)).is_err());
+        assert!(limiter.check(UserId(7)).is_ok());
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use teloxide::types::{ChatId, MessageId};
+
+/// How many recent messages are remembered to spot redeliveries.
+const RECENT_MESSAGE_CAPACITY: usize = 1024;
+
+type MessageKey = (ChatId, MessageId);
+
+/// Remembers the most recent messages so updates Telegram redelivers after a
+/// reconnect are not processed twice. The oldest entry is dropped once full.
+pub struct RecentMessages {
+    capacity: usize,
+    seen: Mutex<(HashSet<MessageKey>, VecDeque<MessageKey>)>,
+}
+
+impl Default for RecentMessages {
+    fn default() -> Self {
+        Self::with_capacity(RECENT_MESSAGE_CAPACITY)
+    }
+}
+
+impl RecentMessages {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seen: Mutex::new((HashSet::new(), VecDeque::new())),
+        }
+    }
+
+    /// Records a message, returning false if it was already seen
+    pub fn first_time(&self, chat_id: ChatId, message_id: MessageId) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        let (set, order) = &mut *seen;
+        if !set.insert((chat_id, message_id)) {
+            return false;
+        }
+
+        order.push_back((chat_id, message_id));
+        if order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                set.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicates_are_detected() {
+        let recent = RecentMessages::default();
+
+        assert!(recent.first_time(ChatId(1), MessageId(10)));
+        assert!(!recent.first_time(ChatId(1), MessageId(10)));
+        assert!(recent.first_time(ChatId(2), MessageId(10)));
+    }
+
+    #[test]
+    fn test_capacity_is_bounded() {
+        let recent = RecentMessages::with_capacity(2);
+
+        for id in 1..=3 {
+            assert!(recent.first_time(ChatId(1), MessageId(id)));
+        }
+        // The oldest message was forgotten, the newer ones are still known
+        assert!(recent.first_time(ChatId(1), MessageId(1)));
+        assert!(!recent.first_time(ChatId(1), MessageId(3)));
+        assert_eq!(recent.seen.lock().unwrap().1.len(), 2);
+    }
+}
@@ -0,0 +1,320 @@
+use crate::export::OutputFormat;
+use crate::formatter::split_safe_utf8_words;
+use std::env;
+use std::future::Future;
+use teloxide::{ApiError, RequestError};
+
+/// Telegram rejects text messages longer than this many bytes.
+pub const TELEGRAM_MESSAGE_LIMIT: usize = 4096;
+
+/// How many times a single send is retried after Telegram flood control.
+const MAX_RETRY_AFTER_ATTEMPTS: usize = 3;
+
+/// How a formatted transcript reaches the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeliveryMode {
+    /// Upload to Pastebin and reply with the link
+    Pastebin,
+    /// Send the text directly as a series of chat messages
+    Messages,
+    /// Send the text as a `.txt` document
+    Document,
+}
+
+impl DeliveryMode {
+    /// Subtitles and JSON are more useful as files; text reads fine as a paste
+    pub fn default_for(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Txt => DeliveryMode::Pastebin,
+            OutputFormat::Srt | OutputFormat::Vtt | OutputFormat::Json => DeliveryMode::Document,
+        }
+    }
+
+    /// The mode set with `DELIVERY_MODE`; unknown values are ignored
+    pub fn from_env() -> Option<Self> {
+        env::var("DELIVERY_MODE")
+            .ok()
+            .and_then(|name| Self::parse(&name))
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "pastebin" => Some(DeliveryMode::Pastebin),
+            "messages" => Some(DeliveryMode::Messages),
+            "document" => Some(DeliveryMode::Document),
+            _ => None,
+        }
+    }
+
+    /// The `DELIVERY_MODE` value selecting this mode
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeliveryMode::Pastebin => "pastebin",
+            DeliveryMode::Messages => "messages",
+            DeliveryMode::Document => "document",
+        }
+    }
+}
+
+/// How `format` is sent: as the request asks, else as `configured`
+/// (`DELIVERY_MODE`) says, else [`DeliveryMode::default_for`] the format.
+pub fn choose_mode(
+    format: OutputFormat,
+    requested: Option<DeliveryMode>,
+    configured: Option<DeliveryMode>,
+) -> DeliveryMode {
+    requested
+        .or(configured)
+        .unwrap_or_else(|| DeliveryMode::default_for(format))
+}
+
+/// Modes tried in turn when a document cannot be sent, from
+/// `DELIVERY_FALLBACK` (e.g. `messages,pastebin`). Defaults to messages.
+pub fn fallbacks_from_env() -> Vec<DeliveryMode> {
+    match env::var("DELIVERY_FALLBACK") {
+        Ok(spec) => parse_fallbacks(&spec),
+        Err(_) => vec![DeliveryMode::Messages],
+    }
+}
+
+fn parse_fallbacks(spec: &str) -> Vec<DeliveryMode> {
+    spec.split(',')
+        .filter(|name| !name.trim().is_empty())
+        .filter_map(|name| {
+            let mode = DeliveryMode::parse(name);
+            if mode.is_none() {
+                log::warn!("Ignoring unknown delivery fallback {:?}", name);
+            }
+            mode
+        })
+        .collect()
+}
+
+/// Delivers with `mode`, then with each of `fallbacks` while delivery keeps
+/// failing. Returns the mode that worked, or the last error.
+pub async fn deliver_with_fallbacks<F, Fut>(
+    mode: DeliveryMode,
+    fallbacks: &[DeliveryMode],
+    mut deliver: F,
+) -> Result<DeliveryMode, RequestError>
+where
+    F: FnMut(DeliveryMode) -> Fut,
+    Fut: Future<Output = Result<(), RequestError>>,
+{
+    let mut current = mode;
+    let mut result = deliver(mode).await;
+    for &fallback in fallbacks.iter().filter(|&&fallback| fallback != mode) {
+        let Err(e) = &result else { break };
+        log::warn!(
+            "Delivery by {} failed, trying {}: {}",
+            current.name(),
+            fallback.name(),
+            e
+        );
+        current = fallback;
+        result = deliver(fallback).await;
+    }
+    result.map(|()| current)
+}
+
+/// Runs a Telegram request, sleeping and retrying when Telegram answers with
+/// `RetryAfter` instead of giving up on the first flood-control response.
+pub async fn send_with_retry<F, Fut, T>(mut send: F) -> Result<T, RequestError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RequestError>>,
+{
+    let mut attempts = 0;
+    loop {
+        match send().await {
+            Err(RequestError::RetryAfter(secs)) if attempts < MAX_RETRY_AFTER_ATTEMPTS => {
+                attempts += 1;
+                log::warn!("Hit Telegram flood control, retrying in {}", secs);
+                tokio::time::sleep(secs.duration()).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Sends `text` as one message, and if Telegram rejects it as too long,
+/// again in chunks of at most `limit` bytes split between words.
+pub async fn send_split_if_too_long<F, Fut>(
+    text: &str,
+    limit: usize,
+    mut send: F,
+) -> Result<(), RequestError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<(), RequestError>>,
+{
+    match send(text.to_string()).await {
+        Err(RequestError::Api(ApiError::MessageIsTooLong)) => {
+            log::warn!("Message of {} bytes was too long, splitting it", text.len());
+            for chunk in split_safe_utf8_words(text, limit) {
+                send(chunk.to_string()).await?;
+            }
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use teloxide::types::Seconds;
+
+    #[tokio::test]
+    async fn test_send_with_retry_after_flood_control() {
+        let calls = AtomicUsize::new(0);
+
+        let result = send_with_retry(|| async {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(RequestError::RetryAfter(Seconds::from_seconds(0)))
+            } else {
+                Ok("chunk delivered")
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "chunk delivered");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_document_falls_back_to_messages() {
+        let attempts = std::sync::Mutex::new(Vec::new());
+
+        let delivered_by = deliver_with_fallbacks(
+            DeliveryMode::Document,
+            &parse_fallbacks("messages, pastebin"),
+            |mode| {
+                attempts.lock().unwrap().push(mode);
+                async move {
+                    match mode {
+                        DeliveryMode::Document => Err(RequestError::Io(io_error())),
+                        _ => Ok(()),
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(delivered_by.unwrap(), DeliveryMode::Messages);
+        assert_eq!(
+            *attempts.lock().unwrap(),
+            vec![DeliveryMode::Document, DeliveryMode::Messages]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fallbacks_exhausted() {
+        let result = deliver_with_fallbacks(
+            DeliveryMode::Document,
+            &[DeliveryMode::Document, DeliveryMode::Messages],
+            |_| async { Err(RequestError::Io(io_error())) },
+        )
+        .await;
+        assert!(matches!(result, Err(RequestError::Io(_))));
+
+        let result = deliver_with_fallbacks(DeliveryMode::Document, &[], |_| async { Ok(()) });
+        assert_eq!(result.await.unwrap(), DeliveryMode::Document);
+    }
+
+    #[test]
+    fn test_default_mode_per_format() {
+        assert_eq!(
+            choose_mode(OutputFormat::Txt, None, None),
+            DeliveryMode::Pastebin
+        );
+        for format in [OutputFormat::Srt, OutputFormat::Vtt, OutputFormat::Json] {
+            assert_eq!(choose_mode(format, None, None), DeliveryMode::Document);
+        }
+    }
+
+    #[test]
+    fn test_explicit_mode_wins() {
+        let configured = Some(DeliveryMode::Messages);
+        assert_eq!(
+            choose_mode(OutputFormat::Srt, None, configured),
+            DeliveryMode::Messages
+        );
+        // A request's own choice beats DELIVERY_MODE
+        assert_eq!(
+            choose_mode(OutputFormat::Srt, Some(DeliveryMode::Pastebin), configured),
+            DeliveryMode::Pastebin
+        );
+        assert_eq!(
+            choose_mode(OutputFormat::Txt, Some(DeliveryMode::Document), None),
+            DeliveryMode::Document
+        );
+    }
+
+    #[test]
+    fn test_parse_fallbacks() {
+        assert_eq!(
+            parse_fallbacks("pastebin,messages,carrier pigeon,"),
+            vec![DeliveryMode::Pastebin, DeliveryMode::Messages]
+        );
+        assert!(parse_fallbacks("").is_empty());
+    }
+
+    /// Sends like Telegram would, refusing messages over the limit.
+    async fn split_sends(text: &str) -> (Result<(), RequestError>, Vec<String>) {
+        let sent = std::sync::Mutex::new(Vec::new());
+        let result = send_split_if_too_long(text, TELEGRAM_MESSAGE_LIMIT, |chunk| {
+            let accepted = chunk.len() <= TELEGRAM_MESSAGE_LIMIT;
+            if accepted {
+                sent.lock().unwrap().push(chunk);
+            }
+            async move {
+                match accepted {
+                    true => Ok(()),
+                    false => Err(RequestError::Api(ApiError::MessageIsTooLong)),
+                }
+            }
+        })
+        .await;
+        (result, sent.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_too_long_message_is_split() {
+        let text = "word ".repeat(2000);
+
+        let (result, sent) = split_sends(&text).await;
+
+        assert!(result.is_ok());
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent.concat(), text);
+        assert!(sent.iter().all(|chunk| chunk.ends_with(' ')));
+    }
+
+    #[tokio::test]
+    async fn test_short_message_is_sent_whole() {
+        let (result, sent) = split_sends("hello").await;
+
+        assert!(result.is_ok());
+        assert_eq!(sent, vec!["hello"]);
+    }
+
+    fn io_error() -> std::io::Error {
+        std::io::Error::other("file too large")
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_gives_up() {
+        let calls = AtomicUsize::new(0);
+
+        let result: Result<(), _> = send_with_retry(|| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(RequestError::RetryAfter(Seconds::from_seconds(0)))
+        })
+        .await;
+
+        assert!(matches!(result, Err(RequestError::RetryAfter(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), MAX_RETRY_AFTER_ATTEMPTS + 1);
+    }
+}
@@ -0,0 +1,100 @@
+use serde_json::json;
+use std::fs;
+
+/// Runtime figures reported by `/admininfo`, so problems in production can
+/// be looked into without shell access.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics {
+    /// Resident memory, when `/proc/self/status` can be read
+    pub memory_kb: Option<u64>,
+    /// Tasks alive in the Tokio runtime
+    pub tokio_tasks: usize,
+    /// Transcripts in the cache, `None` without one
+    pub cache_entries: Option<usize>,
+    /// Users, chats or pairs the rate limiter is tracking
+    pub rate_limit_keys: usize,
+    /// Size of the history database's write-ahead log, `None` without one
+    pub wal_bytes: Option<u64>,
+}
+
+impl Diagnostics {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "memory_kb": self.memory_kb,
+            "tokio_tasks": self.tokio_tasks,
+            "cache_entries": self.cache_entries,
+            "rate_limit_keys": self.rate_limit_keys,
+            "wal_bytes": self.wal_bytes,
+            "version": env!("CARGO_PKG_VERSION"),
+            "commit": env!("GIT_COMMIT"),
+            "built_at": built_at(),
+        })
+    }
+}
+
+/// When this binary was built, as recorded by `build.rs`.
+pub fn built_at() -> String {
+    env!("BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map(|date| date.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Resident memory of this process, read from `/proc` (Linux only).
+pub fn memory_kb() -> Option<u64> {
+    parse_vm_rss(&fs::read_to_string("/proc/self/status").ok()?)
+}
+
+/// Tasks alive in the runtime this is called from.
+pub fn tokio_tasks() -> usize {
+    tokio::runtime::Handle::current()
+        .metrics()
+        .num_alive_tasks()
+}
+
+/// The `VmRSS` line of `/proc/<pid>/status`, in kB.
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vm_rss() {
+        let status = "Name:\ttofuboi\nVmPeak:\t  300000 kB\nVmRSS:\t   51234 kB\nThreads:\t9\n";
+        assert_eq!(parse_vm_rss(status), Some(51234));
+        assert_eq!(parse_vm_rss("Name:\ttofuboi\n"), None);
+    }
+
+    #[test]
+    fn test_json_fields() {
+        let diagnostics = Diagnostics {
+            memory_kb: Some(51234),
+            tokio_tasks: 7,
+            cache_entries: None,
+            rate_limit_keys: 3,
+            wal_bytes: Some(4096),
+        };
+
+        let json = diagnostics.to_json();
+
+        assert_eq!(json["memory_kb"], 51234);
+        assert_eq!(json["tokio_tasks"], 7);
+        assert!(json["cache_entries"].is_null());
+        assert_eq!(json["rate_limit_keys"], 3);
+        assert_eq!(json["wal_bytes"], 4096);
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["commit"], env!("GIT_COMMIT"));
+        assert_eq!(json["built_at"], built_at());
+    }
+}
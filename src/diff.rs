@@ -0,0 +1,96 @@
+use crate::formatter::normalize_text;
+use similar::TextDiff;
+use ytranscript::TranscriptResponse;
+
+/// Unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// One caption per line with entities decoded and whitespace collapsed, so
+/// re-uploads that only differ in timing or spacing compare equal.
+fn normalized_lines(transcript: &[TranscriptResponse]) -> String {
+    let mut text = String::new();
+    for entry in transcript {
+        let line = normalize_text(&entry.text)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !line.is_empty() {
+            text.push_str(&line);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Builds a line-based unified diff of two transcripts, labelled `old_name`
+/// and `new_name`. Returns an empty string when their text is the same.
+pub fn transcript_diff(
+    old: &[TranscriptResponse],
+    new: &[TranscriptResponse],
+    old_name: &str,
+    new_name: &str,
+) -> String {
+    let (old, new) = (normalized_lines(old), normalized_lines(new));
+    if old == new {
+        return String::new();
+    }
+
+    TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_name, new_name)
+        .missing_newline_hint(false)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::test_entry;
+
+    fn entries(lines: &[&str]) -> Vec<TranscriptResponse> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, text)| test_entry(text, i as f64, 1.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_single_changed_line() {
+        let old = entries(&["one", "two", "three", "four"]);
+        let new = entries(&["one", "two", "3", "four"]);
+
+        let diff = transcript_diff(&old, &new, "a", "b");
+
+        assert!(diff.starts_with("--- a\n+++ b\n"));
+        let changed: Vec<&str> = diff
+            .lines()
+            .skip(2)
+            .filter(|line| line.starts_with('-') || line.starts_with('+'))
+            .collect();
+        assert_eq!(changed, vec!["-three", "+3"]);
+    }
+
+    #[test]
+    fn test_identical_text_has_no_diff() {
+        // Timing and spacing differences alone are not changes
+        let old = entries(&["Tom &amp; Jerry", "bye"]);
+        let mut new = entries(&["Tom  & Jerry ", "bye"]);
+        new[1].offset = 42.0;
+
+        assert_eq!(transcript_diff(&old, &new, "a", "b"), "");
+    }
+
+    #[test]
+    fn test_different_lengths() {
+        let old = entries(&["one"]);
+        let new = entries(&["one", "two", "three"]);
+
+        let diff = transcript_diff(&old, &new, "a", "b");
+        assert!(diff.contains("+two\n+three\n"));
+
+        let diff = transcript_diff(&old, &[], "a", "b");
+        assert!(diff.contains("-one\n"));
+    }
+}
@@ -0,0 +1,185 @@
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+use std::fmt::Display;
+
+/// SMTP submission port, used with STARTTLS.
+const DEFAULT_SMTP_PORT: u16 = 587;
+
+/// Where transcripts requested with `email=<addr>` are sent from.
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    host: String,
+    port: u16,
+    username: String,
+    password: String,
+    from: Mailbox,
+}
+
+impl EmailConfig {
+    /// `None` unless `EMAIL_BACKEND=smtp`, which also needs `SMTP_HOST`,
+    /// `SMTP_USERNAME`, `SMTP_PASSWORD` and `EMAIL_FROM`; `SMTP_PORT`
+    /// defaults to 587. The error names the first setting that is missing
+    /// or invalid.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match env::var("EMAIL_BACKEND").as_deref() {
+            Ok("smtp") => {}
+            Ok(other) => {
+                return Err(format!(
+                    "EMAIL_BACKEND '{}' is not supported, only 'smtp' is",
+                    other
+                ))
+            }
+            Err(_) => return Ok(None),
+        }
+        let var = |name: &str| {
+            env::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .ok_or_else(|| format!("{} is not set", name))
+        };
+
+        let port = match env::var("SMTP_PORT") {
+            Ok(port) => port
+                .parse()
+                .map_err(|_| format!("SMTP_PORT '{}' is not a port number", port))?,
+            Err(_) => DEFAULT_SMTP_PORT,
+        };
+        let from = var("EMAIL_FROM")?;
+        Ok(Some(Self {
+            host: var("SMTP_HOST")?,
+            port,
+            username: var("SMTP_USERNAME")?,
+            password: var("SMTP_PASSWORD")?,
+            from: from
+                .parse()
+                .map_err(|_| format!("EMAIL_FROM '{}' is not a valid address", from))?,
+        }))
+    }
+
+    /// An SMTP connection to the configured server, upgraded with STARTTLS.
+    pub fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, String> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)
+            .map_err(|e| e.to_string())?
+            .port(self.port)
+            .credentials(Credentials::new(
+                self.username.clone(),
+                self.password.clone(),
+            ))
+            .build();
+        Ok(transport)
+    }
+
+    /// The transcript as a text attachment, with a line about it as the body.
+    pub fn build_message(
+        &self,
+        to: Address,
+        subject: &str,
+        file_name: &str,
+        transcript: &str,
+    ) -> Result<Message, String> {
+        build_message(self.from.clone(), to, subject, file_name, transcript)
+    }
+}
+
+/// Checks an address typed by the user.
+pub fn parse_address(address: &str) -> Result<Address, String> {
+    address
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid email address", address))
+}
+
+fn build_message(
+    from: Mailbox,
+    to: Address,
+    subject: &str,
+    file_name: &str,
+    transcript: &str,
+) -> Result<Message, String> {
+    let note = SinglePart::plain(format!("The transcript is attached as {}.", file_name));
+    let attachment = Attachment::new(file_name.to_string())
+        .body(transcript.to_string(), ContentType::TEXT_PLAIN);
+
+    Message::builder()
+        .from(from)
+        .to(Mailbox::new(None, to))
+        .subject(subject)
+        .multipart(MultiPart::mixed().singlepart(note).singlepart(attachment))
+        .map_err(|e| e.to_string())
+}
+
+/// Sends through any transport, so tests can pass lettre's stub.
+pub async fn send<T>(transport: &T, message: Message) -> Result<(), String>
+where
+    T: AsyncTransport + Sync,
+    T::Error: Display,
+{
+    transport
+        .send(message)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lettre::transport::stub::AsyncStubTransport;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            parse_address(" reader@example.com ").unwrap().to_string(),
+            "reader@example.com"
+        );
+        for invalid in ["", "reader", "reader@", "@example.com", "a b@example.com"] {
+            assert!(
+                parse_address(invalid).is_err(),
+                "{:?} was accepted",
+                invalid
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcript_is_attached() {
+        let message = build_message(
+            "bot@example.com".parse().unwrap(),
+            parse_address("reader@example.com").unwrap(),
+            "Transcript abc123 (en)",
+            "abc123_en.txt",
+            "[00:00] Hello",
+        )
+        .unwrap();
+        let transport = AsyncStubTransport::new_ok();
+
+        send(&transport, message).await.unwrap();
+
+        let messages = transport.messages().await;
+        assert_eq!(messages.len(), 1);
+        let (envelope, raw) = &messages[0];
+        assert_eq!(envelope.to()[0].to_string(), "reader@example.com");
+        assert!(raw.contains("Subject: Transcript abc123 (en)"));
+        assert!(raw.contains("filename=\"abc123_en.txt\""));
+        assert!(raw.contains("[00:00] Hello"));
+    }
+
+    #[tokio::test]
+    async fn test_transport_errors_are_reported() {
+        let message = build_message(
+            "bot@example.com".parse().unwrap(),
+            parse_address("reader@example.com").unwrap(),
+            "Transcript",
+            "t.txt",
+            "text",
+        )
+        .unwrap();
+
+        assert!(send(&AsyncStubTransport::new_error(), message)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::bot_ext::BotExt;
+use crate::config::ConfigError;
+use crate::metrics::{self, Counter};
+use futures::future::BoxFuture;
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::error_handlers::ErrorHandler;
+use teloxide::prelude::*;
+use teloxide::{ApiError, RequestError};
+
+type HandlerError = Box<dyn Error + Send + Sync>;
+
+/// How an error that escaped a handler is reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    /// Telegram asked the bot to wait this long before sending again
+    RetryAfter(Duration),
+    /// Telegram or another service could not be reached
+    Network,
+    /// The bot token was rejected or a setting is missing, which needs the
+    /// operator rather than a retry
+    Config,
+    Other,
+}
+
+impl ErrorKind {
+    /// Classifies `error` by the first cause in its chain that says more
+    /// than a plain message.
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        for cause in std::iter::successors(Some(error), |&e| e.source()) {
+            if let Some(e) = cause.downcast_ref::<RequestError>() {
+                return match e {
+                    RequestError::RetryAfter(secs) => ErrorKind::RetryAfter(secs.duration()),
+                    RequestError::Network(_) | RequestError::Io(_) => ErrorKind::Network,
+                    RequestError::Api(ApiError::InvalidToken) => ErrorKind::Config,
+                    _ => ErrorKind::Other,
+                };
+            }
+            if cause.is::<reqwest::Error>() || cause.is::<io::Error>() {
+                return ErrorKind::Network;
+            }
+            if cause.is::<ConfigError>() {
+                return ErrorKind::Config;
+            }
+        }
+        ErrorKind::Other
+    }
+
+    /// Counter bumped for errors of this kind; waits Telegram asks for are
+    /// not failures
+    fn counter(self) -> Option<Counter> {
+        match self {
+            ErrorKind::RetryAfter(_) => None,
+            ErrorKind::Network => Some(Counter::NetworkErrors),
+            ErrorKind::Config => Some(Counter::ConfigErrors),
+            ErrorKind::Other => Some(Counter::HandlerErrors),
+        }
+    }
+
+    /// Whether the admin chat is told straight away
+    fn pages_admin(self) -> bool {
+        self == ErrorKind::Config
+    }
+}
+
+/// Dispatcher error handler that logs by [`ErrorKind`], counts errors in
+/// the metrics and pages `ADMIN_CHAT_ID` about ones only the operator can
+/// fix. `RetryAfter` is waited out and otherwise ignored.
+pub struct ErrorReporter {
+    bot: Bot,
+    admin_chat_id: Option<ChatId>,
+}
+
+impl ErrorReporter {
+    pub fn new(bot: Bot, admin_chat_id: Option<ChatId>) -> Arc<Self> {
+        Arc::new(Self { bot, admin_chat_id })
+    }
+
+    async fn report(&self, error: HandlerError) {
+        let kind = ErrorKind::of(error.as_ref());
+        if let Some(counter) = kind.counter() {
+            metrics::increment(counter);
+        }
+        match kind {
+            ErrorKind::RetryAfter(wait) => {
+                log::info!("Telegram asked to wait {:?}: {}", wait, error);
+                tokio::time::sleep(wait).await;
+            }
+            ErrorKind::Network => log::warn!("Network error in handler: {}", error),
+            ErrorKind::Config | ErrorKind::Other => log::error!("Error in handler: {}", error),
+        }
+
+        if kind.pages_admin() {
+            page_admin(&self.bot, self.admin_chat_id, error.as_ref()).await;
+        }
+    }
+}
+
+/// For errors a handler answers the user about instead of returning: counts
+/// and pages about a missing setting as [`ErrorReporter`] would, so the
+/// operator still hears of it. Returns whether `error` was one.
+pub async fn report_config_error(
+    bot: &Bot,
+    admin_chat_id: Option<ChatId>,
+    error: &(dyn Error + Send + Sync + 'static),
+) -> bool {
+    if ErrorKind::of(error) != ErrorKind::Config {
+        return false;
+    }
+    metrics::increment(Counter::ConfigErrors);
+    log::error!("Setting missing in handler: {}", error);
+    page_admin(bot, admin_chat_id, error).await;
+    true
+}
+
+async fn page_admin(bot: &Bot, admin_chat_id: Option<ChatId>, error: &(dyn Error + Send + Sync)) {
+    let Some(chat_id) = admin_chat_id else {
+        return;
+    };
+    let text = format!("Tofuboi needs attention: {}", error);
+    if let Err(e) = bot.send_with_footer(chat_id, text, None, None).await {
+        log::warn!("Failed to page the admin chat: {}", e);
+    }
+}
+
+impl ErrorHandler<HandlerError> for ErrorReporter {
+    fn handle_error(self: Arc<Self>, error: HandlerError) -> BoxFuture<'static, ()> {
+        Box::pin(async move { self.report(error).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::UploadError;
+    use teloxide::types::Seconds;
+
+    #[test]
+    fn test_telegram_errors_are_classified() {
+        let kind = |e: RequestError| ErrorKind::of(&e);
+
+        assert_eq!(
+            kind(RequestError::RetryAfter(Seconds::from_seconds(3))),
+            ErrorKind::RetryAfter(Duration::from_secs(3))
+        );
+        assert_eq!(
+            kind(RequestError::Io(io::Error::other("reset"))),
+            ErrorKind::Network
+        );
+        assert_eq!(
+            kind(RequestError::Api(ApiError::InvalidToken)),
+            ErrorKind::Config
+        );
+        assert_eq!(
+            kind(RequestError::Api(ApiError::BotBlocked)),
+            ErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn test_boxed_errors_are_classified() {
+        let boxed: HandlerError = Box::new(RequestError::Api(ApiError::InvalidToken));
+        assert_eq!(ErrorKind::of(boxed.as_ref()), ErrorKind::Config);
+
+        let boxed: HandlerError = Box::new(io::Error::other("timed out"));
+        assert_eq!(ErrorKind::of(boxed.as_ref()), ErrorKind::Network);
+
+        let boxed: HandlerError = Box::new(ConfigError::missing("PASTEBIN_KEY"));
+        assert_eq!(ErrorKind::of(boxed.as_ref()), ErrorKind::Config);
+
+        // Only the type counts, not a message that happens to look alike
+        let boxed: HandlerError = "PASTEBIN_KEY environment variable not set".into();
+        assert_eq!(ErrorKind::of(boxed.as_ref()), ErrorKind::Other);
+
+        let boxed: HandlerError = "something else broke".into();
+        assert_eq!(ErrorKind::of(boxed.as_ref()), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_only_config_errors_page_the_admin() {
+        assert!(ErrorKind::Config.pages_admin());
+        for kind in [
+            ErrorKind::RetryAfter(Duration::ZERO),
+            ErrorKind::Network,
+            ErrorKind::Other,
+        ] {
+            assert!(!kind.pages_admin(), "{:?} pages the admin", kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_counted_by_kind() {
+        let reporter = ErrorReporter::new(Bot::new("token"), None);
+        let cases: [(HandlerError, Counter); 3] = [
+            (
+                Box::new(RequestError::Io(io::Error::other("reset"))),
+                Counter::NetworkErrors,
+            ),
+            (
+                Box::new(RequestError::Api(ApiError::InvalidToken)),
+                Counter::ConfigErrors,
+            ),
+            ("something else broke".into(), Counter::HandlerErrors),
+        ];
+
+        for (error, counter) in cases {
+            let before = metrics::get(counter);
+            reporter.clone().handle_error(error).await;
+            assert!(metrics::get(counter) > before, "{:?} not counted", counter);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_swallowed_config_errors_are_reported() {
+        let bot = Bot::new("token");
+        let before = metrics::get(Counter::ConfigErrors);
+
+        // Wrapped the way a failed upload reaches the user
+        let upload: HandlerError =
+            Box::new(UploadError(Box::new(ConfigError::missing("PASTEBIN_KEY"))));
+        assert!(report_config_error(&bot, None, upload.as_ref()).await);
+        assert!(metrics::get(Counter::ConfigErrors) > before);
+
+        let other: HandlerError = "Pastebin is down".into();
+        assert!(!report_config_error(&bot, None, other.as_ref()).await);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_is_swallowed() {
+        assert!(ErrorKind::RetryAfter(Duration::ZERO).counter().is_none());
+
+        // Returns once the wait is over, without a bot request to fail on
+        ErrorReporter::new(Bot::new("token"), Some(ChatId(-1001)))
+            .handle_error(Box::new(RequestError::RetryAfter(Seconds::from_seconds(0))))
+            .await;
+    }
+}
@@ -0,0 +1,37 @@
+//! Transcript formats that are sent as documents rather than as a paste.
+
+pub mod pdf;
+pub mod subtitles;
+
+/// A format named in `format=srt,txt`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Plain text, delivered like any transcript (a paste link by default)
+    Txt,
+    Srt,
+    Vtt,
+    /// The unmodified ytranscript output
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "txt" => Some(OutputFormat::Txt),
+            "srt" => Some(OutputFormat::Srt),
+            "vtt" => Some(OutputFormat::Vtt),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// File extension of the format's documents
+    pub fn ext(&self) -> &'static str {
+        match self {
+            OutputFormat::Txt => "txt",
+            OutputFormat::Srt => "srt",
+            OutputFormat::Vtt => "vtt",
+            OutputFormat::Json => "json",
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::formatter::{format_offset, normalize_text};
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use ytranscript::TranscriptResponse;
+
+const PAGE_WIDTH: Mm = Mm(210.0);
+const PAGE_HEIGHT: Mm = Mm(297.0);
+const MARGIN: f32 = 20.0;
+const LINE_HEIGHT: f32 = 5.0;
+const BODY_FONT_SIZE: f32 = 10.0;
+
+/// Characters that fit on one body line in 10pt Helvetica between margins.
+const CHARS_PER_LINE: usize = 90;
+
+/// Body lines per page, leaving room for the page number at the bottom.
+const LINES_PER_PAGE: usize = 48;
+
+/// Indent of the wrapped part of a caption, lining it up after the timestamp.
+const CONTINUATION_INDENT: &str = "        ";
+
+/// Why a transcript could not be rendered as a PDF.
+#[derive(Debug)]
+pub struct PdfError(printpdf::Error);
+
+impl fmt::Display for PdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not create PDF: {}", self.0)
+    }
+}
+
+impl std::error::Error for PdfError {}
+
+impl From<printpdf::Error> for PdfError {
+    fn from(e: printpdf::Error) -> Self {
+        PdfError(e)
+    }
+}
+
+/// Renders a transcript as an A4 PDF: a title page with the video ID and
+/// today's date, then the captions with timestamps, paginated, with page
+/// numbers. The built-in Helvetica font only covers Latin-1, so other
+/// scripts are not rendered faithfully.
+pub fn format_pdf(
+    transcript: &[TranscriptResponse],
+    video_id: &str,
+    lang: &str,
+) -> Result<Vec<u8>, PdfError> {
+    let title = format!("Transcript of {}", video_id);
+    let (doc, page, layer) = PdfDocument::new(&title, PAGE_WIDTH, PAGE_HEIGHT, "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let pages = paginate(&body_lines(transcript));
+    let total_pages = pages.len() + 1;
+
+    let title_layer = doc.get_page(page).get_layer(layer);
+    title_layer.use_text(&title, 24.0, Mm(MARGIN), Mm(200.0), &bold);
+    let details = [
+        format!("Video: https://www.youtube.com/watch?v={}", video_id),
+        format!("Language: {}", lang),
+        format!("Date: {}", today()),
+        format!("Captions: {}", transcript.len()),
+    ];
+    for (i, line) in details.iter().enumerate() {
+        let y = 185.0 - i as f32 * 2.0 * LINE_HEIGHT;
+        title_layer.use_text(line, 12.0, Mm(MARGIN), Mm(y), &font);
+    }
+    add_page_number(&doc, page, layer, 1, total_pages, &font);
+
+    for (i, lines) in pages.iter().enumerate() {
+        let (page, layer) = doc.add_page(PAGE_WIDTH, PAGE_HEIGHT, "Layer 1");
+        let body = doc.get_page(page).get_layer(layer);
+        for (row, line) in lines.iter().enumerate() {
+            let y = PAGE_HEIGHT.0 - MARGIN - row as f32 * LINE_HEIGHT;
+            body.use_text(line, BODY_FONT_SIZE, Mm(MARGIN), Mm(y), &font);
+        }
+        add_page_number(&doc, page, layer, i + 2, total_pages, &font);
+    }
+
+    Ok(doc.save_to_bytes()?)
+}
+
+fn add_page_number(
+    doc: &PdfDocumentReference,
+    page: printpdf::PdfPageIndex,
+    layer: printpdf::PdfLayerIndex,
+    number: usize,
+    total: usize,
+    font: &IndirectFontRef,
+) {
+    let text = format!("Page {} of {}", number, total);
+    // Roughly centred; Helvetica digits are about 2mm wide at 9pt
+    let x = PAGE_WIDTH.0 / 2.0 - text.len() as f32;
+    doc.get_page(page)
+        .get_layer(layer)
+        .use_text(text, 9.0, Mm(x), Mm(MARGIN / 2.0), font);
+}
+
+/// One timestamped line per caption, wrapped to [`CHARS_PER_LINE`].
+fn body_lines(transcript: &[TranscriptResponse]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for entry in transcript {
+        let offset_ms = (entry.offset * 1000.0).max(0.0) as u64;
+        let line = format!(
+            "[{}] {}",
+            format_offset(offset_ms, false),
+            normalize_text(&entry.text).replace('\n', " ")
+        );
+        lines.extend(wrap(&line, CHARS_PER_LINE));
+    }
+    lines
+}
+
+/// Splits `text` at spaces into lines of at most `width` characters,
+/// breaking words that are longer than a line on their own.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        loop {
+            let used = current.chars().count();
+            let line_started = !current.trim().is_empty();
+            if used + usize::from(line_started) + word.len() <= width {
+                if line_started {
+                    current.push(' ');
+                }
+                current.extend(word);
+                break;
+            }
+            if line_started {
+                lines.push(std::mem::take(&mut current));
+                current.push_str(CONTINUATION_INDENT);
+                continue;
+            }
+            // The word does not fit even on an empty line
+            let room = width.saturating_sub(used).max(1);
+            current.extend(word.drain(..room.min(word.len())));
+            lines.push(std::mem::take(&mut current));
+            current.push_str(CONTINUATION_INDENT);
+            if word.is_empty() {
+                break;
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Groups body lines into pages of [`LINES_PER_PAGE`].
+fn paginate(lines: &[String]) -> Vec<Vec<String>> {
+    lines
+        .chunks(LINES_PER_PAGE)
+        .map(<[String]>::to_vec)
+        .collect()
+}
+
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    chrono::DateTime::from_timestamp(secs, 0)
+        .map(|date| date.date_naive().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::test_entry;
+
+    #[test]
+    fn test_pdf_has_magic_bytes() {
+        let transcript = vec![test_entry("Hello &amp; welcome", 0.0, 1.0)];
+
+        let bytes = format_pdf(&transcript, "HQoJMIgNdjo", "en").unwrap();
+
+        assert!(bytes.starts_with(b"%PDF-"));
+    }
+
+    #[test]
+    fn test_long_transcript_is_paginated() {
+        let transcript: Vec<_> = (0..200)
+            .map(|i| test_entry(&format!("caption number {}", i), i as f64, 1.0))
+            .collect();
+
+        let pages = paginate(&body_lines(&transcript));
+
+        assert_eq!(pages.len(), 200_usize.div_ceil(LINES_PER_PAGE));
+        assert!(pages.iter().all(|page| page.len() <= LINES_PER_PAGE));
+        assert_eq!(
+            pages.last().unwrap().last().unwrap(),
+            "[3:19] caption number 199"
+        );
+
+        let bytes = format_pdf(&transcript, "HQoJMIgNdjo", "en").unwrap();
+        // Title page plus the body pages
+        let count = format!("/Count {}", pages.len() + 1);
+        assert!(bytes.windows(count.len()).any(|w| w == count.as_bytes()));
+    }
+
+    #[test]
+    fn test_wrap_long_lines() {
+        let lines = wrap("[0:01] aaaa bbbb cccc", 12);
+        assert_eq!(lines, vec!["[0:01] aaaa", "        bbbb", "        cccc"]);
+
+        let lines = wrap("x".repeat(25).as_str(), 10);
+        assert_eq!(lines[0], "x".repeat(10));
+        assert!(lines.iter().all(|line| line.chars().count() <= 10));
+    }
+}
@@ -0,0 +1,76 @@
+use crate::formatter::{format_offset, normalize_text};
+use ytranscript::TranscriptResponse;
+
+/// Start and end of a caption in milliseconds.
+fn span_ms(entry: &TranscriptResponse) -> (u64, u64) {
+    let start = (entry.offset * 1000.0).max(0.0) as u64;
+    let end = ((entry.offset + entry.duration) * 1000.0).max(0.0) as u64;
+    (start, end.max(start))
+}
+
+/// `HH:MM:SS` followed by `separator` and milliseconds, e.g. `00:01:02,500`.
+fn cue_time(ms: u64, separator: char) -> String {
+    format!("{}{}{:03}", format_offset(ms, true), separator, ms % 1000)
+}
+
+/// Formats a transcript as a SubRip (`.srt`) subtitle file.
+pub fn format_srt(transcript: &[TranscriptResponse]) -> String {
+    let mut srt = String::new();
+    for (i, entry) in transcript.iter().enumerate() {
+        let (start, end) = span_ms(entry);
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            cue_time(start, ','),
+            cue_time(end, ','),
+            normalize_text(&entry.text)
+        ));
+    }
+    srt
+}
+
+/// Formats a transcript as a WebVTT (`.vtt`) subtitle file.
+pub fn format_vtt(transcript: &[TranscriptResponse]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for entry in transcript {
+        let (start, end) = span_ms(entry);
+        vtt.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            cue_time(start, '.'),
+            cue_time(end, '.'),
+            normalize_text(&entry.text)
+        ));
+    }
+    vtt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::test_entry;
+
+    fn transcript() -> Vec<TranscriptResponse> {
+        vec![
+            test_entry("Hello &amp; welcome", 0.0, 2.5),
+            test_entry("to the show", 3661.25, 1.0),
+        ]
+    }
+
+    #[test]
+    fn test_format_srt() {
+        assert_eq!(
+            format_srt(&transcript()),
+            "1\n00:00:00,000 --> 00:00:02,500\nHello & welcome\n\n\
+             2\n01:01:01,250 --> 01:01:02,250\nto the show\n\n"
+        );
+    }
+
+    #[test]
+    fn test_format_vtt() {
+        assert_eq!(
+            format_vtt(&transcript()),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:02.500\nHello & welcome\n\n\
+             01:01:01.250 --> 01:01:02.250\nto the show\n\n"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use crate::ratelimit::{RateLimitKey, RateLimiter};
+use std::time::{Duration, Instant};
+use teloxide::types::UserId;
+
+/// How many feedback messages a user may send per day.
+pub const MAX_FEEDBACK_PER_DAY: usize = 2;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Allows each user [`MAX_FEEDBACK_PER_DAY`] feedback messages a day.
+pub struct FeedbackLimiter(RateLimiter);
+
+impl Default for FeedbackLimiter {
+    fn default() -> Self {
+        Self(RateLimiter::new(MAX_FEEDBACK_PER_DAY, DAY))
+    }
+}
+
+impl FeedbackLimiter {
+    /// Records a feedback attempt, returning false when the user is over the limit
+    pub fn try_acquire(&self, user_id: UserId) -> bool {
+        self.try_acquire_at(user_id, Instant::now())
+    }
+
+    fn try_acquire_at(&self, user_id: UserId, now: Instant) -> bool {
+        self.0.check_at(RateLimitKey::User(user_id), now).is_ok()
+    }
+}
+
+/// Builds the message forwarded to the feedback chat.
+pub fn format_feedback(user_id: UserId, timestamp: &str, text: &str) -> String {
+    format!("Feedback from user {} at {}:\n{}", user_id, timestamp, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_limit_per_day() {
+        let limiter = FeedbackLimiter::default();
+        let user = UserId(42);
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at(user, start));
+        assert!(limiter.try_acquire_at(user, start));
+        assert!(!limiter.try_acquire_at(user, start));
+        // Other users have their own allowance
+        assert!(limiter.try_acquire_at(UserId(7), start));
+        // The allowance resets once a day has passed
+        assert!(limiter.try_acquire_at(user, start + DAY));
+    }
+
+    #[test]
+    fn test_format_feedback() {
+        assert_eq!(
+            format_feedback(UserId(42), "2024-01-01T00:00:00+00:00", "Nice bot"),
+            "Feedback from user 42 at 2024-01-01T00:00:00+00:00:\nNice bot"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+/// Filename template used when none is configured or it renders badly.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{video_id}_{lang}.{ext}";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    VideoId,
+    Lang,
+    Ext,
+    Date,
+}
+
+/// Values substituted into a [`FilenameTemplate`].
+pub struct FilenameParts<'a> {
+    pub video_id: &'a str,
+    pub lang: &'a str,
+    pub ext: &'a str,
+    /// `YYYY-MM-DD`
+    pub date: &'a str,
+}
+
+/// Name given to transcript documents, parsed once from
+/// `TRANSCRIPT_FILENAME_TEMPLATE`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilenameTemplate {
+    segments: Vec<Segment>,
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_FILENAME_TEMPLATE).expect("default filename template is valid")
+    }
+}
+
+impl FilenameTemplate {
+    /// Parses a template, rejecting unknown or unclosed placeholders
+    pub fn parse(template: &str) -> Option<Self> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                segments.push(Segment::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..].find('}')? + open;
+            segments.push(match &rest[open + 1..close] {
+                "video_id" => Segment::VideoId,
+                "lang" => Segment::Lang,
+                "ext" => Segment::Ext,
+                "date" => Segment::Date,
+                _ => return None,
+            });
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        Some(Self { segments })
+    }
+
+    /// Reads `TRANSCRIPT_FILENAME_TEMPLATE`, warning and using the default
+    /// when it cannot be parsed
+    pub fn from_env() -> Self {
+        let Ok(template) = std::env::var("TRANSCRIPT_FILENAME_TEMPLATE") else {
+            return Self::default();
+        };
+        Self::parse(&template).unwrap_or_else(|| {
+            log::warn!(
+                "Ignoring invalid TRANSCRIPT_FILENAME_TEMPLATE: {:?}",
+                template
+            );
+            Self::default()
+        })
+    }
+
+    /// Substitutes the placeholders, falling back to the default template
+    /// when the result contains anything but `[A-Za-z0-9._-]`
+    pub fn render(&self, parts: &FilenameParts) -> String {
+        let name = self.render_unchecked(parts);
+        if is_safe_filename(&name) {
+            return name;
+        }
+        // The values themselves may be unsafe, so scrub the fallback too
+        Self::default()
+            .render_unchecked(parts)
+            .chars()
+            .map(|c| if is_safe_char(c) { c } else { '_' })
+            .collect()
+    }
+
+    fn render_unchecked(&self, parts: &FilenameParts) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.as_str(),
+                Segment::VideoId => parts.video_id,
+                Segment::Lang => parts.lang,
+                Segment::Ext => parts.ext,
+                Segment::Date => parts.date,
+            })
+            .collect()
+    }
+}
+
+fn is_safe_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+fn is_safe_filename(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && name.chars().all(is_safe_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARTS: FilenameParts = FilenameParts {
+        video_id: "HQoJMIgNdjo",
+        lang: "zh-HK",
+        ext: "txt",
+        date: "2024-05-01",
+    };
+
+    fn render(template: &str) -> String {
+        FilenameTemplate::parse(template).unwrap().render(&PARTS)
+    }
+
+    #[test]
+    fn test_default_template() {
+        assert_eq!(
+            FilenameTemplate::default().render(&PARTS),
+            "HQoJMIgNdjo_zh-HK.txt"
+        );
+    }
+
+    #[test]
+    fn test_each_placeholder() {
+        assert_eq!(render("{video_id}"), "HQoJMIgNdjo");
+        assert_eq!(render("t-{lang}"), "t-zh-HK");
+        assert_eq!(render("transcript.{ext}"), "transcript.txt");
+        assert_eq!(
+            render("{date}_{video_id}.{ext}"),
+            "2024-05-01_HQoJMIgNdjo.txt"
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates_are_rejected() {
+        assert_eq!(FilenameTemplate::parse("{title}.txt"), None);
+        assert_eq!(FilenameTemplate::parse("{video_id.txt"), None);
+    }
+
+    #[test]
+    fn test_unsafe_result_falls_back_to_default() {
+        assert_eq!(render("../{video_id}.{ext}"), "HQoJMIgNdjo_zh-HK.txt");
+        assert_eq!(render("my transcript.{ext}"), "HQoJMIgNdjo_zh-HK.txt");
+
+        let parts = FilenameParts {
+            lang: "en/../../etc",
+            ..PARTS
+        };
+        assert_eq!(
+            FilenameTemplate::parse("{lang}.{ext}")
+                .unwrap()
+                .render(&parts),
+            "HQoJMIgNdjo_en_.._.._etc.txt"
+        );
+    }
+}
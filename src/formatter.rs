@@ -0,0 +1,1075 @@
+use crate::args::TimeRange;
+use crate::config::env_flag;
+use crate::transcript::BilingualEntry;
+use html_escape::decode_html_entities;
+use regex::Regex;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+use ytranscript::TranscriptResponse;
+
+/// Summary numbers describing a fetched transcript.
+#[derive(Debug, PartialEq)]
+pub struct TranscriptStats {
+    pub duration_ms: u64,
+    pub entries: usize,
+    pub words: usize,
+}
+
+/// Text emoticons and the emoji they become with `REPLACE_EMOTICONS=1`.
+const EMOTICONS: &[(&str, &str)] = &[
+    (":)", "😊"),
+    (":-)", "😊"),
+    ("^_^", "😊"),
+    (":(", "😢"),
+    (":-(", "😢"),
+    (":'(", "😭"),
+    (":D", "😄"),
+    (":-D", "😄"),
+    ("XD", "😆"),
+    ("xD", "😆"),
+    (";)", "😉"),
+    (";-)", "😉"),
+    (":P", "😛"),
+    (":p", "😛"),
+    (":O", "😮"),
+    (":o", "😮"),
+    (":/", "😕"),
+    (":|", "😐"),
+    (":*", "😘"),
+    ("B)", "😎"),
+    ("<3", "❤️"),
+    ("</3", "💔"),
+];
+
+/// Decodes HTML entities left in caption text by YouTube, and turns
+/// emoticons into emoji when `REPLACE_EMOTICONS=1`.
+pub fn normalize_text(text: &str) -> String {
+    static REPLACE_EMOTICONS: OnceLock<bool> = OnceLock::new();
+
+    let text = decode_html_entities(text).replace("&#39;", "'");
+    if *REPLACE_EMOTICONS.get_or_init(|| env_flag("REPLACE_EMOTICONS")) {
+        replace_emoticons(&text)
+    } else {
+        text
+    }
+}
+
+/// Replaces emoticons that stand as a word of their own with emoji, leaving
+/// ones inside a longer word (`icon:)asm`) alone.
+pub fn replace_emoticons(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for piece in text.split_inclusive(char::is_whitespace) {
+        let word = piece.trim_end_matches(char::is_whitespace);
+        match EMOTICONS.iter().find(|(emoticon, _)| *emoticon == word) {
+            Some((_, emoji)) => {
+                output.push_str(emoji);
+                output.push_str(&piece[word.len()..]);
+            }
+            None => output.push_str(piece),
+        }
+    }
+    output
+}
+
+/// Yields one formatted line per caption, so callers can stream a long
+/// transcript without holding all of it in memory.
+pub fn format_transcript_iter(
+    transcript: &[TranscriptResponse],
+) -> impl Iterator<Item = String> + '_ {
+    transcript.iter().map(|entry| normalize_text(&entry.text))
+}
+
+/// Keeps only the captions that start inside `range`.
+pub fn filter_range(transcript: &mut Vec<TranscriptResponse>, range: TimeRange) {
+    transcript.retain(|entry| range.contains((entry.offset * 1000.0) as u64));
+}
+
+/// Removes `[inaudible]`, `[crosstalk]` and `[unintelligible]` markers,
+/// bracketed or in parentheses and in any case, e.g. `[Inaudible 01:23]`.
+/// Sound cues such as `[Music]` are left alone.
+pub fn strip_inaudible(text: &str) -> String {
+    static MARKER: OnceLock<Regex> = OnceLock::new();
+
+    let marker = MARKER.get_or_init(|| {
+        Regex::new(r"(?i)\s*[\[(]\s*(?:inaudible|crosstalk|unintelligible)\b[^\])]*[\])]").unwrap()
+    });
+    marker.replace_all(text, "").trim().to_string()
+}
+
+/// Applies [`strip_inaudible`] to every caption, dropping captions that
+/// held nothing else.
+pub fn strip_inaudible_markers(transcript: &mut Vec<TranscriptResponse>) {
+    for entry in transcript.iter_mut() {
+        entry.text = strip_inaudible(&entry.text);
+    }
+    transcript.retain(|entry| !entry.text.is_empty());
+}
+
+/// Drops captions whose decoded, trimmed text is shorter than `min_len`
+/// characters, which are mostly speech-recognition noise. 0 keeps them all.
+pub fn drop_short_captions(transcript: &mut Vec<TranscriptResponse>, min_len: usize) {
+    if min_len == 0 {
+        return;
+    }
+    transcript.retain(|entry| normalize_text(&entry.text).trim().chars().count() >= min_len);
+}
+
+/// Serializes the entries exactly as ytranscript returned them, without
+/// decoding entities or any other normalization.
+pub fn format_raw(transcript: &[TranscriptResponse]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(transcript)
+}
+
+/// Renders a bilingual transcript as a timestamped block per caption, with
+/// the second language indented under the first:
+///
+/// ```text
+/// [00:05] Hello
+///         Hola
+/// ```
+pub fn format_bilingual(entries: &[BilingualEntry]) -> String {
+    let mut output = String::new();
+
+    for entry in entries {
+        let ms = (entry.start * 1000.0).max(0.0) as u64;
+        let prefix = format!("[{}] ", format_stamp(ms));
+
+        output.push_str(&prefix);
+        output.push_str(&normalize_text(&entry.text1));
+        output.push('\n');
+        if !entry.text2.is_empty() {
+            output.push_str(&" ".repeat(prefix.len()));
+            output.push_str(&normalize_text(&entry.text2));
+            output.push('\n');
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Joins all transcript entries into plain text, one caption per line.
+pub fn format_plain(transcript: &[TranscriptResponse]) -> String {
+    let mut full_transcript = String::new();
+
+    for line in format_transcript_iter(transcript) {
+        if !full_transcript.is_empty() {
+            full_transcript.push('\n');
+        }
+        full_transcript.push_str(&line);
+    }
+
+    full_transcript
+}
+
+/// Formats each caption as `*\[MM:SS\]* text` for Telegram MarkdownV2: a
+/// bold timestamp, then the caption with its special characters escaped.
+/// The brackets are escaped too, since MarkdownV2 reserves them for links.
+pub fn format_markdown(transcript: &[TranscriptResponse]) -> String {
+    let mut markdown = String::new();
+    for entry in transcript {
+        let stamp = format_stamp((entry.offset * 1000.0).max(0.0) as u64);
+        markdown.push_str(&format!(
+            "*{}* {}\n",
+            escape_markdownv2(&format!("[{}]", stamp)),
+            escape_markdownv2(&normalize_text(&entry.text))
+        ));
+    }
+    markdown
+}
+
+/// Escapes every character MarkdownV2 treats as markup.
+pub fn escape_markdownv2(text: &str) -> String {
+    teloxide::utils::markdown::escape(text)
+}
+
+/// Puts `prefix` before and `suffix` after the transcript lines, each as
+/// lines of their own. Empty ones are left out.
+pub fn wrap_lines<I>(lines: I, prefix: &str, suffix: &str) -> impl Iterator<Item = String>
+where
+    I: Iterator<Item = String>,
+{
+    let prefix = (!prefix.is_empty()).then(|| prefix.to_string());
+    let suffix = (!suffix.is_empty()).then(|| suffix.to_string());
+    prefix.into_iter().chain(lines).chain(suffix)
+}
+
+/// Lazily packs lines into newline-joined chunks of at most `max_bytes`,
+/// splitting any single over-long line with [`split_safe_graphemes`].
+pub fn chunk_lines<I>(lines: I, max_bytes: usize) -> impl Iterator<Item = String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut pieces = lines
+        .flat_map(move |line| {
+            if line.len() <= max_bytes {
+                vec![line]
+            } else {
+                // Only a cluster wider than a whole chunk needs the byte-safe cut
+                split_safe_graphemes(&line, max_bytes)
+                    .unwrap_or_else(|_| split_safe_utf8(&line, max_bytes))
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            }
+        })
+        .peekable();
+
+    std::iter::from_fn(move || {
+        let mut chunk = pieces.next()?;
+        while let Some(next) = pieces.peek() {
+            if chunk.len() + 1 + next.len() > max_bytes {
+                break;
+            }
+            chunk.push('\n');
+            chunk.push_str(next);
+            pieces.next();
+        }
+        Some(chunk)
+    })
+}
+
+pub fn transcript_stats(transcript: &[TranscriptResponse]) -> TranscriptStats {
+    // Offsets and durations are reported in seconds
+    let duration_ms = transcript
+        .iter()
+        .map(|entry| ((entry.offset + entry.duration) * 1000.0).max(0.0) as u64)
+        .max()
+        .unwrap_or(0);
+
+    let words = transcript
+        .iter()
+        .map(|entry| normalize_text(&entry.text).split_whitespace().count())
+        .sum();
+
+    TranscriptStats {
+        duration_ms,
+        entries: transcript.len(),
+        words,
+    }
+}
+
+/// Strips punctuation and case so `Um,` and `um` compare equal.
+fn bare_word(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// What [`search_transcript`] looks for.
+#[derive(Debug)]
+pub enum SearchQuery {
+    /// A phrase, matched case-insensitively anywhere in a caption
+    Text(String),
+    Regex(Regex),
+}
+
+impl SearchQuery {
+    /// Reads `/pattern/` as a case-insensitive regex and anything else as a phrase
+    pub fn parse(query: &str) -> Result<Self, regex::Error> {
+        match query
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            Some(pattern) if !pattern.is_empty() => regex::RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map(SearchQuery::Regex),
+            _ => Ok(SearchQuery::Text(query.to_lowercase())),
+        }
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            SearchQuery::Text(phrase) => text.to_lowercase().contains(phrase.as_str()),
+            SearchQuery::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// Finds the captions matching `query`, with their start time, in order.
+/// Captions are decoded first, so `&amp;` matches a search for `&`.
+pub fn search_transcript(
+    transcript: &[TranscriptResponse],
+    query: &SearchQuery,
+) -> Vec<(Duration, String)> {
+    transcript
+        .iter()
+        .map(|entry| (entry.offset, normalize_text(&entry.text)))
+        .filter(|(_, text)| query.matches(text))
+        .map(|(offset, text)| (Duration::from_secs_f64(offset.max(0.0)), text))
+        .collect()
+}
+
+/// Replaces email addresses and phone numbers with `[redacted]`.
+///
+/// Both patterns are deliberately narrow: an email needs an `@` and a dotted
+/// domain, and a phone number needs a `+` country code, an area code in
+/// parentheses or `-`/`.` separators between digit groups. Years, counts and
+/// other bare numbers are left alone.
+pub fn redact_contacts(text: &str) -> String {
+    static EMAIL: OnceLock<Regex> = OnceLock::new();
+    static PHONE: OnceLock<Regex> = OnceLock::new();
+
+    let email = EMAIL.get_or_init(|| {
+        Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b")
+            .unwrap()
+    });
+    let phone = PHONE.get_or_init(|| {
+        Regex::new(concat!(
+            // +44 20 7946 0958, +1-555-123-4567
+            r"\+\d{1,3}(?:[ .-]?\d{2,4}){2,4}\b",
+            // (555) 123-4567, 555-123-4567, 555.123.4567
+            r"|(?:\(\d{2,4}\)[ .-]?|\b\d{3}[.-])\d{3}[.-]\d{4}\b",
+        ))
+        .unwrap()
+    });
+
+    let text = email.replace_all(text, "[redacted]");
+    phone.replace_all(&text, "[redacted]").into_owned()
+}
+
+/// Removes standalone filler words and collapses immediate word repeats
+/// ("the the" -> "the") line by line. Callers pass the filler list for the
+/// transcript's language only, so other languages are left untouched.
+pub fn remove_fillers(text: &str, fillers: &[String]) -> String {
+    text.lines()
+        .map(|line| {
+            let mut kept: Vec<&str> = Vec::new();
+            for word in line.split_whitespace() {
+                let bare = bare_word(word);
+                if fillers.contains(&bare) {
+                    continue;
+                }
+                if !bare.is_empty() && kept.last().is_some_and(|prev| bare_word(prev) == bare) {
+                    continue;
+                }
+                kept.push(word);
+            }
+            kept.join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Applies [`remove_fillers`] to every caption, dropping captions that
+/// held nothing else.
+pub fn remove_filler_words(transcript: &mut Vec<TranscriptResponse>, fillers: &[String]) {
+    for entry in transcript.iter_mut() {
+        entry.text = remove_fillers(&entry.text, fillers);
+    }
+    transcript.retain(|entry| !entry.text.trim().is_empty());
+}
+
+/// The start of a transcript that could not be uploaded, at most `max_bytes`
+/// long, under a note giving the upload error.
+pub fn failure_excerpt(text: &str, max_bytes: usize, reason: &str) -> String {
+    let excerpt = split_safe_utf8(text, max_bytes)
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    format!(
+        "[Showing first {} chars; upload failed: {}]\n{}",
+        max_bytes, reason, excerpt
+    )
+}
+
+/// Splits text into chunks of at most `max_bytes`, never cutting inside a
+/// UTF-8 character. Chunks prefer to end after a newline when one is close.
+pub fn split_safe_utf8(s: &str, max_bytes: usize) -> Vec<&str> {
+    split_utf8(s, max_bytes, false)
+}
+
+/// Like [`split_safe_utf8`], but a chunk without a line break ends after its
+/// last whitespace rather than in the middle of a word.
+pub fn split_safe_utf8_words(s: &str, max_bytes: usize) -> Vec<&str> {
+    split_utf8(s, max_bytes, true)
+}
+
+fn split_utf8(s: &str, max_bytes: usize, at_words: bool) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = s;
+
+    while rest.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        // Fall back to a hard cut when the chunk has no line break at all
+        let head = &rest[..end];
+        let split_after = head
+            .rfind('\n')
+            .or_else(|| head.rfind(char::is_whitespace).filter(|_| at_words));
+        if let Some(at) = split_after.filter(|&at| at > 0) {
+            end = at + head[at..].chars().next().map_or(1, char::len_utf8);
+        }
+        if end == 0 {
+            // A single character wider than `max_bytes`; emit it whole
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// A grapheme cluster that is larger than the requested chunk size on its own.
+#[derive(Debug, PartialEq)]
+pub struct OversizedGrapheme {
+    pub grapheme_bytes: usize,
+    pub max_bytes: usize,
+}
+
+impl fmt::Display for OversizedGrapheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A {}-byte character does not fit in chunks of {} bytes",
+            self.grapheme_bytes, self.max_bytes
+        )
+    }
+}
+
+impl std::error::Error for OversizedGrapheme {}
+
+/// Like [`split_safe_utf8`], but never cuts inside a grapheme cluster, so an
+/// emoji with modifiers or a letter with combining accents stays in one chunk.
+pub fn split_safe_graphemes(s: &str, max_bytes: usize) -> Result<Vec<&str>, OversizedGrapheme> {
+    let mut chunks = Vec::new();
+    let mut rest = s;
+
+    while rest.len() > max_bytes {
+        let mut end = 0;
+        let mut newline_end = None;
+        for (offset, grapheme) in rest.grapheme_indices(true) {
+            let next = offset + grapheme.len();
+            if next > max_bytes {
+                break;
+            }
+            end = next;
+            if grapheme.ends_with('\n') {
+                newline_end = Some(next);
+            }
+        }
+        if end == 0 {
+            let first = rest.graphemes(true).next().unwrap_or(rest);
+            return Err(OversizedGrapheme {
+                grapheme_bytes: first.len(),
+                max_bytes,
+            });
+        }
+        // Fall back to a hard cut when the chunk has no line break at all
+        if let Some(newline_end) = newline_end {
+            end = newline_end;
+        }
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    Ok(chunks)
+}
+
+/// Formats a time offset for display. With `with_hours` the result is always
+/// `HH:MM:SS` (as SRT/VTT need); otherwise hours only appear once the offset
+/// reaches an hour, giving `M:SS` or `H:MM:SS`.
+pub fn format_offset(ms: u64, with_hours: bool) -> String {
+    let total_secs = ms / 1000;
+    let (hours, minutes, seconds) = (total_secs / 3600, total_secs / 60 % 60, total_secs % 60);
+
+    if with_hours {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Formats an offset as `MM:SS`, or `HH:MM:SS` from an hour on, as used in
+/// `[MM:SS]` markers.
+pub fn format_stamp(ms: u64) -> String {
+    let total_secs = ms / 1000;
+    if total_secs >= 3600 {
+        format_offset(ms, true)
+    } else {
+        format!("{:02}:{:02}", total_secs / 60, total_secs % 60)
+    }
+}
+
+/// Seconds of silence between the end of `prev` and the start of `next`.
+pub fn gap_between(prev: &TranscriptResponse, next: &TranscriptResponse) -> f64 {
+    next.offset - (prev.offset + prev.duration)
+}
+
+/// Silence, in seconds, that ends a paragraph in [`format_paragraphs`].
+const PARAGRAPH_GAP_SECS: f64 = 2.0;
+
+/// Captions joined into running text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paragraph {
+    pub start_ms: u64,
+    pub text: String,
+}
+
+/// Joins captions into paragraphs, starting a new one after a pause of
+/// [`PARAGRAPH_GAP_SECS`] or more.
+pub fn format_paragraphs(transcript: &[TranscriptResponse]) -> Vec<Paragraph> {
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+
+    for (i, entry) in transcript.iter().enumerate() {
+        let text = normalize_text(&entry.text);
+        let continues = i > 0 && gap_between(&transcript[i - 1], entry) < PARAGRAPH_GAP_SECS;
+        match paragraphs.last_mut() {
+            Some(paragraph) if continues => {
+                paragraph.text.push(' ');
+                paragraph.text.push_str(&text);
+            }
+            _ => paragraphs.push(Paragraph {
+                start_ms: (entry.offset * 1000.0).max(0.0) as u64,
+                text,
+            }),
+        }
+    }
+
+    paragraphs
+}
+
+/// Characters that end a sentence in [`format_compact`].
+const SENTENCE_ENDS: [char; 4] = ['.', '!', '?', '…'];
+
+/// One sentence per line, each prefixed with `[MM:SS]` from the first
+/// caption it draws on. Captions are merged before splitting, so sentences
+/// that span several captions end up whole.
+pub fn format_compact(transcript: &[TranscriptResponse]) -> String {
+    let mut lines = Vec::new();
+    let mut sentence = String::new();
+    let mut start_ms = 0;
+
+    for entry in transcript {
+        let offset_ms = (entry.offset * 1000.0).max(0.0) as u64;
+        for word in normalize_text(&entry.text).split_whitespace() {
+            if sentence.is_empty() {
+                start_ms = offset_ms;
+            } else {
+                sentence.push(' ');
+            }
+            sentence.push_str(word);
+            if ends_sentence(word) {
+                lines.push(format!("[{}] {}", format_stamp(start_ms), sentence));
+                sentence.clear();
+            }
+        }
+    }
+    if !sentence.is_empty() {
+        lines.push(format!("[{}] {}", format_stamp(start_ms), sentence));
+    }
+
+    lines.join("\n")
+}
+
+/// Whether `word` closes a sentence, allowing for quotes or brackets after
+/// the punctuation.
+fn ends_sentence(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', ')', ']', '”', '’'])
+        .ends_with(SENTENCE_ENDS)
+}
+
+/// Estimates how many LLM tokens `text` would use. This is only an
+/// approximation of GPT-style tokenizers, good for planning context windows.
+///
+/// Space-separated text uses roughly 4 tokens per 3 words. Scripts written
+/// without spaces (Chinese, Japanese, Thai) would be badly undercounted that
+/// way, so their characters are counted instead.
+pub fn estimate_token_count(text: &str) -> usize {
+    let words = text.split_whitespace().count();
+    let word_estimate = words * 4 / 3;
+
+    // About one token per CJK character and per four other characters
+    let quarter_tokens: usize = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| if (c as u32) >= 0x2E80 { 4 } else { 1 })
+        .sum();
+    let char_estimate = quarter_tokens.div_ceil(4);
+
+    // Unspaced scripts show up as very long "words"
+    if words > 0 && char_estimate > word_estimate * 2 {
+        char_estimate
+    } else {
+        word_estimate.max(usize::from(words > 0))
+    }
+}
+
+/// Builds the compact reply for the `/info` command.
+pub fn format_info(video_id: &str, lang: &str, stats: &TranscriptStats) -> String {
+    format!(
+        "Video: {}\nLanguage: {}\nDuration: {}\nCaptions: {}\nWords: {}",
+        video_id,
+        lang,
+        format_offset(stats.duration_ms, false),
+        stats.entries,
+        stats.words
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::test_entry as entry;
+
+    #[test]
+    fn test_transcript_stats() {
+        let transcript = vec![
+            entry("hello there", 0.0, 2.5),
+            entry("it&#39;s a test", 2.5, 3.0),
+            entry("bye", 80.0, 10.0),
+        ];
+
+        assert_eq!(
+            transcript_stats(&transcript),
+            TranscriptStats {
+                duration_ms: 90_000,
+                entries: 3,
+                words: 6,
+            }
+        );
+        assert_eq!(transcript_stats(&[]).duration_ms, 0);
+    }
+
+    #[test]
+    fn test_format_transcript_iter() {
+        let transcript = vec![entry("first", 0.0, 1.0), entry("it&#39;s second", 1.0, 1.0)];
+        let mut lines = format_transcript_iter(&transcript);
+
+        assert_eq!(lines.next().as_deref(), Some("first"));
+        assert_eq!(lines.next().as_deref(), Some("it's second"));
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn test_filter_range() {
+        let mut transcript = vec![
+            entry("intro", 0.0, 60.0),
+            entry("middle", 120.0, 30.0),
+            entry("outro", 200.0, 10.0),
+        ];
+        filter_range(&mut transcript, TimeRange::from_start(120_000));
+
+        assert_eq!(format_plain(&transcript), "middle\noutro");
+    }
+
+    #[test]
+    fn test_format_paragraphs() {
+        let transcript = vec![
+            entry("first", 0.0, 1.0),
+            entry("still first", 1.5, 1.0),
+            entry("second", 5.0, 1.0),
+        ];
+
+        assert_eq!(
+            format_paragraphs(&transcript),
+            vec![
+                Paragraph {
+                    start_ms: 0,
+                    text: "first still first".to_string()
+                },
+                Paragraph {
+                    start_ms: 5000,
+                    text: "second".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_compact() {
+        let transcript = vec![
+            entry("so today we", 0.0, 1.5),
+            entry("look at rust.", 1.5, 1.5),
+            entry("It's fast! And", 63.0, 2.0),
+            entry("\"safe.\" Right", 65.0, 1.0),
+        ];
+
+        assert_eq!(
+            format_compact(&transcript),
+            "[00:00] so today we look at rust.\n\
+             [01:03] It's fast!\n\
+             [01:03] And \"safe.\"\n\
+             [01:05] Right"
+        );
+        assert_eq!(format_compact(&[]), "");
+    }
+
+    #[test]
+    fn test_strip_inaudible() {
+        assert_eq!(
+            strip_inaudible("so we [inaudible] went (Crosstalk) home"),
+            "so we went home"
+        );
+        assert_eq!(strip_inaudible("[UNINTELLIGIBLE 01:23] yes"), "yes");
+        // Music cues and other brackets are a separate matter
+        assert_eq!(strip_inaudible("[Music] [Applause]"), "[Music] [Applause]");
+
+        let mut transcript = vec![
+            entry("[inaudible]", 0.0, 1.0),
+            entry("[Music] hello [crosstalk]", 1.0, 1.0),
+        ];
+        strip_inaudible_markers(&mut transcript);
+        assert_eq!(format_plain(&transcript), "[Music] hello");
+    }
+
+    #[test]
+    fn test_drop_short_captions() {
+        let mut transcript = vec![
+            entry("uh", 0.0, 1.0),
+            entry(" no ", 1.0, 1.0),
+            entry("&amp;", 2.0, 1.0),
+            entry("yes", 3.0, 1.0),
+            entry("héllo there", 4.0, 1.0),
+        ];
+
+        drop_short_captions(&mut transcript, 0);
+        assert_eq!(transcript.len(), 5);
+
+        // `&amp;` is one character once decoded; `yes` is right at the limit
+        drop_short_captions(&mut transcript, 3);
+        assert_eq!(format_plain(&transcript), "yes\nhéllo there");
+    }
+
+    #[test]
+    fn test_format_raw_keeps_encoded_text() {
+        let transcript = vec![entry("it&#39;s raw", 1.5, 2.0)];
+        let raw = format_raw(&transcript).unwrap();
+
+        assert!(raw.contains("it&#39;s raw"));
+        assert!(raw.contains("\"offset\": 1.5"));
+        assert_eq!(format_plain(&transcript), "it's raw");
+    }
+
+    #[test]
+    fn test_chunk_lines() {
+        let lines = ["aaa", "bbb", "cc", "dddddddddd"].map(String::from);
+        let chunks: Vec<String> = chunk_lines(lines.into_iter(), 8).collect();
+
+        assert_eq!(chunks, vec!["aaa\nbbb", "cc", "dddddddd", "dd"]);
+    }
+
+    #[test]
+    fn test_wrap_lines() {
+        let lines = || vec!["one".to_string(), "two".to_string()].into_iter();
+
+        let wrapped: Vec<String> = wrap_lines(lines(), "Internal use only", "© ACME").collect();
+        assert_eq!(wrapped, vec!["Internal use only", "one", "two", "© ACME"]);
+
+        let unwrapped: Vec<String> = wrap_lines(lines(), "", "").collect();
+        assert_eq!(unwrapped, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn test_chunk_lines_is_lazy() {
+        // An endless source still yields chunks, so nothing is collected up front
+        let endless = std::iter::repeat_with(|| "line".to_string());
+        let chunks: Vec<String> = chunk_lines(endless, 9).take(2).collect();
+
+        assert_eq!(chunks, vec!["line\nline", "line\nline"]);
+    }
+
+    #[test]
+    fn test_split_safe_utf8() {
+        assert_eq!(
+            split_safe_utf8("abc\ndef\nghi", 8),
+            vec!["abc\ndef\n", "ghi"]
+        );
+        assert_eq!(split_safe_utf8("abcdef", 4), vec!["abcd", "ef"]);
+        // "é" is two bytes and must not be split
+        assert_eq!(split_safe_utf8("aéé", 2), vec!["a", "é", "é"]);
+        assert!(split_safe_utf8("", 10).is_empty());
+    }
+
+    #[test]
+    fn test_split_safe_utf8_words() {
+        assert_eq!(
+            split_safe_utf8_words("one two three", 9),
+            vec!["one two ", "three"]
+        );
+        // Line breaks still win over spaces
+        assert_eq!(
+            split_safe_utf8_words("one\ntwo three", 12),
+            vec!["one\n", "two three"]
+        );
+        // A word longer than a chunk is cut
+        assert_eq!(split_safe_utf8_words("abcdef", 4), vec!["abcd", "ef"]);
+        assert_eq!(split_safe_utf8_words("é é", 3), vec!["é ", "é"]);
+    }
+
+    #[test]
+    fn test_format_markdown() {
+        let transcript = vec![
+            entry("Version 2.0 is out - finally", 5.0, 2.0),
+            entry("a_b (c)", 65.0, 1.0),
+        ];
+
+        let markdown = format_markdown(&transcript);
+        assert_eq!(
+            markdown,
+            "*\\[00:05\\]* Version 2\\.0 is out \\- finally\n*\\[01:05\\]* a\\_b \\(c\\)\n"
+        );
+
+        // Outside the bold markers, every special character is escaped
+        for line in markdown.lines() {
+            let body = line.strip_prefix('*').unwrap();
+            let (stamp, text) = body.split_once("* ").unwrap();
+            for part in [stamp, text] {
+                let mut chars = part.chars();
+                while let Some(c) = chars.next() {
+                    if c == '\\' {
+                        chars.next();
+                    } else {
+                        assert!(!"_*[]()~`>#+-=|{}.!".contains(c), "unescaped {:?}", c);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_failure_excerpt() {
+        let text = "é".repeat(2000);
+        let message = failure_excerpt(&text, 3000, "status 500");
+
+        let (note, excerpt) = message.split_once('\n').unwrap();
+        assert_eq!(
+            note,
+            "[Showing first 3000 chars; upload failed: status 500]"
+        );
+        assert_eq!(excerpt, "é".repeat(1500));
+        assert_eq!(
+            failure_excerpt("short", 3000, "x"),
+            "[Showing first 3000 chars; upload failed: x]\nshort"
+        );
+    }
+
+    #[test]
+    fn test_split_safe_graphemes_keeps_flag_together() {
+        // The German flag is two 4-byte regional indicators
+        let flag = "\u{1F1E9}\u{1F1EA}";
+        let text = format!("ab{}", flag);
+
+        // Byte-safe splitting cuts between the two indicators
+        assert_eq!(split_safe_utf8(&text, 8), vec!["ab\u{1F1E9}", "\u{1F1EA}"]);
+        assert_eq!(split_safe_graphemes(&text, 8).unwrap(), vec!["ab", flag]);
+    }
+
+    #[test]
+    fn test_split_safe_graphemes_keeps_combining_accent() {
+        // "e" followed by a combining acute accent (two bytes)
+        let text = "abe\u{301}cd";
+
+        assert_eq!(
+            split_safe_graphemes(text, 4).unwrap(),
+            vec!["ab", "e\u{301}c", "d"]
+        );
+        assert_eq!(
+            split_safe_graphemes("abc\ndef\nghi", 8).unwrap(),
+            vec!["abc\ndef\n", "ghi"]
+        );
+        assert!(split_safe_graphemes("", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_split_safe_graphemes_rejects_oversized_cluster() {
+        let flag = "\u{1F1E9}\u{1F1EA}";
+
+        assert_eq!(
+            split_safe_graphemes(flag, 4),
+            Err(OversizedGrapheme {
+                grapheme_bytes: 8,
+                max_bytes: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn test_replace_emoticons() {
+        for (emoticon, emoji) in EMOTICONS {
+            assert_eq!(replace_emoticons(emoticon), *emoji, "{}", emoticon);
+            assert_eq!(
+                replace_emoticons(&format!("so {} ok", emoticon)),
+                format!("so {} ok", emoji)
+            );
+        }
+        assert_eq!(
+            replace_emoticons("bye :(\nsee you :D"),
+            "bye 😢\nsee you 😄"
+        );
+    }
+
+    #[test]
+    fn test_emoticons_inside_words_are_kept() {
+        assert_eq!(replace_emoticons("icon:)asm"), "icon:)asm");
+        assert_eq!(
+            replace_emoticons("https://example.com"),
+            "https://example.com"
+        );
+        assert_eq!(replace_emoticons("ratio 3:2 :))"), "ratio 3:2 :))");
+    }
+
+    #[test]
+    fn test_search_is_case_insensitive() {
+        let transcript = vec![
+            entry("Today: Climate Change", 5.0, 2.0),
+            entry("and the weather", 65.5, 2.0),
+        ];
+        let query = SearchQuery::parse("climate change").unwrap();
+
+        assert_eq!(
+            search_transcript(&transcript, &query),
+            vec![(Duration::from_secs(5), "Today: Climate Change".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_search_multiple_hits() {
+        let transcript = vec![
+            entry("solar power", 1.0, 2.0),
+            entry("wind power", 3.0, 2.0),
+            entry("Tom &amp; Jerry", 5.0, 2.0),
+            entry("POWERFUL stuff", 7.0, 2.0),
+        ];
+
+        let query = SearchQuery::parse("power").unwrap();
+        let times: Vec<u64> = search_transcript(&transcript, &query)
+            .iter()
+            .map(|(start, _)| start.as_secs())
+            .collect();
+        assert_eq!(times, vec![1, 3, 7]);
+
+        let query = SearchQuery::parse("/^(solar|wind) power$/").unwrap();
+        assert_eq!(search_transcript(&transcript, &query).len(), 2);
+
+        let query = SearchQuery::parse("tom & jerry").unwrap();
+        assert_eq!(search_transcript(&transcript, &query)[0].1, "Tom & Jerry");
+
+        let query = SearchQuery::parse("drought").unwrap();
+        assert!(search_transcript(&transcript, &query).is_empty());
+        assert!(SearchQuery::parse("/(unclosed/").is_err());
+    }
+
+    #[test]
+    fn test_redact_contacts() {
+        assert_eq!(
+            redact_contacts("mail jane.doe@example.co.uk today"),
+            "mail [redacted] today"
+        );
+        assert_eq!(
+            redact_contacts("call 555-123-4567 or (555) 123-4567"),
+            "call [redacted] or [redacted]"
+        );
+        assert_eq!(
+            redact_contacts("our London office is +44 20 7946 0958."),
+            "our London office is [redacted]."
+        );
+        // Years, counts and ranges are not contact details
+        let plain = "in the year 2024 we sold 1500 units, 10-20 of them in 1999";
+        assert_eq!(redact_contacts(plain), plain);
+    }
+
+    #[test]
+    fn test_remove_fillers() {
+        let fillers = vec!["um".to_string(), "uh".to_string()];
+
+        assert_eq!(remove_fillers("so um I think", &fillers), "so I think");
+        assert_eq!(remove_fillers("Um, the the cat", &fillers), "the cat");
+        assert_eq!(
+            remove_fillers("uh first line\nsecond second line", &fillers),
+            "first line\nsecond line"
+        );
+        // Without a configured list only stutters are collapsed
+        assert_eq!(remove_fillers("um the the end", &[]), "um the end");
+
+        let mut transcript = vec![entry("uh", 0.0, 1.0), entry("so um yes", 1.0, 1.0)];
+        remove_filler_words(&mut transcript, &fillers);
+        assert_eq!(format_plain(&transcript), "so yes");
+    }
+
+    #[test]
+    fn test_format_bilingual() {
+        let entries = vec![
+            BilingualEntry {
+                start: 65.2,
+                text1: "Tom &amp; Jerry".to_string(),
+                text2: "Tom y Jerry".to_string(),
+            },
+            BilingualEntry {
+                start: 70.0,
+                text1: "Only English".to_string(),
+                text2: String::new(),
+            },
+        ];
+
+        assert_eq!(
+            format_bilingual(&entries),
+            "[01:05] Tom & Jerry\n        Tom y Jerry\n\n[01:10] Only English\n\n"
+        );
+    }
+
+    #[test]
+    fn test_format_offset() {
+        assert_eq!(format_offset(90_000, false), "1:30");
+        assert_eq!(format_offset(3_700_000, false), "1:01:40");
+        assert_eq!(format_offset(90_000, true), "00:01:30");
+        assert_eq!(format_offset(3_700_000, true), "01:01:40");
+        assert_eq!(format_offset(999, false), "0:00");
+    }
+
+    /// Estimates must stay within 20% of GPT-4 (cl100k) token counts
+    fn assert_close_to(text: &str, gpt4_tokens: usize) {
+        let estimate = estimate_token_count(text) as f64;
+        let actual = gpt4_tokens as f64;
+        assert!(
+            (estimate - actual).abs() <= actual * 0.2,
+            "{:?}: estimated {} vs {} tokens",
+            text,
+            estimate,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_estimate_token_count() {
+        assert_close_to("Hello world", 2);
+        assert_close_to("Photosynthesis converts sunlight into chemical energy.", 8);
+        assert_close_to(
+            "So today we are going to talk about the history of the Roman Empire, \
+             from its founding to the fall of Constantinople.",
+            26,
+        );
+        assert_eq!(estimate_token_count(""), 0);
+        assert_eq!(estimate_token_count("   "), 0);
+    }
+
+    #[test]
+    fn test_estimate_token_count_unspaced_script() {
+        // Without the character fallback this would count as a single word
+        let text = "今天我们来谈谈罗马帝国的历史";
+        assert!(estimate_token_count(text) >= 10);
+    }
+
+    #[test]
+    fn test_format_info() {
+        let transcript = vec![entry("one two three", 0.0, 4.0), entry("four", 3700.0, 1.0)];
+        let info = format_info("abc123", "en", &transcript_stats(&transcript));
+
+        assert!(info.contains("Words: 4"));
+        assert!(info.contains("Duration: 1:01:41"));
+        assert!(info.contains("Language: en"));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::metrics;
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Serves `GET /health` and `GET /metrics` on `addr` for the life of the
+/// process. Only the request line is looked at; this is for probes and
+/// scrapers, not general HTTP clients.
+pub async fn serve(addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    log::info!("Health server listening on {}", addr);
+    serve_on(listener).await
+}
+
+async fn serve_on(listener: TcpListener) -> io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream).await {
+                log::debug!("Health request from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // Drain the headers so the client sees a clean close
+    let mut header = String::new();
+    while reader.read_line(&mut header).await? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/health")) => ("200 OK", "text/plain", "ok\n".to_string()),
+        (Some("GET"), Some("/metrics")) => {
+            ("200 OK", "text/plain; version=0.0.4", metrics::render())
+        }
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn spawn_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_on(listener));
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let addr = spawn_server().await;
+
+        let response = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let body = response.text().await.unwrap();
+        let value = body
+            .lines()
+            .find_map(|line| line.strip_prefix("tofuboi_requests_total "))
+            .expect("requests counter missing");
+        assert!(value.parse::<u64>().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_path_is_not_found() {
+        let addr = spawn_server().await;
+
+        let response = reqwest::get(format!("http://{}/nope", addr)).await.unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
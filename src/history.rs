@@ -0,0 +1,506 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Uploads listed by `/history`.
+pub const HISTORY_LIMIT: usize = 5;
+
+/// Languages listed by `/languages`.
+pub const LANGUAGES_LIMIT: usize = 20;
+
+/// How long a write waits for another connection's lock before failing
+/// with `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Version stored in `PRAGMA user_version` once every migration has run.
+const SCHEMA_VERSION: i64 = 2;
+
+/// Version 1: the upload history. Databases made before versioning have
+/// this table already, hence `IF NOT EXISTS`.
+const SCHEMA_V1: &str = "
+    CREATE TABLE IF NOT EXISTS transcripts (
+        id INTEGER PRIMARY KEY,
+        user_id INTEGER NOT NULL,
+        video_id TEXT NOT NULL,
+        lang TEXT NOT NULL,
+        upload_url TEXT NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS transcripts_user_time ON transcripts (user_id, timestamp);
+    CREATE INDEX IF NOT EXISTS transcripts_video_time ON transcripts (video_id, timestamp);
+";
+
+/// Version 2: requests per language, counted from the uploads so far.
+const SCHEMA_V2: &str = "
+    CREATE TABLE language_usage (
+        lang TEXT PRIMARY KEY,
+        requests INTEGER NOT NULL
+    );
+    INSERT INTO language_usage (lang, requests)
+        SELECT lang, COUNT(*) FROM transcripts GROUP BY lang;
+";
+
+/// One uploaded transcript.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub user_id: u64,
+    pub video_id: String,
+    pub lang: String,
+    pub upload_url: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+impl HistoryEntry {
+    /// An entry stamped with the current time
+    pub fn now(user_id: u64, video_id: &str, lang: &str, upload_url: &str) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        Self {
+            user_id,
+            video_id: video_id.to_string(),
+            lang: lang.to_string(),
+            upload_url: upload_url.to_string(),
+            timestamp,
+        }
+    }
+}
+
+/// Upload URLs kept in SQLite so users can find past transcripts again,
+/// along with how often each language is requested.
+pub struct History {
+    conn: Mutex<Connection>,
+}
+
+impl fmt::Debug for History {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("History").finish_non_exhaustive()
+    }
+}
+
+impl History {
+    /// Opens the database at `path`, creating it and its table if needed
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        Self::init(open_db(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> rusqlite::Result<Self> {
+        migrate(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Bytes in the write-ahead log; zero for an in-memory database or when
+    /// the log has been checkpointed away
+    pub fn wal_size(&self) -> u64 {
+        let conn = self.conn.lock().unwrap();
+        conn.path()
+            .filter(|path| !path.is_empty())
+            .and_then(|path| std::fs::metadata(format!("{}-wal", path)).ok())
+            .map_or(0, |metadata| metadata.len())
+    }
+
+    /// Counts one request for `lang`
+    pub fn count_language(&self, lang: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO language_usage (lang, requests) VALUES (?1, 1)
+             ON CONFLICT (lang) DO UPDATE SET requests = requests + 1",
+            params![lang],
+        )?;
+        Ok(())
+    }
+
+    /// The most requested languages with their request counts, most used first
+    pub fn top_languages(&self, limit: usize) -> rusqlite::Result<Vec<(String, u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT lang, requests FROM language_usage
+             ORDER BY requests DESC, lang ASC LIMIT ?1",
+        )?;
+        let rows = statement.query_map(params![limit as i64], |row| {
+            Ok((row.get(0)?, row.get::<_, i64>(1)? as u64))
+        })?;
+        rows.collect()
+    }
+
+    pub fn record(&self, entry: &HistoryEntry) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO transcripts (user_id, video_id, lang, upload_url, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entry.user_id as i64,
+                entry.video_id,
+                entry.lang,
+                entry.upload_url,
+                entry.timestamp
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The user's most recent uploads, newest first
+    pub fn recent_for_user(
+        &self,
+        user_id: u64,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<HistoryEntry>> {
+        self.query(
+            "SELECT user_id, video_id, lang, upload_url, timestamp FROM transcripts
+             WHERE user_id = ?1 ORDER BY timestamp DESC, id DESC LIMIT ?2",
+            params![user_id as i64, limit as i64],
+        )
+    }
+
+    /// Every upload of the user, oldest first
+    pub fn all_for_user(&self, user_id: u64) -> rusqlite::Result<Vec<HistoryEntry>> {
+        self.query(
+            "SELECT user_id, video_id, lang, upload_url, timestamp FROM transcripts
+             WHERE user_id = ?1 ORDER BY timestamp, id",
+            params![user_id as i64],
+        )
+    }
+
+    /// The most recent uploads of one video by any user, newest first
+    pub fn recent_for_video(
+        &self,
+        video_id: &str,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<HistoryEntry>> {
+        self.query(
+            "SELECT user_id, video_id, lang, upload_url, timestamp FROM transcripts
+             WHERE video_id = ?1 ORDER BY timestamp DESC, id DESC LIMIT ?2",
+            params![video_id, limit as i64],
+        )
+    }
+
+    /// Deletes every upload of the user in one transaction and returns the
+    /// deleted entries
+    pub fn delete_for_user(&self, user_id: u64) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let deleted = {
+            let mut statement = tx.prepare(
+                "SELECT user_id, video_id, lang, upload_url, timestamp FROM transcripts
+                 WHERE user_id = ?1",
+            )?;
+            let rows = statement.query_map(params![user_id as i64], entry_from_row)?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        tx.execute(
+            "DELETE FROM transcripts WHERE user_id = ?1",
+            params![user_id as i64],
+        )?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Number of uploads recorded for the user
+    pub fn count_for_user(&self, user_id: u64) -> rusqlite::Result<usize> {
+        self.conn.lock().unwrap().query_row(
+            "SELECT COUNT(*) FROM transcripts WHERE user_id = ?1",
+            params![user_id as i64],
+            |row| row.get::<_, i64>(0).map(|count| count as usize),
+        )
+    }
+
+    fn query(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(sql)?;
+        let rows = statement.query_map(params, entry_from_row)?;
+        rows.collect()
+    }
+}
+
+/// Reads a row selected as `user_id, video_id, lang, upload_url, timestamp`.
+fn entry_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        user_id: row.get::<_, i64>(0)? as u64,
+        video_id: row.get(1)?,
+        lang: row.get(2)?,
+        upload_url: row.get(3)?,
+        timestamp: row.get(4)?,
+    })
+}
+
+/// Opens a connection set up for writes from several tasks at once: WAL lets
+/// readers carry on during a write, and the busy timeout makes writers wait
+/// for each other instead of failing.
+pub fn open_db(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+/// Brings the database up to [`SCHEMA_VERSION`], one version at a time.
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let version: i64 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version < 1 {
+        tx.execute_batch(SCHEMA_V1)?;
+    }
+    if version < 2 {
+        tx.execute_batch(SCHEMA_V2)?;
+    }
+    if version < SCHEMA_VERSION {
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+    tx.commit()
+}
+
+/// Asks the user to confirm `/clearhistory`.
+pub fn format_clear_prompt(entries: usize) -> String {
+    format!(
+        "This deletes your transcript history ({} {}). Send /clearhistory --confirm to continue.",
+        entries,
+        entries_unit(entries)
+    )
+}
+
+/// Confirms that `/clearhistory --confirm` deleted the history.
+pub fn format_cleared(entries: usize) -> String {
+    format!(
+        "Your transcript history ({} {}) has been deleted.",
+        entries,
+        entries_unit(entries)
+    )
+}
+
+fn entries_unit(entries: usize) -> &'static str {
+    if entries == 1 {
+        "entry"
+    } else {
+        "entries"
+    }
+}
+
+/// Lists languages as `1. es (12 requests)`.
+pub fn format_languages(languages: &[(String, u64)]) -> String {
+    let mut text = String::from("Most requested languages:");
+    for (i, (lang, requests)) in languages.iter().enumerate() {
+        let unit = if *requests == 1 {
+            "request"
+        } else {
+            "requests"
+        };
+        text.push_str(&format!("\n{}. {} ({} {})", i + 1, lang, requests, unit));
+    }
+    text
+}
+
+/// Lists entries as `1. <video> (<lang>), <date>: <url>` under `title`.
+pub fn format_history(title: &str, entries: &[HistoryEntry]) -> String {
+    let mut text = title.to_string();
+    for (i, entry) in entries.iter().enumerate() {
+        let date = chrono::DateTime::from_timestamp(entry.timestamp, 0)
+            .map(|date| date.date_naive().to_string())
+            .unwrap_or_default();
+        text.push_str(&format!(
+            "\n{}. {} ({}), {}: {}",
+            i + 1,
+            entry.video_id,
+            entry.lang,
+            date,
+            entry.upload_url
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn entry(user_id: u64, video_id: &str, timestamp: i64) -> HistoryEntry {
+        HistoryEntry {
+            user_id,
+            video_id: video_id.to_string(),
+            lang: "en".to_string(),
+            upload_url: format!("https://paste.example/{}-{}", video_id, timestamp),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_user_history_is_newest_first_and_limited() {
+        let history = History::open_in_memory().unwrap();
+        for timestamp in [10, 30, 20, 50, 40, 60] {
+            history.record(&entry(1, "abc", timestamp)).unwrap();
+        }
+        history.record(&entry(2, "abc", 100)).unwrap();
+
+        let recent = history.recent_for_user(1, HISTORY_LIMIT).unwrap();
+        let times: Vec<i64> = recent.iter().map(|e| e.timestamp).collect();
+        assert_eq!(times, vec![60, 50, 40, 30, 20]);
+        assert!(recent.iter().all(|e| e.user_id == 1));
+        assert_eq!(recent[0], entry(1, "abc", 60));
+
+        assert!(history
+            .recent_for_user(3, HISTORY_LIMIT)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_video_lookup_spans_users() {
+        let history = History::open_in_memory().unwrap();
+        history.record(&entry(1, "abc", 10)).unwrap();
+        history.record(&entry(2, "abc", 20)).unwrap();
+        history.record(&entry(2, "xyz", 30)).unwrap();
+
+        let found = history.recent_for_video("abc", HISTORY_LIMIT).unwrap();
+        let users: Vec<u64> = found.iter().map(|e| e.user_id).collect();
+        assert_eq!(users, vec![2, 1]);
+        assert!(history
+            .recent_for_video("nope", HISTORY_LIMIT)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_migration_counts_existing_uploads() {
+        // A database from before versioning, with uploads but no counters
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA_V1).unwrap();
+        for (lang, timestamp) in [("es", 1), ("en", 2), ("es", 3)] {
+            conn.execute(
+                "INSERT INTO transcripts (user_id, video_id, lang, upload_url, timestamp)
+                 VALUES (1, 'abc', ?1, 'https://paste.example', ?2)",
+                params![lang, timestamp],
+            )
+            .unwrap();
+        }
+
+        let history = History::init(conn).unwrap();
+        assert_eq!(
+            history.top_languages(LANGUAGES_LIMIT).unwrap(),
+            vec![("es".to_string(), 2), ("en".to_string(), 1)]
+        );
+
+        // Migrating again leaves the counters alone
+        let conn = history.conn.into_inner().unwrap();
+        let history = History::init(conn).unwrap();
+        assert_eq!(
+            history.top_languages(1).unwrap(),
+            vec![("es".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_language_counter() {
+        let history = History::open_in_memory().unwrap();
+        for lang in ["de", "fr", "fr", "ja", "fr", "de"] {
+            history.count_language(lang).unwrap();
+        }
+
+        let top = history.top_languages(LANGUAGES_LIMIT).unwrap();
+        assert_eq!(
+            top,
+            vec![
+                ("fr".to_string(), 3),
+                ("de".to_string(), 2),
+                ("ja".to_string(), 1)
+            ]
+        );
+        assert_eq!(history.top_languages(2).unwrap().len(), 2);
+        assert_eq!(
+            format_languages(&top),
+            "Most requested languages:\n1. fr (3 requests)\n2. de (2 requests)\n3. ja (1 request)"
+        );
+    }
+
+    #[test]
+    fn test_delete_for_user() {
+        let history = History::open_in_memory().unwrap();
+        history.record(&entry(1, "abc", 10)).unwrap();
+        history.record(&entry(1, "xyz", 20)).unwrap();
+        history.record(&entry(2, "abc", 30)).unwrap();
+        assert_eq!(history.count_for_user(1).unwrap(), 2);
+
+        let deleted = history.delete_for_user(1).unwrap();
+        assert_eq!(deleted, vec![entry(1, "abc", 10), entry(1, "xyz", 20)]);
+        assert!(history
+            .recent_for_user(1, HISTORY_LIMIT)
+            .unwrap()
+            .is_empty());
+        assert_eq!(history.count_for_user(2).unwrap(), 1);
+        assert!(history.delete_for_user(1).unwrap().is_empty());
+
+        assert_eq!(
+            format_cleared(deleted.len()),
+            "Your transcript history (2 entries) has been deleted."
+        );
+        assert_eq!(
+            format_clear_prompt(1),
+            "This deletes your transcript history (1 entry). Send /clearhistory --confirm to continue."
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_writes_from_two_connections() {
+        let path = std::env::temp_dir().join(format!("tofuboi-wal-{}.db", std::process::id()));
+        let first = Arc::new(History::open(&path).unwrap());
+        let second = Arc::new(History::open(&path).unwrap());
+        let mode: String = first
+            .conn
+            .lock()
+            .unwrap()
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+
+        let mut tasks = Vec::new();
+        for task in 0..8 {
+            let history = if task % 2 == 0 {
+                first.clone()
+            } else {
+                second.clone()
+            };
+            tasks.push(tokio::task::spawn_blocking(move || {
+                for i in 0..25 {
+                    history.record(&entry(task, "abc", i))?;
+                    history.count_language("en")?;
+                }
+                Ok::<_, rusqlite::Error>(())
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(
+            first.top_languages(1).unwrap(),
+            vec![("en".to_string(), 200)]
+        );
+        assert!(first.wal_size() > 0);
+        assert_eq!(History::open_in_memory().unwrap().wal_size(), 0);
+        drop((first, second));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn test_format_history() {
+        let text = format_history("Your recent transcripts:", &[entry(1, "abc", 86_400)]);
+        assert_eq!(
+            text,
+            "Your recent transcripts:\n1. abc (en), 1970-01-02: https://paste.example/abc-86400"
+        );
+    }
+}
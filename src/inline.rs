@@ -0,0 +1,120 @@
+use crate::formatter::{format_stamp, split_safe_utf8, Paragraph};
+use teloxide::types::{
+    InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText,
+};
+
+/// Most paragraphs offered for one inline query.
+const MAX_INLINE_RESULTS: usize = 10;
+
+/// Characters of a paragraph shown under its title.
+const DESCRIPTION_CHARS: usize = 100;
+
+/// Longest text an inline result may post.
+const MESSAGE_LIMIT: usize = 4096;
+
+/// One result per paragraph of a cached transcript; choosing one posts the
+/// paragraph to the chat.
+pub fn paragraph_results(video_id: &str, paragraphs: &[Paragraph]) -> Vec<InlineQueryResult> {
+    paragraphs
+        .iter()
+        .take(MAX_INLINE_RESULTS)
+        .enumerate()
+        .map(|(i, paragraph)| {
+            let text = split_safe_utf8(&paragraph.text, MESSAGE_LIMIT)
+                .first()
+                .copied()
+                .unwrap_or_default();
+            let content = InputMessageContent::Text(InputMessageContentText::new(text));
+            let title = format!("[{}] {}", format_stamp(paragraph.start_ms), video_id);
+            InlineQueryResult::Article(
+                InlineQueryResultArticle::new(format!("{}:{}", video_id, i), title, content)
+                    .description(snippet(&paragraph.text)),
+            )
+        })
+        .collect()
+}
+
+/// Offered when the transcript is not cached: posts the video's link, which
+/// the bot answers with the transcript when sent to it.
+pub fn fetch_prompt_result(video_id: &str) -> InlineQueryResult {
+    let url = format!("https://youtu.be/{}", video_id);
+    let content = InputMessageContent::Text(InputMessageContentText::new(url.as_str()));
+    InlineQueryResult::Article(
+        InlineQueryResultArticle::new(format!("{}:fetch", video_id), "Tap to fetch", content)
+            .description(url),
+    )
+}
+
+/// The first [`DESCRIPTION_CHARS`] characters of a paragraph.
+fn snippet(text: &str) -> String {
+    text.chars().take(DESCRIPTION_CHARS).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(result: &InlineQueryResult) -> &InlineQueryResultArticle {
+        match result {
+            InlineQueryResult::Article(article) => article,
+            other => panic!("expected an article, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_descriptions_are_truncated() {
+        let long = "é".repeat(150);
+        let paragraphs = vec![
+            Paragraph {
+                start_ms: 0,
+                text: "short".to_string(),
+            },
+            Paragraph {
+                start_ms: 65_000,
+                text: long.clone(),
+            },
+        ];
+
+        let results = paragraph_results("abc123", &paragraphs);
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(article(&results[0]).description.as_deref(), Some("short"));
+        let second = article(&results[1]);
+        assert_eq!(second.title, "[01:05] abc123");
+        assert_eq!(
+            second.description.as_deref(),
+            Some("é".repeat(100).as_str())
+        );
+        // The full paragraph is still what gets posted
+        match &second.input_message_content {
+            InputMessageContent::Text(content) => assert_eq!(content.message_text, long),
+            other => panic!("expected text content, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_at_most_ten_results() {
+        let paragraphs: Vec<Paragraph> = (0..15)
+            .map(|i| Paragraph {
+                start_ms: i * 1000,
+                text: format!("paragraph {}", i),
+            })
+            .collect();
+
+        assert_eq!(paragraph_results("abc123", &paragraphs).len(), 10);
+    }
+
+    #[test]
+    fn test_fetch_prompt_posts_the_video_link() {
+        let result = fetch_prompt_result("abc123");
+        let prompt = article(&result);
+
+        assert_eq!(prompt.title, "Tap to fetch");
+        match &prompt.input_message_content {
+            InputMessageContent::Text(content) => {
+                assert_eq!(content.message_text, "https://youtu.be/abc123")
+            }
+            other => panic!("expected text content, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Language buttons shown on one page of the keyboard.
+const LANGS_PER_PAGE: usize = 12;
+
+const BUTTONS_PER_ROW: usize = 4;
+
+/// What pressing a keyboard button asks for.
+#[derive(Debug, PartialEq)]
+pub enum KeyboardAction {
+    /// Fetch the transcript again in `lang`
+    Fetch { video_id: String, lang: String },
+    /// Show another page of languages
+    Page { video_id: String, page: usize },
+}
+
+/// Builds one page of buttons for `langs`, with previous/next buttons when
+/// there is more than one page. Out of range pages show the last one.
+pub fn languages_keyboard(video_id: &str, langs: &[String], page: usize) -> InlineKeyboardMarkup {
+    let pages = langs.len().div_ceil(LANGS_PER_PAGE).max(1);
+    let page = page.min(pages - 1);
+
+    let start = page * LANGS_PER_PAGE;
+    let end = (start + LANGS_PER_PAGE).min(langs.len());
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = langs[start..end]
+        .chunks(BUTTONS_PER_ROW)
+        .map(|row| {
+            row.iter()
+                .map(|lang| {
+                    InlineKeyboardButton::callback(
+                        lang.clone(),
+                        format!("lang:{}:{}", video_id, lang),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut nav = Vec::new();
+    if page > 0 {
+        nav.push(InlineKeyboardButton::callback(
+            "‹ Prev",
+            format!("langs:{}:{}", video_id, page - 1),
+        ));
+    }
+    if page + 1 < pages {
+        nav.push(InlineKeyboardButton::callback(
+            "Next ›",
+            format!("langs:{}:{}", video_id, page + 1),
+        ));
+    }
+    if !nav.is_empty() {
+        rows.push(nav);
+    }
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// Reads the callback data of a button made by [`languages_keyboard`].
+pub fn parse_callback(data: &str) -> Option<KeyboardAction> {
+    let (kind, rest) = data.split_once(':')?;
+    let (video_id, value) = rest.split_once(':')?;
+    if video_id.is_empty() || value.is_empty() {
+        return None;
+    }
+
+    match kind {
+        "lang" => Some(KeyboardAction::Fetch {
+            video_id: video_id.to_string(),
+            lang: value.to_string(),
+        }),
+        "langs" => Some(KeyboardAction::Page {
+            video_id: video_id.to_string(),
+            page: value.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::types::InlineKeyboardButtonKind;
+
+    fn langs(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("l{}", i)).collect()
+    }
+
+    fn callback_data(button: &InlineKeyboardButton) -> &str {
+        match &button.kind {
+            InlineKeyboardButtonKind::CallbackData(data) => data,
+            other => panic!("unexpected button {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_single_page_has_no_navigation() {
+        let keyboard = languages_keyboard("abc123", &langs(5), 0);
+
+        assert_eq!(keyboard.inline_keyboard.len(), 2);
+        assert_eq!(keyboard.inline_keyboard[0].len(), BUTTONS_PER_ROW);
+        assert_eq!(
+            callback_data(&keyboard.inline_keyboard[1][0]),
+            "lang:abc123:l4"
+        );
+    }
+
+    #[test]
+    fn test_pages_link_to_each_other() {
+        let all = langs(LANGS_PER_PAGE * 2 + 1);
+
+        let first = languages_keyboard("abc123", &all, 0);
+        let nav = first.inline_keyboard.last().unwrap();
+        assert_eq!(nav.len(), 1);
+        assert_eq!(callback_data(&nav[0]), "langs:abc123:1");
+
+        let middle = languages_keyboard("abc123", &all, 1);
+        let nav = middle.inline_keyboard.last().unwrap();
+        assert_eq!(callback_data(&nav[0]), "langs:abc123:0");
+        assert_eq!(callback_data(&nav[1]), "langs:abc123:2");
+
+        // Past the end clamps to the last page, which holds one language
+        let last = languages_keyboard("abc123", &all, 9);
+        assert_eq!(last.inline_keyboard[0].len(), 1);
+    }
+
+    #[test]
+    fn test_parse_callback() {
+        assert_eq!(
+            parse_callback("lang:abc123:zh-HK"),
+            Some(KeyboardAction::Fetch {
+                video_id: "abc123".to_string(),
+                lang: "zh-HK".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_callback("langs:abc123:2"),
+            Some(KeyboardAction::Page {
+                video_id: "abc123".to_string(),
+                page: 2,
+            })
+        );
+        assert_eq!(parse_callback("langs:abc123:x"), None);
+        assert_eq!(parse_callback("lang:abc123:"), None);
+        assert_eq!(parse_callback("other"), None);
+    }
+}
@@ -0,0 +1,80 @@
+/// Characters of transcript text inspected when checking its script.
+const SAMPLE_CHARS: usize = 400;
+
+/// Shown when a transcript fetched as English is written in another script.
+pub const UNEXPECTED_SCRIPT_WARNING: &str = "⚠️ The fetched transcript appears to contain non-Latin characters despite the 'en' language request. Did you mean a different language?";
+
+/// Joins lines until roughly [`SAMPLE_CHARS`] characters have been collected.
+pub fn sample_text<I: Iterator<Item = String>>(lines: I) -> String {
+    let mut sample = String::new();
+    for line in lines {
+        if sample.chars().count() >= SAMPLE_CHARS {
+            break;
+        }
+        if !sample.is_empty() {
+            sample.push(' ');
+        }
+        sample.push_str(&line);
+    }
+    sample.chars().take(SAMPLE_CHARS).collect()
+}
+
+/// True when an English request got text with letters from another script,
+/// which suggests YouTube returned the wrong language. Punctuation such as
+/// curly quotes and dashes does not count.
+pub fn detect_unexpected_script(text: &str, requested_lang: &str) -> bool {
+    requested_lang.starts_with("en")
+        && text
+            .chars()
+            .any(|c| c.is_alphabetic() && !is_latin_letter(c))
+}
+
+/// Letters of the Latin script, including accented and full-width forms.
+fn is_latin_letter(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0041..=0x024F | 0x1E00..=0x1EFF | 0x2C60..=0x2C7F | 0xA720..=0xA7FF | 0xFF21..=0xFF5A
+    ) || c == 'ª'
+        || c == 'º'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin_text_has_no_warning() {
+        let sample = "Welcome back to the channel, today we are looking at how bread is made.";
+        assert!(!detect_unexpected_script(sample, "en"));
+        // Typographic punctuation and accented names are still Latin
+        let sample = "It’s “Crème brûlée” — Zoë’s favourite…";
+        assert!(!detect_unexpected_script(sample, "en-GB"));
+    }
+
+    #[test]
+    fn test_arabic_text_warns() {
+        let sample = "مرحبا بكم في القناة، اليوم سنتحدث عن كيفية صنع الخبز في المنزل";
+        assert!(detect_unexpected_script(sample, "en"));
+    }
+
+    #[test]
+    fn test_cjk_text_warns() {
+        let sample = "歡迎回到頻道，今天我們來看看麵包是怎樣製作的";
+        assert!(detect_unexpected_script(sample, "en"));
+    }
+
+    #[test]
+    fn test_devanagari_text_warns() {
+        let sample = "चैनल पर आपका स्वागत है, आज हम देखेंगे कि रोटी कैसे बनती है";
+        assert!(detect_unexpected_script(sample, "en"));
+        // Only English requests are checked
+        assert!(!detect_unexpected_script(sample, "hi"));
+    }
+
+    #[test]
+    fn test_sample_text_is_bounded() {
+        let lines = std::iter::repeat_with(|| "word ".repeat(20));
+        assert_eq!(sample_text(lines).chars().count(), SAMPLE_CHARS);
+        assert_eq!(sample_text(["a", "b"].map(String::from).into_iter()), "a b");
+    }
+}
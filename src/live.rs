@@ -0,0 +1,78 @@
+use reqwest::Client;
+
+/// Sent before the captions of a stream that is still live.
+pub const LIVE_STREAM_WARNING: &str =
+    "This is a live stream; captions may be incomplete — try again after it ends.";
+
+/// True when a watch page describes a broadcast that is live right now.
+/// Finished streams keep `isLiveContent` but lose `isLiveNow`.
+pub fn page_is_live(html: &str) -> bool {
+    html.contains("\"isLiveNow\":true") || html.contains("\"isLive\":true")
+}
+
+/// Checks the video's watch page for a live broadcast. Any failure counts
+/// as not live, since the check only decides whether to warn.
+pub async fn is_live(client: &Client, video_id: &str) -> bool {
+    // Use mockito server URL in tests, otherwise the real YouTube page
+    #[cfg(test)]
+    let base_url = mockito::server_url();
+
+    #[cfg(not(test))]
+    let base_url = "https://www.youtube.com".to_string();
+
+    let url = format!("{}/watch?v={}", base_url, video_id);
+    let page = match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => response.text().await,
+        Ok(response) => {
+            log::debug!(
+                "Live check of {} got status {}",
+                video_id,
+                response.status()
+            );
+            return false;
+        }
+        Err(e) => Err(e),
+    };
+    match page {
+        Ok(html) => page_is_live(&html),
+        Err(e) => {
+            log::debug!("Live check of {} failed: {}", video_id, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::mock;
+
+    #[test]
+    fn test_page_is_live() {
+        assert!(page_is_live(
+            r#"{"videoDetails":{"isLiveContent":true,"isLive":true}}"#
+        ));
+        assert!(page_is_live(
+            r#"{"liveBroadcastDetails":{"isLiveNow":true}}"#
+        ));
+        // A stream that has ended
+        assert!(!page_is_live(
+            r#"{"isLiveContent":true,"liveBroadcastDetails":{"isLiveNow":false}}"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_is_live() {
+        let _live = mock("GET", "/watch?v=liveNow0001")
+            .with_status(200)
+            .with_body(r#"<script>var x = {"isLiveNow":true};</script>"#)
+            .create();
+        let _missing = mock("GET", "/watch?v=missing0001")
+            .with_status(404)
+            .create();
+
+        let client = Client::new();
+        assert!(is_live(&client, "liveNow0001").await);
+        assert!(!is_live(&client, "missing0001").await);
+    }
+}
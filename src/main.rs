@@ -1,271 +1,3419 @@
+mod analysis;
+mod args;
+mod batch;
+mod bot_ext;
+mod cache;
+mod chapters;
+mod cli;
+mod config;
+mod data_export;
+mod dedup;
+mod delivery;
+mod diagnostics;
+mod diff;
+#[cfg(feature = "email")]
+mod email;
+mod error_handler;
+mod export;
+mod feedback;
+mod filename;
+mod formatter;
+mod health;
+mod history;
+mod inline;
+mod keyboard;
+mod language;
+mod live;
+mod metrics;
+mod middleware;
+mod punctuate;
+mod ratelimit;
+mod selftest;
+mod summarize;
+mod telemetry;
+mod templates;
 mod transcript;
+mod translation;
+mod upload;
+mod video_id;
 
-use html_escape::decode_html_entities;
+use bot_ext::BotExt;
+use cache::CachedFetcher;
+use config::AppConfig;
+use data_export::{DataExport, ExportLimiter};
+use dedup::RecentMessages;
+use delivery::DeliveryMode;
+use export::OutputFormat;
+use feedback::FeedbackLimiter;
+use filename::FilenameParts;
+use metrics::Counter;
+use ratelimit::{cooldown_secs, RateLimiter};
 use reqwest::Client;
-use std::env; // Import the env module
+use std::sync::Arc;
 use teloxide::{
     dispatching::{UpdateFilterExt, UpdateHandler},
+    net::Download,
+    payloads::{SendDocument, SendMessage},
     prelude::*,
+    requests::{JsonRequest, MultipartRequest},
+    types::{
+        CallbackQuery, Document, InlineKeyboardMarkup, InlineQuery, InputFile, ParseMode,
+        ReplyParameters,
+    },
+    utils::command::BotCommands,
 };
-use transcript::TranscriptService;
+use templates::{Reply, ReplyFormat};
+use transcript::{
+    BilingualEntry, FetchError, FetchedTranscript, TranscriptFetcher, TranscriptService,
+    YoutubeTranscriptFetcher,
+};
+use translation::TranslationBackend;
+use video_id::parse_video_input;
+use ytranscript::TranscriptResponse;
 
 type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
+#[derive(BotCommands, Clone)]
+#[command(
+    rename_rule = "lowercase",
+    description = "These commands are supported:"
+)]
+enum Command {
+    #[command(description = "fetch a transcript: /transcript <video_id> [lang].")]
+    Transcript(String),
+    #[command(description = "show transcript statistics without uploading it.")]
+    Info(String),
+    #[command(description = "list the caption languages of a video.")]
+    Langs(String),
+    #[command(description = "list the languages requested most often.")]
+    Languages,
+    #[command(description = "show the most common words: /wordfreq <video_id> [lang].")]
+    Wordfreq(String),
+    #[command(description = "most common words across videos: /corpusfreq <id1> <id2> ...")]
+    Corpusfreq(String),
+    #[command(description = "summarize a transcript: /summarize <video_id> [lang].")]
+    Summarize(String),
+    #[command(description = "translate a transcript: /translate <video_id> <target_lang> [lang].")]
+    Translate(String),
+    #[command(description = "compare two transcripts: /diff <id1> <id2> [lang1] [lang2].")]
+    Diff(String),
+    #[command(description = "list your recent transcripts, or a video's: /history [video_id].")]
+    History(String),
+    #[command(description = "delete your transcript history: /clearhistory --confirm.")]
+    ClearHistory(String),
+    #[command(description = "download everything stored about you as JSON.")]
+    Export,
+    #[command(description = "send feedback to the bot operator.")]
+    Feedback(String),
+    #[command(description = "check that the bot is alive.")]
+    Ping,
+    #[command(description = "show which build of the bot is running.")]
+    Version,
+    #[command(
+        rename = "admin_setdescription",
+        description = "admin: set the bot's profile description."
+    )]
+    AdminSetDescription(String),
+    #[command(description = "admin: show runtime diagnostics.")]
+    AdminInfo,
+}
+
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
+    let telemetry = telemetry::Telemetry::from_env();
+
+    // `tofuboi fetch <video_id>` runs once from the command line instead
+    if let Some(args) = cli::parse_args(std::env::args().skip(1)) {
+        let result = match args {
+            Ok(args) => cli::run(args, &AppConfig::from_env()).await,
+            Err(usage) => Err(usage),
+        };
+        if let Some(telemetry) = telemetry {
+            telemetry.shutdown();
+        }
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Err(e) = config::bot_token() {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
+
     log::info!("Launching Telegram bot...");
 
     let bot = Bot::from_env();
+    // Make the commands show up in Telegram's "/" menu
+    if let Err(e) = bot.set_my_commands(Command::bot_commands()).await {
+        log::warn!("Failed to register bot commands: {}", e);
+    }
+    let config = Arc::new(AppConfig::from_env());
+    let client = match upload::build_http_client(config.outbound_proxy.as_deref()) {
+        Ok(client) => Arc::new(client),
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    Dispatcher::builder(bot, handler_tree())
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+    let fetcher = Arc::new(CachedFetcher::new(
+        YoutubeTranscriptFetcher::new(config.yt_user_agent.as_deref()),
+        config.cache_ttl,
+    ));
+    cache::spawn_refresh_task(fetcher.clone(), config.cache_refresh_interval);
+
+    if let Some(video_id) = &config.selftest_video_id {
+        match selftest::run_selftest(fetcher.as_ref(), client.clone(), video_id).await {
+            Ok(url) => log::info!("Startup self-test passed: {}", url),
+            Err(e) if config.selftest_strict => {
+                log::error!("Startup self-test failed, aborting: {}", e);
+                std::process::exit(1);
+            }
+            Err(e) => log::warn!("Startup self-test failed: {}", e),
+        }
+    }
+
+    if let Some(addr) = config.health_addr {
+        tokio::spawn(async move {
+            if let Err(e) = health::serve(addr).await {
+                log::error!("Health server stopped: {}", e);
+            }
+        });
+    }
+
+    // Held for the life of the bot; dropping it stops the reloads
+    let _templates_watcher = config.templates_path.clone().and_then(|path| {
+        templates::watch(path, config.templates.clone())
+            .inspect_err(|e| log::warn!("Template hot-reload disabled: {}", e))
+            .ok()
+    });
+
+    let rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit_requests,
+        config.rate_limit_window,
+    ));
+
+    let error_handler = error_handler::ErrorReporter::new(bot.clone(), config.admin_chat_id);
+    Dispatcher::builder(
+        bot,
+        handler_tree::<CachedFetcher<YoutubeTranscriptFetcher>>(),
+    )
+    .dependencies(dptree::deps![
+        client,
+        config,
+        fetcher,
+        Arc::new(FeedbackLimiter::default()),
+        Arc::new(ExportLimiter::default()),
+        Arc::new(RecentMessages::default()),
+        rate_limiter
+    ])
+    .error_handler(error_handler)
+    .enable_ctrlc_handler()
+    .build()
+    .dispatch()
+    .await;
+
+    if let Some(telemetry) = telemetry {
+        telemetry.shutdown();
+    }
+}
+
+fn handler_tree<T: TranscriptFetcher>(
+) -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+    middleware::request_logging()
+        .chain(middleware::rate_limit_layer())
+        .branch(
+            Update::filter_message()
+                .branch(
+                    dptree::entry()
+                        .filter_command::<Command>()
+                        .endpoint(handle_command::<T>),
+                )
+                .endpoint(handle_message::<T>),
+        )
+        .branch(Update::filter_callback_query().endpoint(handle_callback::<T>))
+        .branch(Update::filter_inline_query().endpoint(handle_inline_query::<T>))
 }
 
-fn handler_tree() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
-    dptree::entry().branch(Update::filter_message().endpoint(handle_message))
+#[allow(clippy::too_many_arguments)]
+async fn handle_command<T: TranscriptFetcher>(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    client: Arc<Client>,
+    fetcher: Arc<T>,
+    config: Arc<AppConfig>,
+    feedback_limiter: Arc<FeedbackLimiter>,
+    export_limiter: Arc<ExportLimiter>,
+    rate_limiter: Arc<RateLimiter>,
+) -> HandlerResult {
+    match cmd {
+        Command::Transcript(args) => {
+            handle_transcript_request(&bot, &msg, &args, client, fetcher.as_ref(), &config).await
+        }
+        Command::Info(args) => handle_info(&bot, &msg, &args, fetcher.as_ref(), &config).await,
+        Command::Langs(video_id) => {
+            handle_langs(&bot, &msg, video_id.trim(), fetcher.as_ref(), &config).await
+        }
+        Command::Wordfreq(args) => {
+            handle_wordfreq(&bot, &msg, &args, fetcher.as_ref(), &config).await
+        }
+        Command::Corpusfreq(args) => {
+            handle_corpusfreq(&bot, &msg, &args, fetcher.as_ref(), &config).await
+        }
+        Command::Summarize(args) => {
+            handle_summarize(&bot, &msg, &args, &client, fetcher.as_ref(), &config).await
+        }
+        Command::Translate(args) => {
+            handle_translate(&bot, &msg, &args, &client, fetcher.as_ref(), &config).await
+        }
+        Command::Diff(args) => {
+            handle_diff(&bot, &msg, &args, client, fetcher.as_ref(), &config).await
+        }
+        Command::Feedback(text) => {
+            handle_feedback(&bot, &msg, text.trim(), &config, &feedback_limiter).await
+        }
+        Command::Languages => handle_languages(&bot, &msg, &config).await,
+        Command::History(video_id) => handle_history(&bot, &msg, video_id.trim(), &config).await,
+        Command::ClearHistory(args) => {
+            handle_clear_history(&bot, &msg, args.trim(), fetcher.as_ref(), &config).await
+        }
+        Command::Export => handle_export(&bot, &msg, &config, &export_limiter).await,
+        Command::Ping => handle_ping(&bot, &msg, &config).await,
+        Command::Version => {
+            reply_to(&bot, &msg, &config, version_text()).await?;
+            Ok(())
+        }
+        Command::AdminSetDescription(text) => {
+            handle_set_description(&bot, &msg, text.trim(), &config).await
+        }
+        Command::AdminInfo => {
+            handle_admin_info(&bot, &msg, fetcher.as_ref(), &rate_limiter, &config).await
+        }
+    }
 }
 
-async fn handle_message(bot: Bot, msg: Message) -> HandlerResult {
-    let text = match msg.text() {
-        Some(text) => text,
-        None => {
-            bot.send_message(msg.chat.id, "Please provide a valid YouTube video ID.")
+/// Handles presses on the language keyboard: fetches the chosen language or
+/// turns the keyboard to another page.
+async fn handle_callback<T: TranscriptFetcher>(
+    bot: Bot,
+    q: CallbackQuery,
+    client: Arc<Client>,
+    fetcher: Arc<T>,
+    config: Arc<AppConfig>,
+) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).await?;
+    let action = q.data.as_deref().and_then(keyboard::parse_callback);
+    let (Some(action), Some(msg)) = (action, q.regular_message()) else {
+        return Ok(());
+    };
+    // The button sits on the bot's own message; act on behalf of whoever pressed it
+    let mut msg = msg.clone();
+    msg.from = Some(q.from.clone());
+    let msg = &msg;
+
+    match action {
+        keyboard::KeyboardAction::Fetch { video_id, lang } => {
+            let request = format!("{} lang={}", video_id, lang);
+            handle_transcript_request(&bot, msg, &request, client, fetcher.as_ref(), &config)
+                .await?;
+        }
+        keyboard::KeyboardAction::Page { video_id, page } => {
+            let langs = fetcher.list_languages(&video_id).await?;
+            bot.edit_message_reply_markup(msg.chat.id, msg.id)
+                .reply_markup(keyboard::languages_keyboard(&video_id, &langs, page))
                 .await?;
-            return Ok(());
         }
+    }
+    Ok(())
+}
+
+/// Answers `@bot <video_id>` in any chat with paragraphs of the transcript
+/// when it is cached, or else with a result that posts the video's link.
+async fn handle_inline_query<T: TranscriptFetcher>(
+    bot: Bot,
+    q: InlineQuery,
+    fetcher: Arc<T>,
+    config: Arc<AppConfig>,
+) -> HandlerResult {
+    let Some(video_id) = parse_video_input(q.query.trim()) else {
+        bot.answer_inline_query(q.id, Vec::new()).await?;
+        return Ok(());
     };
 
-    let parts: Vec<&str> = text.split_whitespace().collect();
-    if parts.is_empty() {
-        bot.send_message(msg.chat.id, "Please provide a video ID.")
-            .await?;
+    let paragraphs = fetcher
+        .cached(&video_id, config.default_lang())
+        .map(|entries| formatter::format_paragraphs(&entries))
+        .unwrap_or_default();
+    let results = if paragraphs.is_empty() {
+        vec![inline::fetch_prompt_result(&video_id)]
+    } else {
+        inline::paragraph_results(&video_id, &paragraphs)
+    };
+    bot.answer_inline_query(q.id, results).await?;
+    Ok(())
+}
+
+/// Lists the languages requested most often across all users.
+async fn handle_languages(bot: &Bot, msg: &Message, config: &AppConfig) -> HandlerResult {
+    let Some(history) = &config.history else {
+        reply_to(bot, msg, config, "Language statistics are not enabled.").await?;
+        return Ok(());
+    };
+
+    match history.top_languages(history::LANGUAGES_LIMIT) {
+        Ok(languages) if languages.is_empty() => {
+            reply_to(bot, msg, config, "No languages have been requested yet.").await?;
+        }
+        Ok(languages) => {
+            let list = history::format_languages(&languages);
+            send_reply(bot, msg, config, config.templates().info(&list)).await?;
+        }
+        Err(e) => {
+            log::error!("Failed to read language statistics: {}", e);
+            let reply = config
+                .templates()
+                .error("Could not read the language statistics.");
+            send_reply(bot, msg, config, reply).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists the last uploads of the user, or of one video by anyone.
+async fn handle_history(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    config: &AppConfig,
+) -> HandlerResult {
+    let Some(history) = &config.history else {
+        reply_to(bot, msg, config, "Transcript history is not enabled.").await?;
+        return Ok(());
+    };
+    let Some(user) = msg.from.as_ref() else {
         return Ok(());
+    };
+
+    let (title, entries) = if video_id.is_empty() {
+        (
+            "Your recent transcripts:".to_string(),
+            history.recent_for_user(user.id.0, history::HISTORY_LIMIT),
+        )
+    } else {
+        let Some(video_id) = parse_video_input(video_id) else {
+            reply_to(bot, msg, config, "Please provide a valid YouTube video ID.").await?;
+            return Ok(());
+        };
+        (
+            format!("Recent transcripts of {}:", video_id),
+            history.recent_for_video(&video_id, history::HISTORY_LIMIT),
+        )
+    };
+
+    match entries {
+        Ok(entries) if entries.is_empty() => {
+            reply_to(bot, msg, config, "No transcripts found.").await?;
+        }
+        Ok(entries) => {
+            let list = history::format_history(&title, &entries);
+            send_reply(bot, msg, config, config.templates().info(&list)).await?;
+        }
+        Err(e) => {
+            log::error!("Failed to read transcript history: {}", e);
+            let reply = config
+                .templates()
+                .error("Could not read the transcript history.");
+            send_reply(bot, msg, config, reply).await?;
+        }
     }
+    Ok(())
+}
 
-    let video_id = parts[0].trim();
-    let requested_lang = parts.get(1).copied().unwrap_or("en");
+/// Sends the user everything stored about them as a JSON document, at most
+/// once a day.
+async fn handle_export(
+    bot: &Bot,
+    msg: &Message,
+    config: &AppConfig,
+    limiter: &ExportLimiter,
+) -> HandlerResult {
+    let Some(history) = &config.history else {
+        let text = "Transcript history is not enabled, so nothing is stored about you.";
+        reply_to(bot, msg, config, text).await?;
+        return Ok(());
+    };
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    if let Err(wait) = limiter.check(user.id) {
+        let text = format!(
+            "You can export your data once a day. Try again in {} hours.",
+            cooldown_secs(wait).div_ceil(3600)
+        );
+        reply_to(bot, msg, config, text).await?;
+        return Ok(());
+    }
 
-    match TranscriptService::fetch(video_id, requested_lang).await {
-        Ok((transcript, info)) => {
-            if let Some(info) = info {
-                bot.send_message(msg.chat.id, info).await?;
+    let export = history
+        .all_for_user(user.id.0)
+        .map_err(|e| e.to_string())
+        .and_then(|entries| {
+            DataExport {
+                user_id: user.id.0,
+                exported_at: msg.date.timestamp(),
+                history: entries,
             }
-            send_transcript(&bot, &msg, transcript).await?;
+            .to_json()
+            .map_err(|e| e.to_string())
+        });
+    match export {
+        Ok(json) => {
+            let name = data_export::file_name(user.id.0, msg.date.date_naive());
+            let document = InputFile::memory(json.into_bytes()).file_name(name);
+            document_to(bot, msg, config, document).await?;
         }
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("Error fetching transcript: {}", e))
-                .await?;
+            log::error!("Failed to export data for user {}: {}", user.id, e);
+            let reply = config.templates().error("Could not export your data.");
+            send_reply(bot, msg, config, reply).await?;
         }
     }
-
     Ok(())
 }
 
-/// Uploads content to Pastebin and returns the resulting URL
-async fn upload_to_pastebin(
-    content: &str,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let client = Client::new();
-
-    // Get API key from environment variable
-    let api_key = match env::var("PASTEBIN_KEY") {
-        Ok(key) => key,
-        Err(_) => return Err("PASTEBIN_KEY environment variable not set".into()),
+/// Deletes the user's upload history once they confirm with `--confirm`,
+/// along with the cached transcripts of those uploads.
+async fn handle_clear_history<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    args: &str,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let Some(history) = &config.history else {
+        reply_to(bot, msg, config, "Transcript history is not enabled.").await?;
+        return Ok(());
+    };
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
     };
 
-    // Define a user agent, getting it from env var or using a default
-    let user_agent = env::var("UPLOAD_USER_AGENT").unwrap_or_else(|_| "tofuboi/1.0".to_string());
-
-    // Use mockito server URL in tests, otherwise use the real Pastebin URL
-    #[cfg(test)]
-    let upload_url = {
-        use mockito;
-        mockito::server_url()
+    let result = if args == "--confirm" {
+        history.delete_for_user(user.id.0).map(|deleted| {
+            for entry in &deleted {
+                fetcher.evict(&entry.video_id, &entry.lang);
+            }
+            history::format_cleared(deleted.len())
+        })
+    } else {
+        history
+            .count_for_user(user.id.0)
+            .map(history::format_clear_prompt)
     };
 
-    #[cfg(not(test))]
-    let upload_url = "https://pastebin.com/api/api_post.php".to_string();
+    match result {
+        Ok(text) => {
+            reply_to(bot, msg, config, text).await?;
+        }
+        Err(e) => {
+            log::error!("Failed to clear transcript history: {}", e);
+            let reply = config
+                .templates()
+                .error("Could not delete the transcript history.");
+            send_reply(bot, msg, config, reply).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Longest short description Telegram accepts, in characters.
+const SHORT_DESCRIPTION_LIMIT: usize = 120;
 
-    // Convert the content to a String to satisfy type requirements
-    let content_string = content.to_string();
-    let paste_option = "paste".to_string();
+/// Replaces the bot's profile description and short description.
+async fn handle_set_description(
+    bot: &Bot,
+    msg: &Message,
+    text: &str,
+    config: &AppConfig,
+) -> HandlerResult {
+    let Some(admin) = msg.from.as_ref().filter(|user| config.is_admin(user.id.0)) else {
+        reply_to(bot, msg, config, "Unauthorized").await?;
+        return Ok(());
+    };
+    if text.is_empty() {
+        reply_to(bot, msg, config, "Usage: /admin_setdescription <text>").await?;
+        return Ok(());
+    }
 
-    // Send request to Pastebin with the required parameters
-    let response = client
-        .post(upload_url)
-        .header(reqwest::header::USER_AGENT, user_agent)
-        .header(
-            reqwest::header::CONTENT_TYPE,
-            "application/x-www-form-urlencoded",
-        )
-        .form(&[
-            ("api_dev_key", &api_key),
-            ("api_paste_code", &content_string),
-            ("api_option", &paste_option),
-        ])
-        .send()
+    bot.set_my_description().description(text).await?;
+    bot.set_my_short_description()
+        .short_description(short_description(text))
         .await?;
+    log::info!("Bot description updated by admin {}", admin.id);
 
-    if !response.status().is_success() {
-        // Get the status code and response body for the error message
-        let status = response.status();
-        let error_body = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Failed to read response body".to_string());
-        return Err(format!(
-            "Upload failed with status: {}, response: {}",
-            status, error_body
-        )
-        .into());
+    reply_to(bot, msg, config, "Description updated.").await?;
+    Ok(())
+}
+
+/// Replies to admins with runtime diagnostics as a JSON code block.
+async fn handle_admin_info<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    fetcher: &T,
+    rate_limiter: &RateLimiter,
+    config: &AppConfig,
+) -> HandlerResult {
+    if !msg
+        .from
+        .as_ref()
+        .is_some_and(|user| config.is_admin(user.id.0))
+    {
+        reply_to(bot, msg, config, "Unauthorized").await?;
+        return Ok(());
     }
 
-    // Get the URL from the response body
-    let url = response.text().await?.trim().to_string();
+    let diagnostics = diagnostics::Diagnostics {
+        memory_kb: diagnostics::memory_kb(),
+        tokio_tasks: diagnostics::tokio_tasks(),
+        cache_entries: fetcher.cache_len(),
+        rate_limit_keys: rate_limiter.tracked(),
+        wal_bytes: config.history.as_ref().map(|history| history.wal_size()),
+    };
+    let json = serde_json::to_string_pretty(&diagnostics.to_json())?;
+    reply_in_mode(
+        bot,
+        msg,
+        config,
+        teloxide::utils::markdown::code_block_with_lang(&json, "json"),
+        Some(ParseMode::MarkdownV2),
+    )
+    .await?;
+    Ok(())
+}
 
-    // Replace standard URL with raw URL
-    let raw_url = url.replace("https://pastebin.com/", "https://pastebin.com/raw/");
+/// The crate version, git commit and build time, all fixed at compile time.
+fn version_text() -> String {
+    format!(
+        "tofuboi {}\nCommit: {}\nBuilt: {}",
+        env!("CARGO_PKG_VERSION"),
+        env!("GIT_COMMIT"),
+        diagnostics::built_at()
+    )
+}
 
-    Ok(raw_url)
+fn short_description(text: &str) -> String {
+    text.chars().take(SHORT_DESCRIPTION_LIMIT).collect()
 }
 
-/// Helper function to upload transcript to Pastebin and send the link to the user.
-/// Instead of sending the transcript directly, it uploads the text and sends the resulting URL.
-async fn send_transcript(
+/// Replies with the time between the message being sent and this reply.
+async fn handle_ping(bot: &Bot, msg: &Message, config: &AppConfig) -> HandlerResult {
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64);
+    // Message dates have one-second precision, so clamp clock skew to zero
+    let latency_ms = (now_ms - msg.date.timestamp_millis()).max(0);
+
+    reply_to(
+        bot,
+        msg,
+        config,
+        format!("Pong! 🏓 Latency: {}ms", latency_ms),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Forwards a user's feedback to the operator's chat, at most twice a day.
+async fn handle_feedback(
     bot: &Bot,
     msg: &Message,
-    transcript: Vec<ytranscript::TranscriptResponse>,
-) -> Result<(), teloxide::RequestError> {
-    if transcript.is_empty() {
-        bot.send_message(
-            msg.chat.id,
-            "Transcript could not be retrieved or is empty.",
+    text: &str,
+    config: &AppConfig,
+    limiter: &FeedbackLimiter,
+) -> HandlerResult {
+    let Some(feedback_chat_id) = config.feedback_chat_id else {
+        reply_to(bot, msg, config, "Feedback collection is not configured.").await?;
+        return Ok(());
+    };
+
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+
+    if text.is_empty() {
+        reply_to(bot, msg, config, "Usage: /feedback <message>").await?;
+        return Ok(());
+    }
+
+    if !limiter.try_acquire(user.id) {
+        reply_to(
+            bot,
+            msg,
+            config,
+            format!(
+                "You can send at most {} feedback messages per day.",
+                feedback::MAX_FEEDBACK_PER_DAY
+            ),
         )
         .await?;
         return Ok(());
     }
 
-    // Combine all transcript entries into a single string
-    let mut full_transcript = String::new();
+    let forwarded = feedback::format_feedback(user.id, &msg.date.to_string(), text);
+    bot.send_with_footer(
+        feedback_chat_id,
+        forwarded,
+        config.message_footer.as_deref(),
+        None,
+    )
+    .await?;
+    // Only the sender is logged; the message itself stays out of the logs
+    log::info!("Forwarded feedback from user {}", user.id);
 
-    for entry in transcript {
-        // Decode HTML entities and fix specific cases
-        let text = decode_html_entities(&entry.text)
-            .replace("&#39;", "'")
-            .to_string();
+    reply_to(bot, msg, config, "Feedback sent, thank you!").await?;
 
-        if !full_transcript.is_empty() {
-            full_transcript.push('\n');
-        }
-        full_transcript.push_str(&text);
+    Ok(())
+}
+
+/// Replies with transcript statistics only, without uploading anything.
+async fn handle_info<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    args: &str,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    if parts.is_empty() {
+        reply_to(bot, msg, config, "Usage: /info <video_id> [lang]").await?;
+        return Ok(());
     }
 
-    // Upload the transcript to Pastebin
-    match upload_to_pastebin(&full_transcript).await {
-        Ok(url) => {
-            // Send only the link to the user
-            bot.send_message(msg.chat.id, format!("Transcript available at: {}", url))
-                .await?;
+    let video_id = parts[0];
+    let requested_lang = parts.get(1).copied().unwrap_or(config.default_lang());
+
+    match fetch_with_retry_notice(bot, msg, fetcher, video_id, requested_lang, config).await {
+        Ok(fetched) => {
+            let stats = formatter::transcript_stats(&fetched.entries);
+            let mut summary = formatter::format_info(video_id, &fetched.lang, &stats);
+            if config.show_token_estimate {
+                let tokens =
+                    formatter::estimate_token_count(&formatter::format_plain(&fetched.entries));
+                summary.push_str(&format!("\n~{} tokens (estimated)", tokens));
+            }
+            send_reply(bot, msg, config, config.templates().stats(&summary)).await?;
         }
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("Error uploading transcript: {}", e))
-                .await?;
+            let error = format!("Error fetching transcript: {}", e);
+            send_reply(bot, msg, config, config.templates().error(&error)).await?;
         }
     }
 
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use mockito::{mock, Matcher};
-    use teloxide_tests::{MockBot, MockMessageText};
+/// Words listed by `/wordfreq`.
+const WORDFREQ_TOP: usize = 15;
 
-    #[tokio::test]
-    async fn test_handle_message_happy_path() {
-        // Setup mock for Pastebin
-        let mock_url = "https://pastebin.com/abcdef123";
-        // The raw URL that will be sent to the user
-        let raw_mock_url = "https://pastebin.com/raw/abcdef123";
+/// Replies with the most frequent words of a transcript, leaving out the
+/// stopwords of its language.
+async fn handle_wordfreq<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    args: &str,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    if parts.is_empty() {
+        reply_to(bot, msg, config, "Usage: /wordfreq <video_id> [lang]").await?;
+        return Ok(());
+    }
 
-        // Set the expected user agent for the test environment
-        let expected_user_agent =
-            env::var("UPLOAD_USER_AGENT").unwrap_or_else(|_| "tofuboi/1.0".to_string());
+    let video_id = parts[0];
+    let requested_lang = parts.get(1).copied().unwrap_or(config.default_lang());
 
-        // Set a test API key for the environment
-        env::set_var("PASTEBIN_KEY", "test_api_key");
+    match fetch_with_retry_notice(bot, msg, fetcher, video_id, requested_lang, config).await {
+        Ok(fetched) => {
+            let text = formatter::format_plain(&fetched.entries);
+            let counts = analysis::word_frequency(&text, config.stopwords.for_lang(&fetched.lang));
+            let mut summary = format!("Most frequent words in {} ({}):", video_id, fetched.lang);
+            for (rank, (word, count)) in counts.iter().take(WORDFREQ_TOP).enumerate() {
+                summary.push_str(&format!("\n{}. {} ({})", rank + 1, word, count));
+            }
+            send_reply(bot, msg, config, config.templates().stats(&summary)).await?;
+        }
+        Err(e) => {
+            let error = format!("Error fetching transcript: {}", e);
+            send_reply(bot, msg, config, config.templates().error(&error)).await?;
+        }
+    }
 
-        // Create a mock that matches the form request to the Pastebin API
-        let _m = mock("POST", "/")
-            .match_header("user-agent", expected_user_agent.as_str())
-            .match_header("content-type", "application/x-www-form-urlencoded")
-            .match_body(Matcher::Any) // Match any body since we're sending form data
-            .with_status(200)
-            .with_header("content-type", "text/plain")
-            .with_body(mock_url)
-            .create();
+    Ok(())
+}
 
-        let video_id = "https://www.youtube.com/watch?v=HQoJMIgNdjo";
-        let bot = MockBot::new(MockMessageText::new().text(video_id), handler_tree());
+/// Words listed by `/corpusfreq`.
+const CORPUSFREQ_TOP: usize = 30;
 
-        bot.dispatch().await;
+/// Most videos one `/corpusfreq` may combine.
+const CORPUSFREQ_MAX_VIDEOS: usize = 5;
 
-        let messages: Vec<String> = bot
-            .get_responses()
-            .sent_messages
-            .iter()
-            .map(|m| m.text().unwrap_or_default().to_string())
-            .collect();
+/// Fetches several transcripts at once and lists the most frequent words
+/// across all of them. Videos that fail are named and left out.
+async fn handle_corpusfreq<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    args: &str,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let inputs: Vec<&str> = args.split_whitespace().collect();
+    let video_ids: Option<Vec<String>> = inputs
+        .iter()
+        .map(|input| parse_video_input(input))
+        .collect();
+    let video_ids = match video_ids {
+        Some(ids) if !ids.is_empty() && ids.len() <= CORPUSFREQ_MAX_VIDEOS => ids,
+        _ => {
+            let usage = format!(
+                "Usage: /corpusfreq <video_id> <video_id> ... (up to {} videos)",
+                CORPUSFREQ_MAX_VIDEOS
+            );
+            reply_to(bot, msg, config, usage).await?;
+            return Ok(());
+        }
+    };
 
-        // Verify that at least one message was sent
-        assert!(!messages.is_empty());
+    let lang = config.default_lang();
+    let fetches: Vec<_> = video_ids
+        .iter()
+        .map(|video_id| fetch_with_retry_notice(bot, msg, fetcher, video_id, lang, config))
+        .collect();
+    let results = futures::future::join_all(fetches).await;
 
-        // Check if any message contains the expected transcript URL (with /raw/ path)
-        let transcript_message_found = messages
-            .iter()
-            .any(|msg| msg.contains("Transcript available at:") && msg.contains(raw_mock_url));
+    let mut counts = Vec::new();
+    let mut failed = Vec::new();
+    for (video_id, result) in video_ids.iter().zip(results) {
+        match result {
+            Ok(fetched) => {
+                let text = formatter::format_plain(&fetched.entries);
+                counts.push(analysis::count_words(
+                    &text,
+                    config.stopwords.for_lang(&fetched.lang),
+                ));
+            }
+            Err(e) => {
+                log::warn!("Corpus fetch of {} failed: {}", video_id, e);
+                failed.push(video_id.as_str());
+            }
+        }
+    }
+    if counts.is_empty() {
+        let reply = config
+            .templates()
+            .error("None of the transcripts could be fetched.");
+        send_reply(bot, msg, config, reply).await?;
+        return Ok(());
+    }
 
-        assert!(transcript_message_found, "Expected to find a message with the transcript URL, but none was found. Messages: {:?}", messages);
+    let fetched_count = counts.len();
+    let top = analysis::top_words(analysis::merge_frequencies(counts), CORPUSFREQ_TOP);
+    let mut summary = format!("Most frequent words across {} videos:", fetched_count);
+    for (rank, (word, count)) in top.iter().enumerate() {
+        summary.push_str(&format!("\n{}. {} ({})", rank + 1, word, count));
+    }
+    if !failed.is_empty() {
+        summary.push_str(&format!(
+            "\nSkipped (could not fetch): {}",
+            failed.join(", ")
+        ));
     }
+    send_reply(bot, msg, config, config.templates().stats(&summary)).await?;
+    Ok(())
+}
 
-    #[tokio::test]
-    async fn test_handle_invalid_video_id() {
-        let invalid_id = "not_a_valid_video_id";
-        let bot = MockBot::new(MockMessageText::new().text(invalid_id), handler_tree());
+/// Bytes of transcript sent to the translation service per request, well
+/// under DeepL's 128 KiB request limit.
+const TRANSLATE_CHUNK_BYTES: usize = 30_000;
 
-        bot.dispatch().await;
+/// Translates a transcript with the configured backend and sends the result
+/// as chat messages.
+async fn handle_translate<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    args: &str,
+    client: &Client,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let Some(backend) = &config.translation else {
+        reply_to(bot, msg, config, "Translation is not enabled.").await?;
+        return Ok(());
+    };
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let (Some(video_id), Some(&target_lang)) = (
+        parts.first().and_then(|input| parse_video_input(input)),
+        parts.get(1),
+    ) else {
+        let usage = "Usage: /translate <video_id> <target_lang> [lang]";
+        reply_to(bot, msg, config, usage).await?;
+        return Ok(());
+    };
+    let requested_lang = parts.get(2).copied().unwrap_or(config.default_lang());
 
-        let messages: Vec<String> = bot
-            .get_responses()
-            .sent_messages
-            .iter()
-            .map(|m| m.text().unwrap_or_default().to_string())
-            .collect();
+    let fetched =
+        match fetch_with_retry_notice(bot, msg, fetcher, &video_id, requested_lang, config).await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                let error = format!("Error fetching transcript: {}", e);
+                send_reply(bot, msg, config, config.templates().error(&error)).await?;
+                return Ok(());
+            }
+        };
 
-        assert!(!messages.is_empty());
-        assert!(messages[0].contains("Error fetching transcript"));
+    let lines: Vec<String> = formatter::format_transcript_iter(&fetched.entries).collect();
+    let mut translated = Vec::new();
+    for chunk in formatter::chunk_lines(lines.into_iter(), TRANSLATE_CHUNK_BYTES) {
+        match backend.translate(client, &chunk, target_lang).await {
+            Ok(translation) => {
+                if let Some(source) = &translation.source_lang {
+                    log::debug!("Translated part of {} from {}", video_id, source);
+                }
+                translated.push(translation.text);
+            }
+            Err(e) => {
+                log::error!("Translating {} failed: {}", video_id, e);
+                let error = format!("Could not translate the transcript: {}", e);
+                send_reply(bot, msg, config, config.templates().error(&error)).await?;
+                return Ok(());
+            }
+        }
     }
 
-    #[tokio::test]
-    async fn test_handle_empty_message() {
-        let empty_message = "";
-        let bot = MockBot::new(MockMessageText::new().text(empty_message), handler_tree());
-
-        bot.dispatch().await;
+    let lines: Vec<String> = translated
+        .iter()
+        .flat_map(|text| text.lines())
+        .map(String::from)
+        .collect();
+    for chunk in formatter::chunk_lines(lines.into_iter(), chunk_limit(config, None)) {
+        delivery::send_with_retry(|| reply_to(bot, msg, config, chunk.as_str()).send()).await?;
+    }
+    Ok(())
+}
+
+/// Sends a summary of the transcript from the configured summarization
+/// service, noting when only the start of a long transcript was used.
+async fn handle_summarize<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    args: &str,
+    client: &Client,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let Some(api) = &config.summarize else {
+        reply_to(bot, msg, config, "Summaries are not enabled.").await?;
+        return Ok(());
+    };
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let Some(video_id) = parts.first().and_then(|input| parse_video_input(input)) else {
+        reply_to(bot, msg, config, "Usage: /summarize <video_id> [lang]").await?;
+        return Ok(());
+    };
+    let requested_lang = parts.get(1).copied().unwrap_or(config.default_lang());
+
+    let fetched =
+        match fetch_with_retry_notice(bot, msg, fetcher, &video_id, requested_lang, config).await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                let error = format!("Error fetching transcript: {}", e);
+                send_reply(bot, msg, config, config.templates().error(&error)).await?;
+                return Ok(());
+            }
+        };
+
+    let text = formatter::format_plain(&fetched.entries);
+    match api.summarize(client, &text).await {
+        Ok((summary, truncated)) => {
+            if truncated {
+                reply_to(bot, msg, config, summarize::TRUNCATED_NOTICE).await?;
+            }
+            let lines: Vec<String> = summary.lines().map(String::from).collect();
+            for chunk in formatter::chunk_lines(lines.into_iter(), chunk_limit(config, None)) {
+                reply_to(bot, msg, config, chunk).await?;
+            }
+        }
+        Err(e) => {
+            log::error!("Summarizing {} failed: {}", video_id, e);
+            let reply = config
+                .templates()
+                .error("Could not summarize the transcript.");
+            send_reply(bot, msg, config, reply).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Sends a unified diff of two transcripts: two videos in one language, or
+/// two languages of the same video when `lang2` differs from `lang1`.
+async fn handle_diff<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    args: &str,
+    client: Arc<Client>,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    // `delivery=<mode>` may go anywhere; the rest are positional
+    let (options, parts): (Vec<&str>, Vec<&str>) = args
+        .split_whitespace()
+        .partition(|token| token.contains('='));
+    let requested = args::parse_format_args(&options).delivery;
+    if parts.len() < 2 {
+        let usage = "Usage: /diff <id1> <id2> [lang1] [lang2] [delivery=<mode>]";
+        reply_to(bot, msg, config, usage).await?;
+        return Ok(());
+    }
+    let (Some(id1), Some(id2)) = (parse_video_input(parts[0]), parse_video_input(parts[1])) else {
+        reply_to(bot, msg, config, "Please provide a valid YouTube video ID.").await?;
+        return Ok(());
+    };
+    let lang1 = parts.get(2).copied().unwrap_or(config.default_lang());
+    let lang2 = parts.get(3).copied().unwrap_or(lang1);
+
+    let (first, second) = tokio::join!(
+        fetch_with_retry_notice(bot, msg, fetcher, &id1, lang1, config),
+        fetch_with_retry_notice(bot, msg, fetcher, &id2, lang2, config)
+    );
+    let (first, second) = match (first, second) {
+        (Ok(first), Ok(second)) => (first, second),
+        (Err(e), _) => return send_diff_error(bot, msg, &id1, e, config).await,
+        (_, Err(e)) => return send_diff_error(bot, msg, &id2, e, config).await,
+    };
+
+    let old_name = format!("{} ({})", id1, first.lang);
+    let new_name = format!("{} ({})", id2, second.lang);
+    let diff = diff::transcript_diff(&first.entries, &second.entries, &old_name, &new_name);
+    if diff.is_empty() {
+        reply_to(bot, msg, config, "The two transcripts are identical.").await?;
+        return Ok(());
+    }
+
+    let video_id = format!("{}_vs_{}", id1, id2);
+    let lang = if first.lang == second.lang {
+        first.lang.clone()
+    } else {
+        format!("{}-{}", first.lang, second.lang)
+    };
+    let file = FileInfo {
+        video_id: &video_id,
+        lang: &lang,
+        ext: "diff",
+    };
+    let lines: Vec<String> = diff.lines().map(String::from).collect();
+    let mode = delivery::choose_mode(OutputFormat::Txt, requested, config.delivery_mode);
+    deliver_lines(
+        bot,
+        msg,
+        mode,
+        file,
+        lines.into_iter(),
+        None,
+        client,
+        config,
+    )
+    .await?;
+    Ok(())
+}
+
+async fn send_diff_error(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    e: FetchError,
+    config: &AppConfig,
+) -> HandlerResult {
+    let error = format!("Error fetching transcript for {}: {}", video_id, e);
+    send_reply(bot, msg, config, config.templates().error(&error)).await?;
+    Ok(())
+}
+
+/// Replies with the caption languages available for a video.
+async fn handle_langs<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let templates = config.templates();
+    if video_id.is_empty() {
+        reply_to(bot, msg, config, "Usage: /langs <video_id>").await?;
+        return Ok(());
+    }
+
+    match fetcher.list_languages(video_id).await {
+        Ok(langs) if langs.is_empty() => {
+            reply_to(bot, msg, config, "No caption languages found.").await?;
+        }
+        Ok(langs) => {
+            let list = format!("Available languages: {}", langs.join(", "));
+            let keyboard = keyboard::languages_keyboard(video_id, &langs, 0);
+            send_reply_with_keyboard(bot, msg, config, templates.info(&list), keyboard).await?;
+        }
+        Err(e) => {
+            let error = format!("Error fetching transcript: {}", e);
+            send_reply(bot, msg, config, templates.error(&error)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reply to messages that carry neither text nor a caption.
+const MEDIA_PROMPT: &str = "Send me a YouTube link or video ID to get its transcript.";
+
+/// Reply when handling a message takes longer than `HANDLER_TIMEOUT_SECS`.
+const HANDLER_TIMEOUT_MESSAGE: &str = "Request timed out. Please try again later.";
+
+/// Handles a plain message, giving up after `config.handler_timeout` so a
+/// stuck fetch or upload can't hold the chat forever.
+async fn handle_message<T: TranscriptFetcher>(
+    bot: Bot,
+    msg: Message,
+    client: Arc<Client>,
+    fetcher: Arc<T>,
+    config: Arc<AppConfig>,
+    recent: Arc<RecentMessages>,
+) -> HandlerResult {
+    let Some(limit) = config.handler_timeout else {
+        return process_message(bot, msg, client, fetcher, config, recent).await;
+    };
+
+    let handled = process_message(
+        bot.clone(),
+        msg.clone(),
+        client,
+        fetcher,
+        config.clone(),
+        recent,
+    );
+    match tokio::time::timeout(limit, handled).await {
+        Ok(result) => result,
+        Err(_) => {
+            let video_id = msg
+                .text()
+                .or(msg.caption())
+                .and_then(|text| text.split_whitespace().find_map(parse_video_input));
+            log::warn!(
+                "Handler timed out after {}s for video {} from user {:?}",
+                limit.as_secs(),
+                video_id.as_deref().unwrap_or("-"),
+                msg.from.as_ref().map(|user| user.id.0)
+            );
+            reply_to(&bot, &msg, &config, HANDLER_TIMEOUT_MESSAGE).await?;
+            Ok(())
+        }
+    }
+}
+
+async fn process_message<T: TranscriptFetcher>(
+    bot: Bot,
+    msg: Message,
+    client: Arc<Client>,
+    fetcher: Arc<T>,
+    config: Arc<AppConfig>,
+    recent: Arc<RecentMessages>,
+) -> HandlerResult {
+    // Telegram may redeliver an update after a reconnect
+    if !recent.first_time(msg.chat.id, msg.id) {
+        log::debug!(
+            "Ignoring duplicate message {} in chat {}",
+            msg.id,
+            msg.chat.id
+        );
+        return Ok(());
+    }
+
+    let (mut text, mut caption) = (msg.text(), msg.caption());
+    if let Some(prefix) = &config.group_prefix {
+        if msg.chat.is_group() || msg.chat.is_supergroup() {
+            // In groups only messages starting with the trigger are meant for us
+            let Some(rest) = text
+                .or(caption)
+                .and_then(|t| t.trim_start().strip_prefix(prefix.as_str()))
+            else {
+                return Ok(());
+            };
+            if text.is_some() {
+                text = Some(rest);
+            } else {
+                caption = Some(rest);
+            }
+        }
+    }
+
+    if let Some(doc) = msg.document() {
+        if batch::is_text_document(doc) {
+            return handle_batch_document(&bot, &msg, doc, client, fetcher.as_ref(), &config).await;
+        }
+    }
+
+    // Media shared from mobile carries the link in its caption instead of text
+    let caption_video_id =
+        caption.and_then(|caption| caption.split_whitespace().find_map(parse_video_input));
+    let text = match (text, caption_video_id.as_deref()) {
+        (Some(text), _) | (None, Some(text)) => text,
+        (None, None) if caption.is_some() => {
+            reply_to(
+                &bot,
+                &msg,
+                &config,
+                "Please provide a valid YouTube video ID.",
+            )
+            .await?;
+            return Ok(());
+        }
+        // Stickers, voice notes and bare photos have nothing to look at
+        (None, None) => {
+            reply_to(&bot, &msg, &config, MEDIA_PROMPT).await?;
+            return Ok(());
+        }
+    };
+
+    handle_transcript_request(&bot, &msg, text, client, fetcher.as_ref(), &config).await
+}
+
+/// Fetches and delivers a transcript for `<video_id> [lang]`, shared by plain
+/// messages and the `/transcript` command.
+#[tracing::instrument(
+    skip_all,
+    fields(video_id, lang, user_id = msg.from.as_ref().map(|user| user.id.0))
+)]
+async fn handle_transcript_request<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    text: &str,
+    client: Arc<Client>,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    if parts.is_empty() {
+        reply_to(bot, msg, config, "Please provide a video ID.").await?;
+        return Ok(());
+    }
+
+    metrics::increment(Counter::Requests);
+    // Unrecognised input is passed through so the fetch error reaches the user
+    let video_id = parse_video_input(parts[0]).unwrap_or_else(|| parts[0].to_string());
+    let args = args::parse_format_args(&parts[1..]);
+    let requested_lang = args.lang.as_deref().unwrap_or(config.default_lang());
+    tracing::Span::current()
+        .record("video_id", video_id.as_str())
+        .record("lang", requested_lang);
+    // A `t=` in a pasted link starts the transcript there unless `range=` says otherwise
+    let range = args
+        .range
+        .or_else(|| video_id::parse_start_offset(parts[0]).map(args::TimeRange::from_start));
+
+    if let Some(second_lang) = args.bilingual.as_deref() {
+        if let Some(note) = unknown_options_note(&args.unknown) {
+            send_reply(bot, msg, config, config.templates().info(&note)).await?;
+        }
+        let filter = |entries: &mut Vec<TranscriptResponse>, lang: &str| {
+            filter_captions(entries, lang, &args, config)
+        };
+        match TranscriptService::fetch_bilingual(
+            fetcher,
+            &video_id,
+            requested_lang,
+            second_lang,
+            filter,
+        )
+        .await
+        {
+            Ok(mut entries) => {
+                count_language(config, requested_lang);
+                count_language(config, second_lang);
+                if let Some(range) = range {
+                    entries.retain(|entry| range.contains((entry.start * 1000.0) as u64));
+                }
+                if args.redact {
+                    for entry in &mut entries {
+                        entry.text1 = formatter::redact_contacts(&entry.text1);
+                        entry.text2 = formatter::redact_contacts(&entry.text2);
+                    }
+                }
+                let lang = format!("{}+{}", requested_lang, second_lang);
+                send_bilingual_transcript(
+                    bot,
+                    msg,
+                    &video_id,
+                    &lang,
+                    entries,
+                    args.delivery,
+                    client,
+                    config,
+                )
+                .await?;
+            }
+            Err(e) => {
+                metrics::increment(Counter::Errors);
+                let error = format!("Error fetching transcript: {}", e);
+                send_reply(bot, msg, config, config.templates().error(&error)).await?;
+            }
+        }
+        return Ok(());
+    }
+
+    // The live check runs alongside the fetch so it adds no wait
+    let (result, is_live) = tokio::join!(
+        fetch_with_retry_notice_exact(
+            bot,
+            msg,
+            fetcher,
+            &video_id,
+            requested_lang,
+            args.exact,
+            config
+        ),
+        async { config.detect_live && live::is_live(&client, &video_id).await },
+    );
+    if is_live {
+        // Whatever captions exist so far are still sent
+        let reply = config.templates().info(live::LIVE_STREAM_WARNING);
+        send_reply(bot, msg, config, reply).await?;
+    }
+    if let Ok(fetched) = &result {
+        count_language(config, &fetched.lang);
+    }
+    match result {
+        Ok(fetched) if args.raw => {
+            // Raw output is for debugging, so it skips every kind of processing
+            send_raw_transcript(bot, msg, &video_id, fetched, args.delivery, client, config)
+                .await?;
+        }
+        Ok(mut fetched) => {
+            if let Some(range) = range {
+                formatter::filter_range(&mut fetched.entries, range);
+            }
+            if args.redact {
+                for entry in &mut fetched.entries {
+                    entry.text = formatter::redact_contacts(&entry.text);
+                }
+            }
+            filter_captions(&mut fetched.entries, &fetched.lang, &args, config);
+            if let Some(find) = &args.find {
+                send_search_results(bot, msg, &video_id, &fetched.entries, find, config).await?;
+                return Ok(());
+            }
+            // The fallback note goes out with the transcript itself
+            if let Some(note) = unknown_options_note(&args.unknown) {
+                send_reply(bot, msg, config, config.templates().info(&note)).await?;
+            }
+            if fetched.info.is_none() && (args.verbose || config.verbose) {
+                let note = fetched.resolved_note();
+                send_reply(bot, msg, config, config.templates().info(&note)).await?;
+            }
+            if fetched.info.is_none() {
+                // No fallback, so an English request should have got English
+                let sample =
+                    language::sample_text(formatter::format_transcript_iter(&fetched.entries));
+                if language::detect_unexpected_script(&sample, &fetched.lang) {
+                    let warning = language::UNEXPECTED_SCRIPT_WARNING;
+                    send_reply(bot, msg, config, config.templates().info(warning)).await?;
+                }
+            }
+            if let Some(address) = &args.email {
+                send_email_transcript(bot, msg, &video_id, &fetched, address, config).await?;
+            } else if !args.formats.is_empty() {
+                let formats = &args.formats;
+                send_formats(
+                    bot,
+                    msg,
+                    &video_id,
+                    fetched,
+                    formats,
+                    args.delivery,
+                    args.chapters,
+                    client,
+                    config,
+                )
+                .await?;
+            } else if args.markdown {
+                send_markdown_transcript(bot, msg, &fetched, config).await?;
+            } else if args.compact {
+                let mode =
+                    delivery::choose_mode(OutputFormat::Txt, args.delivery, config.delivery_mode);
+                send_compact_transcript(bot, msg, &video_id, fetched, mode, client, config).await?;
+            } else if args.pdf {
+                send_pdf_transcript(bot, msg, &video_id, fetched, config).await?;
+            } else {
+                let mode =
+                    delivery::choose_mode(OutputFormat::Txt, args.delivery, config.delivery_mode);
+                send_transcript(
+                    bot,
+                    msg,
+                    &video_id,
+                    fetched,
+                    mode,
+                    args.chapters,
+                    client,
+                    config,
+                )
+                .await?;
+            }
+        }
+        Err(e) => {
+            metrics::increment(Counter::Errors);
+            let error = format!("Error fetching transcript: {}", e);
+            send_reply(bot, msg, config, config.templates().error(&error)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds a request for `lang` to the `/languages` statistics.
+fn count_language(config: &AppConfig, lang: &str) {
+    if let Some(history) = &config.history {
+        if let Err(e) = history.count_language(lang) {
+            log::warn!("Failed to count a request for language {}: {}", lang, e);
+        }
+    }
+}
+
+/// Fetches a transcript, telling the chat when a YouTube rate limit means
+/// it has to wait and try again.
+async fn fetch_with_retry_notice<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    fetcher: &T,
+    video_id: &str,
+    lang: &str,
+    config: &AppConfig,
+) -> Result<FetchedTranscript, transcript::FetchError> {
+    fetch_with_retry_notice_exact(bot, msg, fetcher, video_id, lang, false, config).await
+}
+
+/// Like [`fetch_with_retry_notice`]; with `exact` there is no fallback to
+/// another language.
+async fn fetch_with_retry_notice_exact<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    fetcher: &T,
+    video_id: &str,
+    lang: &str,
+    exact: bool,
+    config: &AppConfig,
+) -> Result<FetchedTranscript, transcript::FetchError> {
+    let policy = config.youtube_retry;
+    TranscriptService::fetch_with_retry(fetcher, video_id, lang, exact, policy, |delay| {
+        let text = format!(
+            "YouTube is rate-limiting requests. Retrying in {} seconds...",
+            delay.as_secs()
+        );
+        async move {
+            // The retry goes ahead whether or not the notice got through
+            if let Err(e) = reply_to(bot, msg, config, text).await {
+                log::warn!("Could not send retry notice: {}", e);
+            }
+        }
+    })
+    .await
+}
+
+/// Cleans up the captions of one language as configured and asked for in
+/// `args`, before they are put into any output format.
+fn filter_captions(
+    entries: &mut Vec<TranscriptResponse>,
+    lang: &str,
+    args: &args::FormatArgs,
+    config: &AppConfig,
+) {
+    if args.strip_inaudible.unwrap_or(config.strip_inaudible) {
+        formatter::strip_inaudible_markers(entries);
+    }
+    if let Some(fillers) = config.defiller.then(|| config.fillers_for(lang)).flatten() {
+        formatter::remove_filler_words(entries, fillers);
+    }
+    formatter::drop_short_captions(entries, args.min_len.unwrap_or(config.min_caption_len));
+    if config.dedup_transcripts {
+        *entries = transcript::deduplicate_consecutive(std::mem::take(entries));
+    }
+}
+
+fn unknown_options_note(unknown: &[String]) -> Option<String> {
+    (!unknown.is_empty()).then(|| format!("Ignored unknown options: {}", unknown.join(", ")))
+}
+
+/// Starts a message to the chat `msg` came from. In groups it answers `msg`
+/// directly unless `REPLY_IN_THREAD=0`, so replies to people asking at the
+/// same time stay apart.
+fn reply_to(
+    bot: &Bot,
+    msg: &Message,
+    config: &AppConfig,
+    text: impl Into<String>,
+) -> JsonRequest<SendMessage> {
+    reply_in_mode(bot, msg, config, text, None)
+}
+
+/// Like [`reply_to`], for text formatted in `parse_mode`.
+fn reply_in_mode(
+    bot: &Bot,
+    msg: &Message,
+    config: &AppConfig,
+    text: impl Into<String>,
+    parse_mode: Option<ParseMode>,
+) -> JsonRequest<SendMessage> {
+    let footer = config.message_footer.as_deref();
+    let request = bot.send_with_footer(msg.chat.id, text, footer, parse_mode);
+    match thread_under(msg, config) {
+        Some(parameters) => request.reply_parameters(parameters),
+        None => request,
+    }
+}
+
+/// Longest chunk of a reply split over several messages in `parse_mode`,
+/// leaving room for the footer in each.
+fn chunk_limit(config: &AppConfig, parse_mode: Option<ParseMode>) -> usize {
+    bot_ext::text_limit(config.message_footer.as_deref(), parse_mode)
+}
+
+/// Replies with `text`, split into several messages if Telegram finds it
+/// too long for one.
+async fn send_long_message(
+    bot: &Bot,
+    msg: &Message,
+    config: &AppConfig,
+    text: &str,
+) -> HandlerResult {
+    delivery::send_split_if_too_long(text, chunk_limit(config, None), |chunk| async move {
+        reply_to(bot, msg, config, chunk).await.map(|_| ())
+    })
+    .await?;
+    Ok(())
+}
+
+/// Like [`reply_to`], for a document.
+fn document_to(
+    bot: &Bot,
+    msg: &Message,
+    config: &AppConfig,
+    document: InputFile,
+) -> MultipartRequest<SendDocument> {
+    let request = bot.send_document(msg.chat.id, document);
+    match thread_under(msg, config) {
+        Some(parameters) => request.reply_parameters(parameters),
+        None => request,
+    }
+}
+
+/// The reference to `msg` that replies in a group carry, if they thread.
+fn thread_under(msg: &Message, config: &AppConfig) -> Option<ReplyParameters> {
+    let in_group = msg.chat.is_group() || msg.chat.is_supergroup();
+    (config.reply_in_thread && in_group).then(|| ReplyParameters::new(msg.id))
+}
+
+/// Sends a rendered template reply, applying its parse mode if it has one.
+async fn send_reply(
+    bot: &Bot,
+    msg: &Message,
+    config: &AppConfig,
+    reply: Reply,
+) -> Result<Message, teloxide::RequestError> {
+    reply_in_mode(bot, msg, config, reply.text, reply.parse_mode).await
+}
+
+/// Like [`send_reply`], with buttons under the message.
+async fn send_reply_with_keyboard(
+    bot: &Bot,
+    msg: &Message,
+    config: &AppConfig,
+    reply: Reply,
+    keyboard: InlineKeyboardMarkup,
+) -> Result<Message, teloxide::RequestError> {
+    reply_in_mode(bot, msg, config, reply.text, reply.parse_mode)
+        .reply_markup(keyboard)
+        .await
+}
+
+/// Processes a text file containing one YouTube link per line and replies
+/// with one consolidated list of results.
+async fn handle_batch_document<T: TranscriptFetcher>(
+    bot: &Bot,
+    msg: &Message,
+    doc: &Document,
+    client: Arc<Client>,
+    fetcher: &T,
+    config: &AppConfig,
+) -> HandlerResult {
+    let file = bot.get_file(doc.file.id.clone()).await?;
+    let mut contents = Vec::new();
+    bot.download_file(&file.path, &mut contents).await?;
+
+    let text = String::from_utf8_lossy(&contents);
+    let lines = batch::parse_batch_file(&text);
+    if lines.is_empty() {
+        reply_to(bot, msg, config, "The file does not contain any links.").await?;
+        return Ok(());
+    }
+
+    let lang = config.default_lang();
+    let items = batch::process_batch(fetcher, client, &lines, lang, config.batch_concurrency).await;
+    send_long_message(bot, msg, config, &batch::format_batch_results(&items)).await?;
+    // Every link fails alike on a missing setting, so the admin hears once
+    for error in items.iter().filter_map(|item| item.result.as_ref().err()) {
+        if error_handler::report_config_error(bot, config.admin_chat_id, error.as_ref()).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Helper function to deliver the transcript to the user.
+/// In `pastebin` mode it uploads the text and sends the resulting URL; in
+/// `messages` mode the text is sent directly in chunks.
+#[allow(clippy::too_many_arguments)]
+async fn send_transcript(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    mut fetched: FetchedTranscript,
+    mode: DeliveryMode,
+    with_chapters: bool,
+    client: Arc<Client>,
+    config: &AppConfig,
+) -> Result<(), teloxide::RequestError> {
+    let notice = fallback_notice(video_id, &mut fetched);
+
+    if fetched.is_blank() {
+        reply_to(
+            bot,
+            msg,
+            config,
+            "Transcript could not be retrieved or is empty.",
+        )
+        .await?;
+        if let Some(notice) = notice {
+            let reply = config.templates().info(&notice.note);
+            send_reply_with_keyboard(bot, msg, config, reply, notice.keyboard).await?;
+        }
+        return Ok(());
+    }
+
+    if config.punctuate {
+        punctuate::punctuate(
+            &client,
+            config.punctuate_api_url.as_deref(),
+            &mut fetched.entries,
+        )
+        .await;
+    }
+
+    // Chapters are found after punctuation and dedup so they match the output
+    let sections = if with_chapters {
+        chapters::detect_sections(&fetched.entries)
+    } else {
+        Vec::new()
+    };
+    let toc = with_chapters.then(|| {
+        let toc = chapters::build_toc(&sections, video_id, ReplyFormat::Plain);
+        format!("{}\n", toc)
+    });
+
+    // Owns the entries so the upload stream can outlive this borrow of `config`
+    let lines =
+        toc.into_iter().chain(
+            fetched
+                .entries
+                .into_iter()
+                .enumerate()
+                .map(move |(i, entry)| {
+                    let line = formatter::normalize_text(&entry.text);
+                    // Each chapter opens with its title, set off by a blank line
+                    match sections
+                        .iter()
+                        .position(|section| section.entries.start == i)
+                    {
+                        Some(n) => format!(
+                            "\n{}\n{}",
+                            chapters::chapter_title(n + 1, &sections[n]),
+                            line
+                        ),
+                        None => line,
+                    }
+                }),
+        );
+
+    let lines = formatter::wrap_lines(lines, &config.transcript_prefix, &config.transcript_suffix);
+
+    let file = FileInfo {
+        video_id,
+        lang: &fetched.lang,
+        ext: "txt",
+    };
+    deliver_lines(bot, msg, mode, file, lines, notice, client, config).await
+}
+
+/// Takes the fallback note out of `fetched`, offering the other languages so
+/// the user can switch with one tap.
+fn fallback_notice(video_id: &str, fetched: &mut FetchedTranscript) -> Option<FallbackNotice> {
+    fetched.info.take().map(|note| {
+        let alternatives: Vec<String> = fetched
+            .available_langs
+            .iter()
+            .filter(|lang| **lang != fetched.lang)
+            .cloned()
+            .collect();
+        FallbackNotice {
+            note,
+            keyboard: keyboard::languages_keyboard(video_id, &alternatives, 0),
+        }
+    })
+}
+
+/// Sends the transcript one sentence per line with inline timestamps,
+/// delivered like plain text.
+async fn send_compact_transcript(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    mut fetched: FetchedTranscript,
+    mode: DeliveryMode,
+    client: Arc<Client>,
+    config: &AppConfig,
+) -> HandlerResult {
+    let notice = fallback_notice(video_id, &mut fetched);
+    let compact = formatter::format_compact(&fetched.entries);
+    if compact.is_empty() {
+        reply_to(
+            bot,
+            msg,
+            config,
+            "Transcript could not be retrieved or is empty.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = compact.lines().map(str::to_string).collect();
+    let lines = formatter::wrap_lines(
+        lines.into_iter(),
+        &config.transcript_prefix,
+        &config.transcript_suffix,
+    );
+    let file = FileInfo {
+        video_id,
+        lang: &fetched.lang,
+        ext: "txt",
+    };
+    deliver_lines(bot, msg, mode, file, lines, notice, client, config).await?;
+    Ok(())
+}
+
+/// Sends one fetched transcript in each of `formats`, each the way
+/// [`delivery::choose_mode`] picks: subtitles and JSON as documents and
+/// text as a paste unless the request or `DELIVERY_MODE` says otherwise.
+#[allow(clippy::too_many_arguments)]
+async fn send_formats(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    fetched: FetchedTranscript,
+    formats: &[OutputFormat],
+    requested: Option<DeliveryMode>,
+    with_chapters: bool,
+    client: Arc<Client>,
+    config: &AppConfig,
+) -> HandlerResult {
+    if fetched.is_blank() {
+        reply_to(
+            bot,
+            msg,
+            config,
+            "Transcript could not be retrieved or is empty.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    for &format in formats {
+        let mode = delivery::choose_mode(format, requested, config.delivery_mode);
+        let text = match format {
+            OutputFormat::Txt => {
+                let fetched = fetched.clone();
+                send_transcript(
+                    bot,
+                    msg,
+                    video_id,
+                    fetched,
+                    mode,
+                    with_chapters,
+                    client.clone(),
+                    config,
+                )
+                .await?;
+                continue;
+            }
+            OutputFormat::Srt => export::subtitles::format_srt(&fetched.entries),
+            OutputFormat::Vtt => export::subtitles::format_vtt(&fetched.entries),
+            OutputFormat::Json => formatter::format_raw(&fetched.entries)?,
+        };
+        let file = FileInfo {
+            video_id,
+            lang: &fetched.lang,
+            ext: format.ext(),
+        };
+        if mode == DeliveryMode::Document {
+            send_as_document(bot, msg, file, &text, None, config).await?;
+        } else {
+            let lines: Vec<String> = text.lines().map(String::from).collect();
+            deliver_lines(
+                bot,
+                msg,
+                mode,
+                file,
+                lines.into_iter(),
+                None,
+                client.clone(),
+                config,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Sends the transcript straight to the chat as MarkdownV2 messages with
+/// bold timestamps, whatever `DELIVERY_MODE` says.
+async fn send_markdown_transcript(
+    bot: &Bot,
+    msg: &Message,
+    fetched: &FetchedTranscript,
+    config: &AppConfig,
+) -> HandlerResult {
+    if fetched.is_blank() {
+        reply_to(
+            bot,
+            msg,
+            config,
+            "Transcript could not be retrieved or is empty.",
+        )
+        .await?;
+        return Ok(());
+    }
+    if let Some(note) = &fetched.info {
+        send_reply(bot, msg, config, config.templates().info(note)).await?;
+    }
+
+    let markdown = formatter::format_markdown(&fetched.entries);
+    // Whole lines per chunk, so no escape is cut in half
+    let lines: Vec<String> = markdown.lines().map(String::from).collect();
+    let mode = Some(ParseMode::MarkdownV2);
+    for chunk in formatter::chunk_lines(lines.into_iter(), chunk_limit(config, mode)) {
+        delivery::send_with_retry(|| reply_in_mode(bot, msg, config, chunk.as_str(), mode).send())
+            .await?;
+    }
+    metrics::increment(Counter::Successes);
+    Ok(())
+}
+
+/// Emails the transcript as a text attachment and confirms in the chat.
+#[cfg(feature = "email")]
+async fn send_email_transcript(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    fetched: &FetchedTranscript,
+    address: &str,
+    config: &AppConfig,
+) -> HandlerResult {
+    if fetched.is_blank() {
+        reply_to(
+            bot,
+            msg,
+            config,
+            "Transcript could not be retrieved or is empty.",
+        )
+        .await?;
+        return Ok(());
+    }
+    let Some(email_config) = &config.email else {
+        let reply = config
+            .templates()
+            .error("Email delivery is not configured on this bot.");
+        send_reply(bot, msg, config, reply).await?;
+        return Ok(());
+    };
+    let to = match email::parse_address(address) {
+        Ok(to) => to,
+        Err(e) => {
+            send_reply(bot, msg, config, config.templates().error(&e)).await?;
+            return Ok(());
+        }
+    };
+
+    let file_name = config.filename_template.render(&FilenameParts {
+        video_id,
+        lang: &fetched.lang,
+        ext: "txt",
+        date: &msg.date.date_naive().to_string(),
+    });
+    let subject = upload::paste_title(video_id, &fetched.lang);
+    let transcript = formatter::format_plain(&fetched.entries);
+    let sent = match email_config.build_message(to.clone(), &subject, &file_name, &transcript) {
+        Ok(message) => match email_config.transport() {
+            Ok(transport) => email::send(&transport, message).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    match sent {
+        Ok(()) => {
+            reply_to(bot, msg, config, format!("Sent to {}.", to)).await?;
+            metrics::increment(Counter::Successes);
+        }
+        Err(e) => {
+            log::error!("Emailing {} failed: {}", video_id, e);
+            metrics::increment(Counter::Errors);
+            let reply = config.templates().error("Could not send the email.");
+            send_reply(bot, msg, config, reply).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Without the `email` feature there is nothing to send with.
+#[cfg(not(feature = "email"))]
+async fn send_email_transcript(
+    bot: &Bot,
+    msg: &Message,
+    _video_id: &str,
+    _fetched: &FetchedTranscript,
+    _address: &str,
+    config: &AppConfig,
+) -> HandlerResult {
+    let reply = config
+        .templates()
+        .error("Email delivery is not available in this build.");
+    send_reply(bot, msg, config, reply).await?;
+    Ok(())
+}
+
+/// Sends the transcript as a typeset PDF document, whatever `DELIVERY_MODE`
+/// says, with any fallback note as its caption.
+async fn send_pdf_transcript(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    fetched: FetchedTranscript,
+    config: &AppConfig,
+) -> HandlerResult {
+    if fetched.is_blank() {
+        reply_to(
+            bot,
+            msg,
+            config,
+            "Transcript could not be retrieved or is empty.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let pdf = export::pdf::format_pdf(&fetched.entries, video_id, &fetched.lang)?;
+    let file_name = config.filename_template.render(&FilenameParts {
+        video_id,
+        lang: &fetched.lang,
+        ext: "pdf",
+        date: &msg.date.date_naive().to_string(),
+    });
+    let document = InputFile::memory(pdf).file_name(file_name);
+    delivery::send_with_retry(|| {
+        let request = document_to(bot, msg, config, document.clone());
+        match &fetched.info {
+            Some(note) => request.caption(note.as_str()).send(),
+            None => request.send(),
+        }
+    })
+    .await?;
+    metrics::increment(Counter::Successes);
+    Ok(())
+}
+
+/// Lists the captions matching `find`, each with a link to that moment.
+async fn send_search_results(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    entries: &[TranscriptResponse],
+    find: &str,
+    config: &AppConfig,
+) -> HandlerResult {
+    let query = match formatter::SearchQuery::parse(find) {
+        Ok(query) => query,
+        Err(e) => {
+            let error = format!("Invalid search pattern: {}", e);
+            send_reply(bot, msg, config, config.templates().error(&error)).await?;
+            return Ok(());
+        }
+    };
+
+    let hits = formatter::search_transcript(entries, &query);
+    if hits.is_empty() {
+        reply_to(bot, msg, config, format!("No matches for \"{}\".", find)).await?;
+        return Ok(());
+    }
+
+    let header = format!("Matches for \"{}\" in {}:", find, video_id);
+    let lines = std::iter::once(header).chain(hits.into_iter().map(|(start, text)| {
+        format!(
+            "[{}] {} https://youtu.be/{}?t={}",
+            formatter::format_stamp(start.as_millis() as u64),
+            text,
+            video_id,
+            start.as_secs()
+        )
+    }));
+    for chunk in formatter::chunk_lines(lines, chunk_limit(config, None)) {
+        delivery::send_with_retry(|| reply_to(bot, msg, config, chunk.as_str()).send()).await?;
+    }
+    Ok(())
+}
+
+/// Sends the transcript exactly as ytranscript returned it, as JSON.
+#[allow(clippy::too_many_arguments)]
+async fn send_raw_transcript(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    fetched: FetchedTranscript,
+    requested: Option<DeliveryMode>,
+    client: Arc<Client>,
+    config: &AppConfig,
+) -> HandlerResult {
+    let json = formatter::format_raw(&fetched.entries)?;
+    let lines: Vec<String> = json.lines().map(String::from).collect();
+
+    let file = FileInfo {
+        video_id,
+        lang: &fetched.lang,
+        ext: "json",
+    };
+    let mode = delivery::choose_mode(OutputFormat::Json, requested, config.delivery_mode);
+    deliver_lines(
+        bot,
+        msg,
+        mode,
+        file,
+        lines.into_iter(),
+        None,
+        client,
+        config,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Delivers a side-by-side transcript; `lang` names both languages, e.g. `en+es`.
+#[allow(clippy::too_many_arguments)]
+async fn send_bilingual_transcript(
+    bot: &Bot,
+    msg: &Message,
+    video_id: &str,
+    lang: &str,
+    entries: Vec<BilingualEntry>,
+    requested: Option<DeliveryMode>,
+    client: Arc<Client>,
+    config: &AppConfig,
+) -> Result<(), teloxide::RequestError> {
+    if entries.is_empty() {
+        reply_to(
+            bot,
+            msg,
+            config,
+            "Transcript could not be retrieved or is empty.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let text = formatter::format_bilingual(&entries);
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    let file = FileInfo {
+        video_id,
+        lang,
+        ext: "txt",
+    };
+    let mode = delivery::choose_mode(OutputFormat::Txt, requested, config.delivery_mode);
+    deliver_lines(
+        bot,
+        msg,
+        mode,
+        file,
+        lines.into_iter(),
+        None,
+        client,
+        config,
+    )
+    .await
+}
+
+/// The fallback language note, sent together with the transcript along with
+/// buttons for asking again in another language.
+struct FallbackNotice {
+    note: String,
+    keyboard: InlineKeyboardMarkup,
+}
+
+/// Identifies a transcript when it is delivered as a document.
+#[derive(Clone, Copy)]
+struct FileInfo<'a> {
+    video_id: &'a str,
+    lang: &'a str,
+    ext: &'a str,
+}
+
+/// Delivers formatted lines by `mode`, falling back per `DELIVERY_FALLBACK`
+/// when a document cannot be sent.
+#[tracing::instrument(
+    skip_all,
+    fields(video_id = file.video_id, lang = file.lang, upload_backend)
+)]
+#[allow(clippy::too_many_arguments)]
+async fn deliver_lines<I>(
+    bot: &Bot,
+    msg: &Message,
+    mode: DeliveryMode,
+    file: FileInfo<'_>,
+    lines: I,
+    notice: Option<FallbackNotice>,
+    client: Arc<Client>,
+    config: &AppConfig,
+) -> Result<(), teloxide::RequestError>
+where
+    I: Iterator<Item = String> + Send + 'static,
+{
+    tracing::Span::current().record("upload_backend", mode.name());
+    match mode {
+        DeliveryMode::Messages => send_as_messages(bot, msg, lines, notice.as_ref(), config).await,
+        DeliveryMode::Pastebin => {
+            upload_as_paste(bot, msg, file, lines, notice.as_ref(), client, config).await
+        }
+        DeliveryMode::Document => {
+            let text = lines.collect::<Vec<_>>().join("\n");
+            let notice = notice.as_ref();
+            // A document Telegram refuses, e.g. over its size limit, is sent another way
+            let delivered_by =
+                delivery::deliver_with_fallbacks(mode, &config.delivery_fallbacks, |mode| {
+                    let (text, client) = (&text, client.clone());
+                    async move {
+                        let lines = text.lines().map(String::from).collect::<Vec<_>>();
+                        match mode {
+                            DeliveryMode::Document => {
+                                send_as_document(bot, msg, file, text, notice, config).await
+                            }
+                            DeliveryMode::Messages => {
+                                send_as_messages(bot, msg, lines.into_iter(), notice, config).await
+                            }
+                            DeliveryMode::Pastebin => {
+                                let lines = lines.into_iter();
+                                upload_as_paste(bot, msg, file, lines, notice, client, config).await
+                            }
+                        }
+                    }
+                })
+                .await?;
+            if delivered_by != mode {
+                tracing::Span::current().record("upload_backend", delivered_by.name());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Sends the lines as chat messages of at most Telegram's message size.
+async fn send_as_messages<I>(
+    bot: &Bot,
+    msg: &Message,
+    lines: I,
+    notice: Option<&FallbackNotice>,
+    config: &AppConfig,
+) -> Result<(), teloxide::RequestError>
+where
+    I: Iterator<Item = String>,
+{
+    if let Some(notice) = notice {
+        let reply = config.templates().info(&notice.note);
+        send_reply_with_keyboard(bot, msg, config, reply, notice.keyboard.clone()).await?;
+    }
+    // Chunks are built one at a time instead of from the whole transcript
+    for chunk in formatter::chunk_lines(lines, chunk_limit(config, None)) {
+        delivery::send_with_retry(|| reply_to(bot, msg, config, chunk.as_str()).send()).await?;
+    }
+    metrics::increment(Counter::Successes);
+    Ok(())
+}
+
+/// Sends the text as a document named by `TRANSCRIPT_FILENAME_TEMPLATE`.
+async fn send_as_document(
+    bot: &Bot,
+    msg: &Message,
+    file: FileInfo<'_>,
+    text: &str,
+    notice: Option<&FallbackNotice>,
+    config: &AppConfig,
+) -> Result<(), teloxide::RequestError> {
+    let file_name = config.filename_template.render(&FilenameParts {
+        video_id: file.video_id,
+        lang: file.lang,
+        ext: file.ext,
+        date: &msg.date.date_naive().to_string(),
+    });
+    let document = InputFile::memory(text.as_bytes().to_vec()).file_name(file_name);
+    delivery::send_with_retry(|| {
+        let request = document_to(bot, msg, config, document.clone());
+        match notice {
+            Some(notice) => request
+                .caption(notice.note.as_str())
+                .reply_markup(notice.keyboard.clone())
+                .send(),
+            None => request.send(),
+        }
+    })
+    .await?;
+    metrics::increment(Counter::Successes);
+    Ok(())
+}
+
+/// Bytes of the transcript sent in a message when the upload fails.
+const UPLOAD_FAILURE_EXCERPT_BYTES: usize = 3000;
+
+/// Uploads the lines and replies with the link. When the upload fails, the
+/// start of the transcript is sent instead, with the error.
+async fn upload_as_paste<I>(
+    bot: &Bot,
+    msg: &Message,
+    file: FileInfo<'_>,
+    lines: I,
+    notice: Option<&FallbackNotice>,
+    client: Arc<Client>,
+    config: &AppConfig,
+) -> Result<(), teloxide::RequestError>
+where
+    I: Iterator<Item = String> + Send + 'static,
+{
+    // The start is kept back so it can still be sent if the upload fails
+    let mut lines = lines;
+    let mut head = Vec::new();
+    let mut head_len = 0;
+    while head_len < UPLOAD_FAILURE_EXCERPT_BYTES {
+        let Some(line) = lines.next() else { break };
+        head_len += line.len() + 1;
+        head.push(line);
+    }
+    let excerpt = head.join("\n");
+    let lines = head.into_iter().chain(lines);
+
+    // Stream the lines to Pastebin instead of joining them into one string
+    let title = upload::paste_title(file.video_id, file.lang);
+    let slot = upload::PasteSlot {
+        gists: &config.gists,
+        chat_id: msg.chat.id.0,
+        video_id: file.video_id,
+    };
+    match upload::upload_lines_from_env(client, lines, &title, Some(slot)).await {
+        Ok(paste) => {
+            metrics::increment(Counter::Successes);
+            if let (Some(history), Some(user)) = (&config.history, msg.from.as_ref()) {
+                let entry =
+                    history::HistoryEntry::now(user.id.0, file.video_id, file.lang, &paste.url);
+                if let Err(e) = history.record(&entry) {
+                    log::warn!("Failed to record upload of {}: {}", file.video_id, e);
+                }
+            }
+            // Send only the link to the user
+            let reply = if paste.compressed {
+                config.templates().compressed_success(&paste.url)
+            } else {
+                config.templates().success(&paste.url)
+            };
+            match notice {
+                Some(notice) => {
+                    let reply = config.templates().with_note(&notice.note, reply);
+                    send_reply_with_keyboard(bot, msg, config, reply, notice.keyboard.clone())
+                        .await?;
+                }
+                None => {
+                    send_reply(bot, msg, config, reply).await?;
+                }
+            }
+        }
+        Err(e) => {
+            metrics::increment(Counter::UploadFailures);
+            if excerpt.trim().is_empty() {
+                let error = format!("Error uploading transcript: {}", e);
+                send_reply(bot, msg, config, config.templates().error(&error)).await?;
+            } else {
+                // Every backend failed; the start of the transcript beats nothing
+                let text = formatter::failure_excerpt(
+                    &excerpt,
+                    UPLOAD_FAILURE_EXCERPT_BYTES,
+                    &e.to_string(),
+                );
+                delivery::send_with_retry(|| reply_to(bot, msg, config, text.as_str()).send())
+                    .await?;
+            }
+            error_handler::report_config_error(bot, config.admin_chat_id, e.as_ref()).await;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dptree::di::DependencyMap;
+    use mockito::{mock, Matcher};
+    use std::env;
+    use teloxide::types::MessageId;
+    use teloxide_tests::{
+        MockBot, MockCallbackQuery, MockGroupChat, MockMessagePhoto, MockMessageText, MockUser,
+    };
+    use transcript::{test_entry, MockTranscriptFetcher};
+    use ytranscript::YoutubeTranscriptError;
+
+    /// Dependencies the handler tree expects, with a mocked transcript source
+    fn test_deps(fetcher: Arc<MockTranscriptFetcher>, config: AppConfig) -> DependencyMap {
+        let rate_limiter = RateLimiter::new(config.rate_limit_requests, config.rate_limit_window);
+        dptree::deps![
+            Arc::new(Client::new()),
+            Arc::new(config),
+            fetcher,
+            Arc::new(FeedbackLimiter::default()),
+            Arc::new(ExportLimiter::default()),
+            Arc::new(RecentMessages::default()),
+            Arc::new(rate_limiter)
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_slow_fetch_times_out() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_delay(std::time::Duration::from_secs(5))
+            .with_response(Ok(vec![test_entry("too late", 0.0, 1.0)]));
+        let config = AppConfig {
+            handler_timeout: Some(std::time::Duration::from_millis(50)),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("dQw4w9WgXcQ"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(fetcher), config));
+
+        bot.dispatch().await;
+
+        let messages = sent_texts(&bot);
+        assert_eq!(messages.last().unwrap(), HANDLER_TIMEOUT_MESSAGE);
+        assert!(!messages.iter().any(|m| m.contains("too late")));
+    }
+
+    #[tokio::test]
+    async fn test_bare_request_uses_default_lang() {
+        let fetcher = Arc::new(MockTranscriptFetcher::new());
+        let config = AppConfig {
+            default_lang: Some("es".to_string()),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), config));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            fetcher.calls()[0],
+            ("HQoJMIgNdjo".to_string(), "es".to_string())
+        );
+    }
+
+    fn sent_texts(bot: &MockBot) -> Vec<String> {
+        bot.get_responses()
+            .sent_messages
+            .iter()
+            .map(|m| m.text().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_happy_path() {
+        // Setup mock for Pastebin
+        let mock_url = "https://pastebin.com/abcdef123";
+        // The raw URL that will be sent to the user
+        let raw_mock_url = "https://pastebin.com/raw/abcdef123";
+
+        // Set the expected user agent for the test environment
+        let expected_user_agent =
+            env::var("UPLOAD_USER_AGENT").unwrap_or_else(|_| "tofuboi/1.0".to_string());
+
+        // Set a test API key for the environment
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+
+        // Create a mock that matches the form request to the Pastebin API
+        let _m = mock("POST", "/")
+            .match_header("user-agent", expected_user_agent.as_str())
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body(Matcher::Any) // Match any body since we're sending form data
+            .with_status(200)
+            .with_header("content-type", "text/plain")
+            .with_body(mock_url)
+            .create();
+
+        let video_id = "https://www.youtube.com/watch?v=HQoJMIgNdjo";
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![test_entry(
+            "Hello &amp; welcome",
+            0.0,
+            2.0,
+        )]));
+        let mut bot = MockBot::new(
+            MockMessageText::new().text(video_id),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(fetcher), AppConfig::default()));
+
+        bot.dispatch().await;
 
         let messages: Vec<String> = bot
             .get_responses()
             .sent_messages
             .iter()
-            .map(|m| m.text().unwrap_or_default().to_string())
+            .map(|m| m.text().unwrap_or_default().to_string())
+            .collect();
+
+        // Verify that at least one message was sent
+        assert!(!messages.is_empty());
+
+        // Check if any message contains the expected transcript URL (with /raw/ path)
+        let transcript_message_found = messages
+            .iter()
+            .any(|msg| msg.contains("Transcript available at:") && msg.contains(raw_mock_url));
+
+        assert!(transcript_message_found, "Expected to find a message with the transcript URL, but none was found. Messages: {:?}", messages);
+    }
+
+    #[tokio::test]
+    async fn test_handle_invalid_video_id() {
+        let invalid_id = "not_a_valid_video_id";
+        let fetcher = MockTranscriptFetcher::new().with_response(Err(
+            YoutubeTranscriptError::VideoUnavailable(invalid_id.to_string()),
+        ));
+        let mut bot = MockBot::new(
+            MockMessageText::new().text(invalid_id),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(fetcher), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        let messages: Vec<String> = bot
+            .get_responses()
+            .sent_messages
+            .iter()
+            .map(|m| m.text().unwrap_or_default().to_string())
+            .collect();
+
+        assert!(!messages.is_empty());
+        assert!(messages[0].contains("Error fetching transcript"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_empty_message() {
+        let empty_message = "";
+        let mut bot = MockBot::new(
+            MockMessageText::new().text(empty_message),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(
+            Arc::new(MockTranscriptFetcher::new()),
+            AppConfig::default(),
+        ));
+
+        bot.dispatch().await;
+
+        let messages: Vec<String> = bot
+            .get_responses()
+            .sent_messages
+            .iter()
+            .map(|m| m.text().unwrap_or_default().to_string())
+            .collect();
+
+        assert!(!messages.is_empty());
+        assert_eq!(messages[0], "Please provide a video ID.");
+    }
+
+    #[tokio::test]
+    async fn test_handle_langs_command() {
+        let fetcher = MockTranscriptFetcher::new().with_languages(&["en", "es", "zh-HK"]);
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/langs abc123"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(fetcher), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        let messages: Vec<String> = bot
+            .get_responses()
+            .sent_messages
+            .iter()
+            .map(|m| m.text().unwrap_or_default().to_string())
+            .collect();
+
+        assert_eq!(messages, vec!["Available languages: en, es, zh-HK"]);
+    }
+
+    #[tokio::test]
+    async fn test_feedback_is_forwarded() {
+        let config = AppConfig {
+            feedback_chat_id: Some(ChatId(-1001)),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/feedback Love the bot"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(MockTranscriptFetcher::new()), config));
+
+        bot.dispatch().await;
+
+        let responses = bot.get_responses();
+        let forwarded = responses
+            .sent_messages
+            .iter()
+            .find(|m| m.chat.id == ChatId(-1001))
+            .expect("feedback should be forwarded to the feedback chat");
+        assert!(forwarded.text().unwrap().contains("Love the bot"));
+        assert!(sent_texts(&bot).contains(&"Feedback sent, thank you!".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_feedback_rate_limited() {
+        let config = AppConfig {
+            feedback_chat_id: Some(ChatId(-1001)),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            vec![
+                MockMessageText::new().text("/feedback one"),
+                MockMessageText::new().text("/feedback two"),
+                MockMessageText::new().text("/feedback three"),
+            ],
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(MockTranscriptFetcher::new()), config));
+
+        bot.dispatch().await;
+
+        let messages = sent_texts(&bot);
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|m| *m == "Feedback sent, thank you!")
+                .count(),
+            2
+        );
+        assert!(messages
+            .last()
+            .unwrap()
+            .contains("at most 2 feedback messages per day"));
+    }
+
+    #[tokio::test]
+    async fn test_feedback_not_configured() {
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/feedback hello"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(
+            Arc::new(MockTranscriptFetcher::new()),
+            AppConfig::default(),
+        ));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            sent_texts(&bot),
+            vec!["Feedback collection is not configured."]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_caption_with_youtube_url() {
+        let fetcher = MockTranscriptFetcher::new().with_response(Err(
+            YoutubeTranscriptError::TranscriptDisabled("HQoJMIgNdjo".to_string()),
+        ));
+        let fetcher = Arc::new(fetcher);
+        let mut bot = MockBot::new(
+            MockMessagePhoto::new().caption("Watch this https://youtu.be/HQoJMIgNdjo?t=5"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            fetcher.calls(),
+            vec![("HQoJMIgNdjo".to_string(), "en".to_string())]
+        );
+        assert!(sent_texts(&bot)[0].contains("Error fetching transcript"));
+    }
+
+    #[tokio::test]
+    async fn test_photo_without_caption_gets_prompt() {
+        let fetcher = Arc::new(MockTranscriptFetcher::new());
+        let mut bot = MockBot::new(
+            MockMessagePhoto::new(),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(sent_texts(&bot), vec![MEDIA_PROMPT]);
+        assert!(fetcher.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_caption_without_youtube_url() {
+        let mut bot = MockBot::new(
+            MockMessagePhoto::new().caption("my holiday photo"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(
+            Arc::new(MockTranscriptFetcher::new()),
+            AppConfig::default(),
+        ));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            sent_texts(&bot),
+            vec!["Please provide a valid YouTube video ID."]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_options_are_reported() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "unknown options".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/unknown")
+            .create();
+
+        let fetcher = Arc::new(
+            MockTranscriptFetcher::new().with_response(Ok(vec![test_entry(
+                "unknown options",
+                0.0,
+                1.0,
+            )])),
+        );
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo foobar baz"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            fetcher.calls(),
+            vec![("HQoJMIgNdjo".to_string(), "en".to_string())]
+        );
+        assert_eq!(
+            sent_texts(&bot),
+            vec![
+                "Ignored unknown options: foobar, baz",
+                "Transcript available at: https://pastebin.com/raw/unknown"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verbose_names_the_requested_language() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "verbose hola".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/verbose")
+            .create();
+
+        let fetcher = Arc::new(
+            MockTranscriptFetcher::new().with_response(Ok(vec![test_entry(
+                "verbose hola",
+                0.0,
+                1.0,
+            )])),
+        );
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo es verbose"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher, AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            sent_texts(&bot),
+            vec![
+                "Transcript in es",
+                "Transcript available at: https://pastebin.com/raw/verbose"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blank_captions_count_as_empty_transcript() {
+        let fetcher = Arc::new(MockTranscriptFetcher::new().with_response(Ok(vec![
+            test_entry("", 0.0, 0.0),
+            test_entry("   ", 1.0, 0.0),
+        ])));
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher, AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            sent_texts(&bot),
+            vec!["Transcript could not be retrieved or is empty."]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compact_puts_each_sentence_on_a_timed_line() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "[00:00] compact one.\n[00:02] Two".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/compact")
+            .create();
+
+        let fetcher = Arc::new(MockTranscriptFetcher::new().with_response(Ok(vec![
+            test_entry("compact", 0.0, 1.0),
+            test_entry("one. Two", 2.0, 1.0),
+        ])));
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo compact"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher, AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            sent_texts(&bot),
+            vec!["Transcript available at: https://pastebin.com/raw/compact"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_sends_json_once_a_day() {
+        let history = Arc::new(history::History::open_in_memory().unwrap());
+        history
+            .record(&history::HistoryEntry {
+                user_id: 42,
+                video_id: "abc".to_string(),
+                lang: "en".to_string(),
+                upload_url: "https://pastebin.com/raw/abc".to_string(),
+                timestamp: 10,
+            })
+            .unwrap();
+        let config = AppConfig {
+            history: Some(history),
+            ..AppConfig::default()
+        };
+        let user = || MockUser::new().id(42).build();
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/export").from(user()),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(MockTranscriptFetcher::new()), config));
+
+        bot.dispatch().await;
+
+        let responses = bot.get_responses();
+        let document = &responses.sent_messages_document[0].bot_request;
+        assert!(document.file_name.starts_with("my_data_42_"));
+        assert!(document.file_name.ends_with(".json"));
+        let json: serde_json::Value = serde_json::from_str(&document.file_data).unwrap();
+        assert_eq!(json["history"][0]["video_id"], "abc");
+
+        bot.update(MockMessageText::new().text("/export").from(user()));
+        bot.dispatch().await;
+        assert!(sent_texts(&bot)
+            .last()
+            .unwrap()
+            .starts_with("You can export your data once a day."));
+    }
+
+    #[tokio::test]
+    async fn test_clear_history_asks_for_confirmation() {
+        let history = Arc::new(history::History::open_in_memory().unwrap());
+        for (video_id, timestamp) in [("abc", 10), ("xyz", 20)] {
+            let entry = history::HistoryEntry {
+                user_id: 42,
+                video_id: video_id.to_string(),
+                lang: "en".to_string(),
+                upload_url: "https://pastebin.com/raw/abc".to_string(),
+                timestamp,
+            };
+            history.record(&entry).unwrap();
+        }
+        let config = AppConfig {
+            history: Some(history.clone()),
+            ..AppConfig::default()
+        };
+        let user = || MockUser::new().id(42).build();
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/clearhistory").from(user()),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(MockTranscriptFetcher::new()), config));
+
+        bot.dispatch().await;
+        assert!(sent_texts(&bot)[0].contains("/clearhistory --confirm"));
+        assert_eq!(history.count_for_user(42).unwrap(), 2);
+
+        bot.update(
+            MockMessageText::new()
+                .text("/clearhistory --confirm")
+                .from(user()),
+        );
+        bot.dispatch().await;
+        assert_eq!(
+            sent_texts(&bot).last().unwrap(),
+            "Your transcript history (2 entries) has been deleted."
+        );
+
+        bot.update(MockMessageText::new().text("/history").from(user()));
+        bot.dispatch().await;
+        assert_eq!(sent_texts(&bot).last().unwrap(), "No transcripts found.");
+    }
+
+    #[tokio::test]
+    async fn test_set_description_requires_admin() {
+        let config = AppConfig {
+            admin_ids: vec![42],
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new()
+                .text("/admin_setdescription Transcripts on demand")
+                .from(MockUser::new().id(7).build()),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(MockTranscriptFetcher::new()), config));
+
+        bot.dispatch().await;
+        assert_eq!(sent_texts(&bot), vec!["Unauthorized"]);
+
+        bot.update(
+            MockMessageText::new()
+                .text("/admin_setdescription Transcripts on demand")
+                .from(MockUser::new().id(42).build()),
+        );
+        bot.dispatch().await;
+        assert_eq!(sent_texts(&bot).last().unwrap(), "Description updated.");
+    }
+
+    #[tokio::test]
+    async fn test_admin_info_sends_parseable_json() {
+        let config = AppConfig {
+            admin_ids: vec![42],
+            // The admin's own request is the one key the limiter tracks
+            rate_limit_requests: 5,
+            rate_limit_window: std::time::Duration::from_secs(60),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new()
+                .text("/admininfo")
+                .from(MockUser::new().id(42).build()),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(MockTranscriptFetcher::new()), config));
+
+        bot.dispatch().await;
+
+        let messages = sent_texts(&bot);
+        assert_eq!(messages.len(), 1);
+        let json = messages[0]
+            .strip_prefix("```json\n")
+            .and_then(|text| text.strip_suffix("\n```"))
+            .unwrap();
+        let diagnostics: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(diagnostics["rate_limit_keys"], 1);
+        assert!(diagnostics["version"].is_string());
+        assert!(diagnostics["commit"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_chunked_messages_keep_the_footer() {
+        let entries = (0..400)
+            .map(|i| {
+                test_entry(
+                    "a caption long enough to fill several messages",
+                    i as f64,
+                    1.0,
+                )
+            })
+            .collect();
+        let config = AppConfig {
+            message_footer: Some("\n— via @tofuboi".to_string()),
+            delivery_mode: Some(DeliveryMode::Messages),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("dQw4w9WgXcQ"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(entries));
+        bot.dependencies(test_deps(Arc::new(fetcher), config));
+
+        bot.dispatch().await;
+
+        let messages = sent_texts(&bot);
+        assert!(messages.len() > 2);
+        for message in messages {
+            assert!(message.ends_with("\n— via @tofuboi"), "{:?}", message);
+        }
+    }
+
+    #[test]
+    fn test_short_description_is_truncated() {
+        let long = "é".repeat(200);
+        assert_eq!(short_description(&long).chars().count(), 120);
+        assert_eq!(short_description("short"), "short");
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload_sends_excerpt() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "Pastebin is down".into(),
+            ))
+            .with_status(503)
+            .with_body("Service Unavailable")
+            .create();
+
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![test_entry(
+            "Pastebin is down",
+            0.0,
+            2.0,
+        )]));
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(fetcher), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        let last = sent_texts(&bot).pop().unwrap();
+        assert!(last.starts_with("[Showing first 3000 chars; upload failed: "));
+        assert!(last.ends_with("\nPastebin is down"));
+    }
+
+    #[tokio::test]
+    async fn test_two_formats_send_document_and_link() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "Two formats".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/formats")
+            .create();
+
+        let fetcher = Arc::new(
+            MockTranscriptFetcher::new().with_response(Ok(vec![test_entry(
+                "Two formats",
+                0.0,
+                2.0,
+            )])),
+        );
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo format=srt,txt"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        // Fetched once for both formats
+        assert_eq!(fetcher.calls().len(), 1);
+        let responses = bot.get_responses();
+        let document = &responses.sent_messages_document[0].bot_request;
+        assert!(document.file_name.ends_with(".srt"));
+        assert!(document
+            .file_data
+            .contains("00:00:00,000 --> 00:00:02,000\nTwo formats"));
+        assert!(sent_texts(&bot)
+            .iter()
+            .any(|text| text.contains("https://pastebin.com/raw/formats")));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_captions_are_dropped_from_exports() {
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![
+            test_entry("hello", 0.0, 1.0),
+            test_entry("hello", 1.0, 1.0),
+            test_entry("world", 2.0, 1.0),
+        ]));
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo format=srt"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        let config = AppConfig {
+            dedup_transcripts: true,
+            ..AppConfig::default()
+        };
+        bot.dependencies(test_deps(Arc::new(fetcher), config));
+
+        bot.dispatch().await;
+
+        let responses = bot.get_responses();
+        let document = &responses.sent_messages_document[0].bot_request;
+        assert_eq!(document.file_data.matches("hello").count(), 1);
+        assert!(document
+            .file_data
+            .contains("2\n00:00:02,000 --> 00:00:03,000\nworld"));
+    }
+
+    #[tokio::test]
+    async fn test_raw_json_is_sent_as_document() {
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![test_entry(
+            "Raw caption",
+            0.0,
+            2.0,
+        )]));
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo raw"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(fetcher), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        let responses = bot.get_responses();
+        let document = &responses.sent_messages_document[0].bot_request;
+        assert!(document.file_name.ends_with(".json"));
+        assert!(document.file_data.contains("\"text\": \"Raw caption\""));
+        assert!(sent_texts(&bot)
+            .iter()
+            .all(|text| !text.contains("pastebin.com")));
+    }
+
+    #[tokio::test]
+    async fn test_live_stream_warning_comes_with_captions() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _page = mock("GET", "/watch?v=jfKfPfyJRdk")
+            .with_status(200)
+            .with_body(r#"{"liveBroadcastDetails":{"isLiveNow":true}}"#)
+            .create();
+        let _paste = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "so far".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/live")
+            .create();
+
+        let fetcher =
+            MockTranscriptFetcher::new().with_response(Ok(vec![test_entry("so far", 0.0, 2.0)]));
+        let config = AppConfig {
+            detect_live: true,
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("jfKfPfyJRdk"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(fetcher), config));
+
+        bot.dispatch().await;
+
+        let messages = sent_texts(&bot);
+        assert_eq!(messages[0], live::LIVE_STREAM_WARNING);
+        assert!(messages
+            .iter()
+            .any(|text| text.contains("https://pastebin.com/raw/live")));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_command() {
+        let _service = mock("POST", "/summarize")
+            .match_body(Matcher::Json(
+                serde_json::json!({ "text": "First line\nSecond line" }),
+            ))
+            .with_status(200)
+            .with_body(r#"{"summary": "Two lines."}"#)
+            .create();
+
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![
+            test_entry("First line", 0.0, 2.0),
+            test_entry("Second line", 2.0, 2.0),
+        ]));
+        let config = AppConfig {
+            summarize: Some(summarize::SummarizeApi {
+                url: format!("{}/summarize", mockito::server_url()),
+                key: None,
+                max_chars: summarize::DEFAULT_SUMMARIZE_MAX_CHARS,
+            }),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/summarize HQoJMIgNdjo"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(fetcher), config));
+
+        bot.dispatch().await;
+
+        assert_eq!(sent_texts(&bot), vec!["Two lines."]);
+    }
+
+    #[tokio::test]
+    async fn test_ping_command() {
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/ping"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(
+            Arc::new(MockTranscriptFetcher::new()),
+            AppConfig::default(),
+        ));
+
+        bot.dispatch().await;
+
+        let messages = sent_texts(&bot);
+        assert_eq!(messages.len(), 1);
+        let latency = messages[0]
+            .strip_prefix("Pong! 🏓 Latency: ")
+            .and_then(|rest| rest.strip_suffix("ms"))
+            .unwrap();
+        assert!(latency.parse::<i64>().unwrap() >= 0);
+    }
+
+    #[tokio::test]
+    async fn test_version_command() {
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/version"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(
+            Arc::new(MockTranscriptFetcher::new()),
+            AppConfig::default(),
+        ));
+
+        bot.dispatch().await;
+
+        let messages = sent_texts(&bot);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn test_version_text() {
+        let text = version_text();
+        assert!(text.starts_with(&format!("tofuboi {}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(text.contains("Commit: "));
+        assert!(!text.contains("Built: unknown"));
+    }
+
+    #[tokio::test]
+    async fn test_group_messages_require_prefix() {
+        let fetcher = Arc::new(MockTranscriptFetcher::new());
+        let config = AppConfig {
+            group_prefix: Some("!t ".to_string()),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            vec![
+                MockMessageText::new()
+                    .text("HQoJMIgNdjo")
+                    .id(1)
+                    .chat(MockGroupChat::new().build()),
+                MockMessageText::new()
+                    .text("!t dQw4w9WgXcQ")
+                    .id(2)
+                    .chat(MockGroupChat::new().build()),
+            ],
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), config));
+
+        bot.dispatch().await;
+
+        // Only the prefixed message is fetched and answered
+        assert_eq!(
+            fetcher.calls(),
+            vec![("dQw4w9WgXcQ".to_string(), "en".to_string())]
+        );
+        assert_eq!(sent_texts(&bot).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_group_replies_are_threaded() {
+        let config = AppConfig {
+            reply_in_thread: true,
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            vec![
+                MockMessageText::new()
+                    .text("/ping")
+                    .id(7)
+                    .chat(MockGroupChat::new().build()),
+                MockMessageText::new().text("/ping").id(8),
+            ],
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(Arc::new(MockTranscriptFetcher::new()), config));
+
+        bot.dispatch().await;
+
+        let replies: Vec<Option<MessageId>> = bot
+            .get_responses()
+            .sent_messages
+            .iter()
+            .map(|m| m.reply_to_message().map(|original| original.id))
             .collect();
+        // Private chats stay unthreaded
+        assert_eq!(replies, vec![Some(MessageId(7)), None]);
+    }
 
-        assert!(!messages.is_empty());
-        assert_eq!(messages[0], "Please provide a video ID.");
+    #[tokio::test]
+    async fn test_duplicate_update_is_ignored() {
+        let fetcher = Arc::new(MockTranscriptFetcher::new());
+        let mut bot = MockBot::new(
+            vec![
+                MockMessageText::new().text("HQoJMIgNdjo").id(42),
+                MockMessageText::new().text("HQoJMIgNdjo").id(42),
+            ],
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(fetcher.calls().len(), 1);
+        assert_eq!(sent_texts(&bot).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_stops_before_handler() {
+        let fetcher = Arc::new(MockTranscriptFetcher::new());
+        let config = AppConfig {
+            rate_limit_requests: 1,
+            rate_limit_window: std::time::Duration::from_secs(60),
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            vec![
+                MockMessageText::new().text("HQoJMIgNdjo").id(1),
+                MockMessageText::new().text("/transcript dQw4w9WgXcQ").id(2),
+            ],
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), config));
+
+        bot.dispatch().await;
+
+        // The command never reaches its handler once the user is limited
+        assert_eq!(
+            fetcher.calls(),
+            vec![("HQoJMIgNdjo".to_string(), "en".to_string())]
+        );
+        let messages = sent_texts(&bot);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[1].ends_with("Try again in 60 seconds."));
+    }
+
+    #[tokio::test]
+    async fn test_bilingual_transcript() {
+        let fetcher = Arc::new(
+            MockTranscriptFetcher::new()
+                .with_response(Ok(vec![test_entry("Hello", 0.0, 1.0)]))
+                .with_response(Ok(vec![test_entry("Hola", 0.2, 1.0)])),
+        );
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo bilingual:es"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(fetcher.calls().len(), 2);
+        assert_eq!(sent_texts(&bot), vec!["[00:00] Hello\n        Hola\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_bilingual_transcript_is_deduplicated() {
+        let fetcher = Arc::new(
+            MockTranscriptFetcher::new()
+                .with_response(Ok(vec![
+                    test_entry("Hello", 0.0, 1.0),
+                    test_entry("Hello", 1.0, 1.0),
+                ]))
+                .with_response(Ok(vec![test_entry("Hola", 0.2, 1.0)])),
+        );
+        let config = AppConfig {
+            dedup_transcripts: true,
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo bilingual:es"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher, config));
+
+        bot.dispatch().await;
+
+        assert_eq!(sent_texts(&bot), vec!["[00:00] Hello\n        Hola\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_bilingual_transcript_is_filtered() {
+        let fetcher = Arc::new(
+            MockTranscriptFetcher::new()
+                .with_response(Ok(vec![
+                    test_entry("um Hello [inaudible]", 0.0, 1.0),
+                    test_entry("ok", 1.0, 1.0),
+                ]))
+                .with_response(Ok(vec![test_entry("Hola", 0.2, 1.0)])),
+        );
+        let config = AppConfig {
+            defiller: true,
+            filler_words: std::collections::HashMap::from([(
+                "en".to_string(),
+                vec!["um".to_string()],
+            )]),
+            strip_inaudible: true,
+            ..AppConfig::default()
+        };
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo bilingual:es min_len=3"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher, config));
+
+        bot.dispatch().await;
+
+        assert_eq!(sent_texts(&bot), vec!["[00:00] Hello\n        Hola\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_note_is_sent_with_link_and_keyboard() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .with_status(200)
+            .with_body("https://pastebin.com/fallback1")
+            .create();
+
+        let fetcher = Arc::new(
+            MockTranscriptFetcher::new()
+                .with_response(Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(
+                    "fr".to_string(),
+                    vec!["de".to_string(), "en".to_string()],
+                    "HQoJMIgNdjo".to_string(),
+                )))
+                .with_response(Ok(vec![test_entry("Hello", 0.0, 1.0)])),
+        );
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("HQoJMIgNdjo fr"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher, AppConfig::default()));
+
+        bot.dispatch().await;
+
+        let responses = bot.get_responses();
+        assert_eq!(responses.sent_messages_text.len(), 1);
+        let request = &responses.sent_messages_text[0].bot_request;
+        assert_eq!(
+            request.text,
+            "Requested language 'fr' not available. Using fallback language 'en'.\n\n\
+             Transcript available at: https://pastebin.com/raw/fallback1"
+        );
+        assert!(request.reply_markup.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_language_button_fetches_that_language() {
+        let fetcher = Arc::new(
+            MockTranscriptFetcher::new().with_response(Ok(vec![test_entry("Hola", 0.0, 1.0)])),
+        );
+        let mut bot = MockBot::new(
+            MockCallbackQuery::new().data("lang:HQoJMIgNdjo:es"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            fetcher.calls(),
+            vec![("HQoJMIgNdjo".to_string(), "es".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transcript_command_with_language() {
+        let fetcher = Arc::new(MockTranscriptFetcher::new().with_response(Err(
+            YoutubeTranscriptError::TranscriptDisabled("HQoJMIgNdjo".to_string()),
+        )));
+        let mut bot = MockBot::new(
+            MockMessageText::new().text("/transcript HQoJMIgNdjo es"),
+            handler_tree::<MockTranscriptFetcher>(),
+        );
+        bot.dependencies(test_deps(fetcher.clone(), AppConfig::default()));
+
+        bot.dispatch().await;
+
+        assert_eq!(
+            fetcher.calls(),
+            vec![("HQoJMIgNdjo".to_string(), "es".to_string())]
+        );
     }
 }
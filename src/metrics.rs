@@ -0,0 +1,96 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Usage counters, exported on the health server's `/metrics`. The names
+/// are scraped by dashboards, so do not rename them.
+#[derive(Debug, Clone, Copy)]
+pub enum Counter {
+    Requests,
+    Successes,
+    Errors,
+    UploadFailures,
+    CacheHits,
+    NetworkErrors,
+    ConfigErrors,
+    HandlerErrors,
+}
+
+impl Counter {
+    const ALL: [Counter; 8] = [
+        Counter::Requests,
+        Counter::Successes,
+        Counter::Errors,
+        Counter::UploadFailures,
+        Counter::CacheHits,
+        Counter::NetworkErrors,
+        Counter::ConfigErrors,
+        Counter::HandlerErrors,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Counter::Requests => "tofuboi_requests_total",
+            Counter::Successes => "tofuboi_successes_total",
+            Counter::Errors => "tofuboi_errors_total",
+            Counter::UploadFailures => "tofuboi_upload_failures_total",
+            Counter::CacheHits => "tofuboi_cache_hits_total",
+            Counter::NetworkErrors => "tofuboi_network_errors_total",
+            Counter::ConfigErrors => "tofuboi_config_errors_total",
+            Counter::HandlerErrors => "tofuboi_handler_errors_total",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Counter::Requests => "Transcript requests received.",
+            Counter::Successes => "Transcripts delivered to users.",
+            Counter::Errors => "Transcript requests that failed to fetch.",
+            Counter::UploadFailures => "Transcript uploads to Pastebin that failed.",
+            Counter::CacheHits => "Transcripts served from the cache.",
+            Counter::NetworkErrors => "Handler errors from unreachable services.",
+            Counter::ConfigErrors => "Handler errors from a rejected token or missing setting.",
+            Counter::HandlerErrors => "Other errors that escaped a handler.",
+        }
+    }
+}
+
+static COUNTS: [AtomicU64; Counter::ALL.len()] = [const { AtomicU64::new(0) }; Counter::ALL.len()];
+
+pub fn increment(counter: Counter) {
+    COUNTS[counter as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn get(counter: Counter) -> u64 {
+    COUNTS[counter as usize].load(Ordering::Relaxed)
+}
+
+/// Renders every counter in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut output = String::new();
+    for counter in Counter::ALL {
+        let _ = writeln!(output, "# HELP {} {}", counter.name(), counter.help());
+        let _ = writeln!(output, "# TYPE {} counter", counter.name());
+        let _ = writeln!(output, "{} {}", counter.name(), get(counter));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_exposition_format() {
+        let before = get(Counter::UploadFailures);
+        increment(Counter::UploadFailures);
+        assert!(get(Counter::UploadFailures) > before);
+
+        let output = render();
+        assert!(output.contains("# TYPE tofuboi_requests_total counter\n"));
+        for line in output.lines().filter(|l| !l.starts_with('#')) {
+            let (name, value) = line.split_once(' ').unwrap();
+            assert!(name.starts_with("tofuboi_") && name.ends_with("_total"));
+            assert!(value.parse::<u64>().is_ok());
+        }
+    }
+}
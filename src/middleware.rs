@@ -0,0 +1,190 @@
+use crate::bot_ext::BotExt;
+use crate::config::AppConfig;
+use crate::ratelimit::{cooldown_secs, RateLimiter};
+use dptree::di::{DependencyMap, DependencySupplier};
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Instant;
+use teloxide::dispatching::UpdateHandler;
+use teloxide::prelude::*;
+use teloxide::types::{Update, UpdateKind};
+use teloxide::DownloadError;
+use teloxide::RequestError;
+
+type HandlerError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Longest message text included in a request log line.
+const LOGGED_TEXT_CHARS: usize = 50;
+
+/// What is logged about one handled update.
+#[derive(Debug)]
+pub struct RequestLog {
+    pub update_id: u32,
+    pub chat_id: Option<i64>,
+    pub user_id: Option<u64>,
+    pub message_text: Option<String>,
+    pub handler_duration_ms: u128,
+    /// Kind and message of the error the handler returned
+    pub error: Option<(&'static str, String)>,
+}
+
+impl RequestLog {
+    fn new(update: &Update) -> Self {
+        let message_text = match &update.kind {
+            UpdateKind::Message(msg) | UpdateKind::EditedMessage(msg) => msg
+                .text()
+                .or(msg.caption())
+                .map(|text| text.chars().take(LOGGED_TEXT_CHARS).collect()),
+            _ => None,
+        };
+        Self {
+            update_id: update.id.0,
+            chat_id: update.chat().map(|chat| chat.id.0),
+            user_id: update.from().map(|user| user.id.0),
+            message_text,
+            handler_duration_ms: 0,
+            error: None,
+        }
+    }
+}
+
+/// Names the error types handlers are known to return.
+fn error_kind(e: &HandlerError) -> &'static str {
+    if e.downcast_ref::<RequestError>().is_some() {
+        "RequestError"
+    } else if e.downcast_ref::<DownloadError>().is_some() {
+        "DownloadError"
+    } else {
+        "Other"
+    }
+}
+
+fn emit(log: &RequestLog) {
+    match &log.error {
+        None => tracing::info!(
+            update_id = log.update_id,
+            chat_id = ?log.chat_id,
+            user_id = ?log.user_id,
+            message_text = ?log.message_text,
+            handler_duration_ms = log.handler_duration_ms as u64,
+            "update handled"
+        ),
+        Some((kind, message)) => tracing::warn!(
+            update_id = log.update_id,
+            chat_id = ?log.chat_id,
+            user_id = ?log.user_id,
+            message_text = ?log.message_text,
+            handler_duration_ms = log.handler_duration_ms as u64,
+            error_type = kind,
+            error = %message,
+            "update failed"
+        ),
+    }
+}
+
+/// Logs every update passing through the handlers chained after it, with
+/// how long they took and any error they returned.
+pub fn request_logging() -> UpdateHandler<HandlerError> {
+    request_logging_with(emit)
+}
+
+fn request_logging_with(sink: fn(&RequestLog)) -> UpdateHandler<HandlerError> {
+    dptree::from_fn(move |deps: DependencyMap, cont| async move {
+        let update: Arc<Update> = deps.get();
+        let mut log = RequestLog::new(&update);
+        let start = Instant::now();
+
+        let result = cont(deps).await;
+
+        log.handler_duration_ms = start.elapsed().as_millis();
+        if let ControlFlow::Break(Err(e)) = &result {
+            log.error = Some((error_kind(e), e.to_string()));
+        }
+        sink(&log);
+        result
+    })
+}
+
+/// Stops updates over their [`RateLimiter`] budget before any handler runs,
+/// telling the sender when to try again. Whether the budget belongs to the
+/// user, the chat or both follows `rate_limit_scope`.
+pub fn rate_limit_layer() -> UpdateHandler<HandlerError> {
+    dptree::from_fn(|deps: DependencyMap, cont| async move {
+        let update: Arc<Update> = deps.get();
+        let (Some(user), Some(chat)) = (update.from(), update.chat()) else {
+            return cont(deps).await;
+        };
+
+        let config: Arc<AppConfig> = deps.get();
+        let key = config.rate_limit_scope.key(user.id, chat.id);
+        let limiter: Arc<RateLimiter> = deps.get();
+        let Err(wait) = limiter.check(key) else {
+            return cont(deps).await;
+        };
+
+        log::info!("Rate limited {:?}", key);
+        let bot: Arc<Bot> = deps.get();
+        let text = format!(
+            "You're sending requests too quickly. Try again in {} seconds.",
+            cooldown_secs(wait)
+        );
+        let sent = bot
+            .send_with_footer(chat.id, text, config.message_footer.as_deref(), None)
+            .await;
+        ControlFlow::Break(sent.map(|_| ()).map_err(Into::into))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    static LOGGED: Mutex<Vec<RequestLog>> = Mutex::new(Vec::new());
+
+    fn collect(log: &RequestLog) {
+        LOGGED.lock().unwrap().push(RequestLog {
+            message_text: log.message_text.clone(),
+            error: log.error.clone(),
+            ..*log
+        });
+    }
+
+    fn text_update(text: &str) -> Update {
+        // Update only deserializes correctly from a string, not a `Value`
+        let json = serde_json::json!({
+            "update_id": 7,
+            "message": {
+                "message_id": 1,
+                "date": 1700000000,
+                "chat": { "id": 42, "type": "private", "first_name": "Ann" },
+                "from": { "id": 99, "is_bot": false, "first_name": "Ann" },
+                "text": text
+            }
+        });
+        serde_json::from_str(&json.to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_logs_when_handler_fails() {
+        let handler = request_logging_with(collect).chain(dptree::endpoint(|| async {
+            Err::<(), HandlerError>("boom".into())
+        }));
+
+        let result = handler
+            .dispatch(dptree::deps![text_update(&"x".repeat(80))])
+            .await;
+
+        assert!(matches!(result, ControlFlow::Break(Err(_))));
+        let logged = LOGGED.lock().unwrap();
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].update_id, 7);
+        assert_eq!(logged[0].chat_id, Some(42));
+        assert_eq!(logged[0].user_id, Some(99));
+        assert_eq!(
+            logged[0].message_text.as_ref().unwrap().len(),
+            LOGGED_TEXT_CHARS
+        );
+        assert_eq!(logged[0].error, Some(("Other", "boom".to_string())));
+    }
+}
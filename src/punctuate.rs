@@ -0,0 +1,162 @@
+use crate::formatter;
+use reqwest::Client;
+use ytranscript::TranscriptResponse;
+
+/// Silence, in seconds, treated as the end of a sentence by the heuristic.
+const SENTENCE_GAP_SECS: f64 = 1.5;
+
+type PunctuateResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Restores punctuation in place, through the service at `api_url` when one
+/// is configured and with [`punctuate_heuristic`] otherwise or on failure.
+pub async fn punctuate(client: &Client, api_url: Option<&str>, entries: &mut [TranscriptResponse]) {
+    if let Some(api_url) = api_url {
+        match punctuate_remote(client, api_url, entries).await {
+            Ok(()) => return,
+            Err(e) => log::warn!("Punctuation service failed, using heuristic: {}", e),
+        }
+    }
+    punctuate_heuristic(entries);
+}
+
+/// Sends one caption per line as plain text and expects the same number of
+/// lines back, so each caption keeps its timing.
+async fn punctuate_remote(
+    client: &Client,
+    api_url: &str,
+    entries: &mut [TranscriptResponse],
+) -> PunctuateResult<()> {
+    let body = entries
+        .iter()
+        .map(|entry| entry.text.replace('\n', " "))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let response = client
+        .post(api_url)
+        .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(format!("punctuation service returned {}", response.status()).into());
+    }
+
+    let punctuated = response.text().await?;
+    let lines: Vec<&str> = punctuated.lines().collect();
+    if lines.len() != entries.len() {
+        return Err(format!(
+            "punctuation service returned {} lines for {} captions",
+            lines.len(),
+            entries.len()
+        )
+        .into());
+    }
+
+    for (entry, line) in entries.iter_mut().zip(lines) {
+        entry.text = line.to_string();
+    }
+    Ok(())
+}
+
+/// Starts a sentence after every long pause: the caption before the pause
+/// gets a full stop and the one after it a capital letter.
+pub fn punctuate_heuristic(entries: &mut [TranscriptResponse]) {
+    for i in 0..entries.len() {
+        let starts_sentence = match i.checked_sub(1).map(|prev| &entries[prev]) {
+            None => true,
+            Some(prev) => formatter::gap_between(prev, &entries[i]) >= SENTENCE_GAP_SECS,
+        };
+        if !starts_sentence {
+            continue;
+        }
+
+        if i > 0 {
+            end_sentence(&mut entries[i - 1].text);
+        }
+        capitalize_first(&mut entries[i].text);
+    }
+
+    if let Some(last) = entries.last_mut() {
+        end_sentence(&mut last.text);
+    }
+}
+
+fn end_sentence(text: &mut String) {
+    let trimmed_len = text.trim_end().len();
+    text.truncate(trimmed_len);
+    if !text.is_empty() && !text.ends_with(['.', '!', '?', '…']) {
+        text.push('.');
+    }
+}
+
+fn capitalize_first(text: &mut String) {
+    if let Some(first) = text.chars().next() {
+        let upper: String = first.to_uppercase().collect();
+        text.replace_range(..first.len_utf8(), &upper);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::test_entry;
+    use mockito::{mock, Matcher};
+
+    fn texts(entries: &[TranscriptResponse]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_heuristic_splits_on_long_gaps() {
+        let mut entries = vec![
+            test_entry("so today we", 0.0, 1.0),
+            test_entry("look at bread", 1.0, 1.0),
+            test_entry("it needs flour", 4.0, 1.0),
+            test_entry("really?", 7.0, 1.0),
+            test_entry("übrigens", 10.0, 1.0),
+        ];
+        punctuate_heuristic(&mut entries);
+
+        assert_eq!(
+            texts(&entries),
+            vec![
+                "So today we",
+                "look at bread.",
+                "It needs flour.",
+                "Really?",
+                "Übrigens."
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_service() {
+        let _m = mock("POST", "/punctuate")
+            .match_header("content-type", "text/plain; charset=utf-8")
+            .match_body(Matcher::Exact("hello there\nhow are you".to_string()))
+            .with_status(200)
+            .with_body("Hello there.\nHow are you?")
+            .create();
+
+        let mut entries = vec![
+            test_entry("hello there", 0.0, 1.0),
+            test_entry("how are you", 1.0, 1.0),
+        ];
+        let url = format!("{}/punctuate", mockito::server_url());
+        punctuate(&Client::new(), Some(&url), &mut entries).await;
+
+        assert_eq!(texts(&entries), vec!["Hello there.", "How are you?"]);
+    }
+
+    #[tokio::test]
+    async fn test_remote_failure_falls_back_to_heuristic() {
+        let _m = mock("POST", "/punctuate-down").with_status(503).create();
+
+        let mut entries = vec![test_entry("hello there", 0.0, 1.0)];
+        let url = format!("{}/punctuate-down", mockito::server_url());
+        punctuate(&Client::new(), Some(&url), &mut entries).await;
+
+        assert_eq!(texts(&entries), vec!["Hello there."]);
+    }
+}
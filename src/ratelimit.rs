@@ -0,0 +1,236 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use teloxide::types::{ChatId, UserId};
+
+/// Requests a user may make per window when `RATE_LIMIT_REQUESTS` is unset.
+pub const DEFAULT_RATE_LIMIT_REQUESTS: usize = 5;
+
+/// Window length when `RATE_LIMIT_WINDOW_SECS` is unset.
+pub const DEFAULT_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Who shares a rate limit budget.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RateLimitScope {
+    /// Each user, across every chat
+    #[default]
+    User,
+    /// Each chat, shared by everyone in it
+    Chat,
+    /// Each user separately in each chat
+    UserPerChat,
+}
+
+impl RateLimitScope {
+    /// Reads `RATE_LIMIT_SCOPE`, defaulting to per-user for unknown values
+    pub fn from_env() -> Self {
+        env::var("RATE_LIMIT_SCOPE")
+            .ok()
+            .and_then(|name| Self::parse(&name))
+            .unwrap_or_default()
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "user" => Some(RateLimitScope::User),
+            "chat" => Some(RateLimitScope::Chat),
+            "user_per_chat" => Some(RateLimitScope::UserPerChat),
+            _ => None,
+        }
+    }
+
+    /// The budget a request from `user_id` in `chat_id` counts against
+    pub fn key(&self, user_id: UserId, chat_id: ChatId) -> RateLimitKey {
+        match self {
+            RateLimitScope::User => RateLimitKey::User(user_id),
+            RateLimitScope::Chat => RateLimitKey::Chat(chat_id),
+            RateLimitScope::UserPerChat => RateLimitKey::UserPerChat(user_id, chat_id),
+        }
+    }
+}
+
+/// One budget in a [`RateLimiter`], chosen by [`RateLimitScope::key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    User(UserId),
+    Chat(ChatId),
+    UserPerChat(UserId, ChatId),
+}
+
+/// Sliding-window limit on how many requests each key may make.
+pub struct RateLimiter {
+    max_requests: usize,
+    window: Duration,
+    requests: Mutex<HashMap<RateLimitKey, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    /// A limit of zero requests disables rate limiting
+    pub fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keys with requests on record
+    pub fn tracked(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Records a request, or returns how long the sender has to wait
+    pub fn check(&self, key: RateLimitKey) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    /// Like [`Self::check`], as of `now`
+    pub fn check_at(&self, key: RateLimitKey, now: Instant) -> Result<(), Duration> {
+        if self.max_requests == 0 {
+            return Ok(());
+        }
+
+        let mut requests = self.requests.lock().unwrap();
+        // Keys whose requests have all expired are dropped, so the map only
+        // holds recent senders
+        requests.retain(|_, times| {
+            while times
+                .front()
+                .is_some_and(|&t| now.duration_since(t) >= self.window)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        let times = requests.entry(key).or_default();
+
+        if times.len() >= self.max_requests {
+            // The oldest request in the window is the next one to expire
+            let oldest = times[0];
+            return Err(self.window - now.duration_since(oldest));
+        }
+        times.push_back(now);
+        Ok(())
+    }
+}
+
+/// Whole seconds to tell a user to wait, never rounding down to zero.
+pub fn cooldown_secs(wait: Duration) -> u64 {
+    wait.as_millis().div_ceil(1000).max(1) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_and_cooldown() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let user = RateLimitKey::User(UserId(1));
+        let start = Instant::now();
+
+        assert!(limiter.check_at(user, start).is_ok());
+        assert!(limiter
+            .check_at(user, start + Duration::from_secs(10))
+            .is_ok());
+
+        let wait = limiter
+            .check_at(user, start + Duration::from_secs(20))
+            .unwrap_err();
+        assert_eq!(wait, Duration::from_secs(40));
+        assert_eq!(cooldown_secs(wait), 40);
+
+        assert_eq!(limiter.tracked(), 1);
+        // Other users have their own budget
+        assert!(limiter
+            .check_at(RateLimitKey::User(UserId(2)), start)
+            .is_ok());
+        // Once the first request leaves the window there is room again
+        assert!(limiter
+            .check_at(user, start + Duration::from_secs(60))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_expired_keys_are_dropped() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        for id in 1..=3 {
+            assert!(limiter
+                .check_at(RateLimitKey::User(UserId(id)), start)
+                .is_ok());
+        }
+        assert_eq!(limiter.tracked(), 3);
+
+        // A request after the window leaves only its own sender on record
+        let later = start + Duration::from_secs(60);
+        assert!(limiter
+            .check_at(RateLimitKey::User(UserId(4)), later)
+            .is_ok());
+        assert_eq!(limiter.tracked(), 1);
+    }
+
+    #[test]
+    fn test_zero_disables_limit() {
+        let limiter = RateLimiter::new(0, Duration::from_secs(60));
+        for _ in 0..100 {
+            assert!(limiter.check(RateLimitKey::User(UserId(1))).is_ok());
+        }
+    }
+
+    /// Whether each of `requests` is allowed under a one-request limit.
+    fn allowed(scope: RateLimitScope, requests: &[(u64, i64)]) -> Vec<bool> {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        requests
+            .iter()
+            .map(|&(user, chat)| limiter.check(scope.key(UserId(user), ChatId(chat))).is_ok())
+            .collect()
+    }
+
+    #[test]
+    fn test_user_scope() {
+        // The same user is limited across chats; another user is not
+        assert_eq!(
+            allowed(RateLimitScope::User, &[(1, -10), (1, -20), (2, -10)]),
+            vec![true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_chat_scope() {
+        // Everyone in a chat shares its budget; another chat has its own
+        assert_eq!(
+            allowed(RateLimitScope::Chat, &[(1, -10), (2, -10), (1, -20)]),
+            vec![true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_user_per_chat_scope() {
+        assert_eq!(
+            allowed(
+                RateLimitScope::UserPerChat,
+                &[(1, -10), (1, -20), (2, -10), (1, -10)]
+            ),
+            vec![true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!(RateLimitScope::parse("chat"), Some(RateLimitScope::Chat));
+        assert_eq!(
+            RateLimitScope::parse("user_per_chat"),
+            Some(RateLimitScope::UserPerChat)
+        );
+        assert_eq!(RateLimitScope::parse("group"), None);
+    }
+
+    #[test]
+    fn test_cooldown_rounds_up() {
+        assert_eq!(cooldown_secs(Duration::from_millis(1)), 1);
+        assert_eq!(cooldown_secs(Duration::from_millis(2001)), 3);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::transcript::TranscriptFetcher;
+use crate::upload::fetch_and_upload;
+use reqwest::Client;
+use std::sync::Arc;
+
+/// Fetches and uploads one transcript so broken credentials or network
+/// problems show up at deploy time rather than on the first user request.
+pub async fn run_selftest<T: TranscriptFetcher>(
+    fetcher: &T,
+    client: Arc<Client>,
+    video_id: &str,
+) -> Result<String, String> {
+    log::info!("Running startup self-test with video {}", video_id);
+    fetch_and_upload(fetcher, client, video_id, "en")
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::{test_entry, MockTranscriptFetcher};
+    use mockito::{mock, Matcher};
+    use std::env;
+    use ytranscript::YoutubeTranscriptError;
+
+    #[tokio::test]
+    async fn test_selftest_passes() {
+        env::set_var("PASTEBIN_KEY", "test_api_key");
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "self-test entry".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/selftest")
+            .create();
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![test_entry(
+            "self-test entry",
+            0.0,
+            1.0,
+        )]));
+
+        let result = run_selftest(&fetcher, Arc::new(Client::new()), "HQoJMIgNdjo").await;
+
+        assert_eq!(result.unwrap(), "https://pastebin.com/raw/selftest");
+    }
+
+    #[tokio::test]
+    async fn test_selftest_reports_fetch_failure() {
+        let fetcher = MockTranscriptFetcher::new().with_response(Err(
+            YoutubeTranscriptError::VideoUnavailable("HQoJMIgNdjo".to_string()),
+        ));
+
+        let result = run_selftest(&fetcher, Arc::new(Client::new()), "HQoJMIgNdjo").await;
+
+        assert!(result.unwrap_err().starts_with("Error fetching transcript"));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::formatter::estimate_token_count;
+use reqwest::Client;
+use std::env;
+
+/// Longest transcript, in characters, sent for summary by default.
+pub const DEFAULT_SUMMARIZE_MAX_CHARS: usize = 20_000;
+
+/// Estimated tokens sent per request, so each chunk fits a small context
+/// window together with the service's prompt and its reply.
+const CHUNK_TOKENS: usize = 3_000;
+
+/// Sent before the summary of a transcript that had to be cut short.
+pub const TRUNCATED_NOTICE: &str =
+    "The transcript is too long to summarize in full; this summary covers only its beginning.";
+
+type SummarizeResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// The external service behind `/summarize`. It takes `{"text": "..."}` and
+/// answers `{"summary": "..."}`.
+#[derive(Debug, Clone)]
+pub struct SummarizeApi {
+    /// `SUMMARIZE_API_URL`
+    pub url: String,
+    /// Sent as a bearer token when set (`SUMMARIZE_API_KEY`)
+    pub key: Option<String>,
+    /// Transcripts longer than this are truncated (`SUMMARIZE_MAX_CHARS`)
+    pub max_chars: usize,
+}
+
+impl SummarizeApi {
+    /// `None` unless `SUMMARIZE_API_URL` is set.
+    pub fn from_env() -> Option<Self> {
+        let url = env::var("SUMMARIZE_API_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())?;
+        Some(Self {
+            url,
+            key: env::var("SUMMARIZE_API_KEY")
+                .ok()
+                .filter(|key| !key.is_empty()),
+            max_chars: env::var("SUMMARIZE_MAX_CHARS")
+                .ok()
+                .and_then(|chars| chars.parse().ok())
+                .filter(|&chars| chars > 0)
+                .unwrap_or(DEFAULT_SUMMARIZE_MAX_CHARS),
+        })
+    }
+
+    /// Summarizes `text` one context-sized chunk at a time and joins the
+    /// partial summaries. The flag is set when `text` had to be truncated.
+    pub async fn summarize(&self, client: &Client, text: &str) -> SummarizeResult<(String, bool)> {
+        let (text, truncated) = truncate_chars(text, self.max_chars);
+        let mut summaries = Vec::new();
+        for chunk in chunk_for_context(text, CHUNK_TOKENS) {
+            summaries.push(self.summarize_chunk(client, &chunk).await?);
+        }
+        Ok((summaries.join("\n\n"), truncated))
+    }
+
+    async fn summarize_chunk(&self, client: &Client, text: &str) -> SummarizeResult<String> {
+        let body = serde_json::json!({ "text": text });
+        let mut request = client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        if let Some(key) = &self.key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(format!("summarization service returned {}", response.status()).into());
+        }
+        let body: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        match body["summary"].as_str() {
+            Some(summary) => Ok(summary.trim().to_string()),
+            None => Err("summarization service returned no summary".into()),
+        }
+    }
+}
+
+/// The first `max_chars` characters of `text`, and whether anything was cut.
+fn truncate_chars(text: &str, max_chars: usize) -> (&str, bool) {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => (&text[..end], true),
+        None => (text, false),
+    }
+}
+
+/// Packs whole lines into chunks of at most `max_tokens` estimated tokens.
+/// A line that is longer on its own gets a chunk to itself.
+fn chunk_for_context(text: &str, max_tokens: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+
+    for line in text.lines() {
+        if !chunk.is_empty() && estimate_token_count(&format!("{}\n{}", chunk, line)) > max_tokens {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        if !chunk.is_empty() {
+            chunk.push('\n');
+        }
+        chunk.push_str(line);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+
+    fn api(path: &str, max_chars: usize) -> SummarizeApi {
+        SummarizeApi {
+            url: format!("{}{}", mockito::server_url(), path),
+            key: Some("secret".to_string()),
+            max_chars,
+        }
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("héllo", 2), ("hé", true));
+        assert_eq!(truncate_chars("héllo", 5), ("héllo", false));
+    }
+
+    #[test]
+    fn test_chunk_for_context() {
+        let text = "one two three\nfour five six\nseven eight nine";
+        assert_eq!(chunk_for_context(text, 100), vec![text]);
+
+        let chunks = chunk_for_context(text, 5);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.join("\n"), text);
+        assert!(chunk_for_context("", 5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_summarize() {
+        let service = mock("POST", "/summarize")
+            .match_header("authorization", "Bearer secret")
+            .match_body(Matcher::Json(serde_json::json!({ "text": "hello\nworld" })))
+            .with_status(200)
+            .with_body(r#"{"summary": "A greeting."}"#)
+            .create();
+
+        let (summary, truncated) = api("/summarize", 100)
+            .summarize(&Client::new(), "hello\nworld")
+            .await
+            .unwrap();
+
+        assert_eq!(summary, "A greeting.");
+        assert!(!truncated);
+        service.assert();
+    }
+
+    #[tokio::test]
+    async fn test_summarize_truncates_long_transcripts() {
+        let _service = mock("POST", "/summarize-short")
+            .match_body(Matcher::Json(serde_json::json!({ "text": "hello" })))
+            .with_status(200)
+            .with_body(r#"{"summary": "A greeting."}"#)
+            .create();
+
+        let (_, truncated) = api("/summarize-short", 5)
+            .summarize(&Client::new(), "hello\nworld")
+            .await
+            .unwrap();
+
+        assert!(truncated);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_reports_service_errors() {
+        let _service = mock("POST", "/summarize-down").with_status(502).create();
+
+        let result = api("/summarize-down", 100)
+            .summarize(&Client::new(), "hello")
+            .await;
+
+        assert!(result.is_err());
+    }
+}
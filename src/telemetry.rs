@@ -0,0 +1,115 @@
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use std::env;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Name the bot's spans are reported under.
+const SERVICE_NAME: &str = "tofuboi";
+
+/// Exports the bot's tracing spans over OTLP/HTTP while it runs.
+pub struct Telemetry {
+    provider: TracerProvider,
+}
+
+impl Telemetry {
+    /// Starts exporting when `OTEL_EXPORTER_OTLP_ENDPOINT` is set, e.g. to
+    /// `http://localhost:4318` for Jaeger. Plain logging is unaffected.
+    pub fn from_env() -> Option<Self> {
+        let endpoint = env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.trim().is_empty())?;
+
+        match Self::start(&endpoint) {
+            Ok(telemetry) => {
+                log::info!("Exporting traces to {}", endpoint);
+                Some(telemetry)
+            }
+            Err(e) => {
+                log::error!("Trace export disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    fn start(endpoint: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let provider = build_provider(endpoint)?;
+        global::set_tracer_provider(provider.clone());
+
+        // Only the bot's own spans: exporting the HTTP client's would trace
+        // every export request too
+        let otel = tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(SERVICE_NAME))
+            .with_filter(Targets::new().with_target(SERVICE_NAME, LevelFilter::INFO));
+        // Events from `tracing` stop going to `log` once a subscriber is set
+        let fmt = tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO);
+        tracing_subscriber::registry()
+            .with(otel)
+            .with(fmt)
+            .try_init()?;
+
+        Ok(Self { provider })
+    }
+
+    /// Exports the spans still queued. Call before the process exits.
+    pub fn shutdown(self) {
+        // The tracing layer holds its own reference to the provider, so
+        // dropping the global one alone would not flush it
+        if let Err(e) = self.provider.shutdown() {
+            log::warn!("Failed to export remaining spans: {}", e);
+        }
+        global::shutdown_tracer_provider();
+    }
+}
+
+/// Batches spans to `<endpoint>/v1/traces`, labelled with the service name
+/// and version.
+fn build_provider(endpoint: &str) -> Result<TracerProvider, TraceError> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([
+            KeyValue::new("service.name", SERVICE_NAME),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ]))
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::mock;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_spans_are_sent_to_collector() {
+        let collector = mock("POST", "/v1/traces")
+            .match_header("content-type", "application/x-protobuf")
+            .with_status(200)
+            .expect_at_least(1)
+            .create();
+
+        let provider = build_provider(&mockito::server_url()).unwrap();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME));
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("transcript_request", video_id = "abc", lang = "en")
+                .in_scope(|| tracing::info!("fetched"));
+        });
+
+        // Shutting down blocks until the batch has been exported
+        tokio::task::spawn_blocking(move || provider.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+        collector.assert();
+    }
+}
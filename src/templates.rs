@@ -0,0 +1,338 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use teloxide::types::ParseMode;
+use teloxide::utils::{html, markdown};
+
+/// Formatting applied to one kind of reply.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplyFormat {
+    /// Plain text, sent without a parse mode
+    #[default]
+    Plain,
+    Html,
+    MarkdownV2,
+}
+
+impl ReplyFormat {
+    fn from_env(name: &str) -> Self {
+        env::var(name)
+            .ok()
+            .and_then(|v| Self::parse(&v))
+            .unwrap_or_default()
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "plain" => Some(ReplyFormat::Plain),
+            "html" => Some(ReplyFormat::Html),
+            "markdownv2" | "markdown" => Some(ReplyFormat::MarkdownV2),
+            _ => None,
+        }
+    }
+
+    fn parse_mode(self) -> Option<ParseMode> {
+        match self {
+            ReplyFormat::Plain => None,
+            ReplyFormat::Html => Some(ParseMode::Html),
+            ReplyFormat::MarkdownV2 => Some(ParseMode::MarkdownV2),
+        }
+    }
+
+    /// Escapes user-supplied text for this format
+    fn escape(self, text: &str) -> String {
+        match self {
+            ReplyFormat::Plain => text.to_string(),
+            ReplyFormat::Html => html::escape(text),
+            ReplyFormat::MarkdownV2 => markdown::escape(text),
+        }
+    }
+}
+
+/// A rendered reply ready to send.
+#[derive(Debug, PartialEq)]
+pub struct Reply {
+    pub text: String,
+    pub parse_mode: Option<ParseMode>,
+}
+
+/// Per reply type formatting chosen by the operator.
+#[derive(Debug, Clone, Default)]
+pub struct MessageTemplates {
+    /// Link to the uploaded transcript (`PARSE_MODE_SUCCESS`)
+    pub success: ReplyFormat,
+    /// Error replies (`PARSE_MODE_ERROR`)
+    pub error: ReplyFormat,
+    /// Informational notes such as language fallback (`PARSE_MODE_INFO`)
+    pub info: ReplyFormat,
+    /// Transcript statistics (`PARSE_MODE_STATS`)
+    pub stats: ReplyFormat,
+}
+
+impl MessageTemplates {
+    pub fn from_env() -> Self {
+        Self {
+            success: ReplyFormat::from_env("PARSE_MODE_SUCCESS"),
+            error: ReplyFormat::from_env("PARSE_MODE_ERROR"),
+            info: ReplyFormat::from_env("PARSE_MODE_INFO"),
+            stats: ReplyFormat::from_env("PARSE_MODE_STATS"),
+        }
+    }
+
+    /// Reads formats from a TOML file such as `success = "html"`. Reply types
+    /// the file leaves out keep their `PARSE_MODE_*` setting.
+    pub fn from_toml(contents: &str) -> Result<Self, String> {
+        let table: toml::Table = contents.parse().map_err(|e| format!("{}", e))?;
+        let mut templates = Self::from_env();
+
+        for (key, value) in &table {
+            let format = value
+                .as_str()
+                .and_then(ReplyFormat::parse)
+                .ok_or_else(|| format!("invalid format for '{}': {}", key, value))?;
+            match key.as_str() {
+                "success" => templates.success = format,
+                "error" => templates.error = format,
+                "info" => templates.info = format,
+                "stats" => templates.stats = format,
+                _ => return Err(format!("unknown reply type '{}'", key)),
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Loads the file at `path`, falling back to the environment when it
+    /// cannot be read or parsed.
+    pub fn from_file_or_env(path: &Path) -> Self {
+        match fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| Self::from_toml(&contents))
+        {
+            Ok(templates) => templates,
+            Err(e) => {
+                log::warn!("Ignoring templates in {}: {}", path.display(), e);
+                Self::from_env()
+            }
+        }
+    }
+
+    pub fn success(&self, url: &str) -> Reply {
+        self.link("Transcript available at:", "View transcript", url)
+    }
+
+    /// Like [`Self::success`], for a gzip+base64 encoded paste
+    pub fn compressed_success(&self, url: &str) -> Reply {
+        self.link(
+            "Compressed transcript available at:",
+            "View compressed transcript",
+            url,
+        )
+    }
+
+    /// Puts `note` above a success reply, escaped for the same format
+    pub fn with_note(&self, note: &str, reply: Reply) -> Reply {
+        Reply {
+            text: format!("{}\n\n{}", self.success.escape(note), reply.text),
+            parse_mode: reply.parse_mode,
+        }
+    }
+
+    fn link(&self, plain_prefix: &str, label: &str, url: &str) -> Reply {
+        let text = match self.success {
+            ReplyFormat::Plain => format!("{} {}", plain_prefix, url),
+            ReplyFormat::Html => format!("<a href=\"{}\">{}</a>", html::escape(url), label),
+            ReplyFormat::MarkdownV2 => {
+                format!("[{}]({})", label, markdown::escape_link_url(url))
+            }
+        };
+        Reply {
+            text,
+            parse_mode: self.success.parse_mode(),
+        }
+    }
+
+    /// Renders an error; `message` may contain user input and is always escaped
+    pub fn error(&self, message: &str) -> Reply {
+        let escaped = self.error.escape(message);
+        let text = match self.error {
+            ReplyFormat::Plain => escaped,
+            ReplyFormat::Html => format!("<b>Error:</b> {}", escaped),
+            ReplyFormat::MarkdownV2 => format!("*Error:* {}", escaped),
+        };
+        Reply {
+            text,
+            parse_mode: self.error.parse_mode(),
+        }
+    }
+
+    pub fn info(&self, message: &str) -> Reply {
+        Reply {
+            text: self.info.escape(message),
+            parse_mode: self.info.parse_mode(),
+        }
+    }
+
+    pub fn stats(&self, message: &str) -> Reply {
+        Reply {
+            text: self.stats.escape(message),
+            parse_mode: self.stats.parse_mode(),
+        }
+    }
+}
+
+/// Templates shared with a [`watch`]er that swaps them on file changes.
+pub type SharedTemplates = Arc<RwLock<MessageTemplates>>;
+
+/// Replaces `shared` with the templates in `contents`. Returns false and
+/// keeps the current templates when `contents` does not parse.
+pub fn reload(shared: &SharedTemplates, contents: &str) -> bool {
+    match MessageTemplates::from_toml(contents) {
+        Ok(templates) => {
+            *shared.write().unwrap() = templates;
+            true
+        }
+        Err(e) => {
+            log::warn!("Keeping previous templates: {}", e);
+            false
+        }
+    }
+}
+
+/// Reloads `shared` whenever the file at `path` changes. The watcher stops
+/// when the returned handle is dropped.
+pub fn watch(path: PathBuf, shared: SharedTemplates) -> notify::Result<RecommendedWatcher> {
+    // Editors often save by replacing the file, which a watch on the file
+    // itself would not survive, so watch its directory instead
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    let file_name = path.file_name().map(|name| name.to_os_string());
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        let touches_file = event
+            .paths
+            .iter()
+            .any(|changed| changed.file_name() == file_name.as_deref());
+        if !touches_file || !(event.kind.is_modify() || event.kind.is_create()) {
+            return;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                if reload(&shared, &contents) {
+                    log::info!("Reloaded message templates from {}", path.display());
+                }
+            }
+            Err(e) => log::warn!("Could not read {}: {}", path.display(), e),
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html_templates() -> MessageTemplates {
+        MessageTemplates {
+            success: ReplyFormat::Html,
+            error: ReplyFormat::Html,
+            info: ReplyFormat::Html,
+            stats: ReplyFormat::Html,
+        }
+    }
+
+    #[test]
+    fn test_plain_templates_are_unchanged() {
+        let templates = MessageTemplates::default();
+
+        assert_eq!(
+            templates.success("https://pastebin.com/raw/abc"),
+            Reply {
+                text: "Transcript available at: https://pastebin.com/raw/abc".to_string(),
+                parse_mode: None,
+            }
+        );
+        assert_eq!(templates.error("Error: <b>").text, "Error: <b>");
+        assert_eq!(
+            templates
+                .compressed_success("https://pastebin.com/raw/abc")
+                .text,
+            "Compressed transcript available at: https://pastebin.com/raw/abc"
+        );
+    }
+
+    #[test]
+    fn test_html_success_link() {
+        let reply = html_templates().success("https://pastebin.com/raw/abc");
+
+        assert_eq!(
+            reply.text,
+            "<a href=\"https://pastebin.com/raw/abc\">View transcript</a>"
+        );
+        assert_eq!(reply.parse_mode, Some(ParseMode::Html));
+    }
+
+    #[test]
+    fn test_note_is_escaped_above_link() {
+        let templates = html_templates();
+        let reply = templates.with_note("Using 'en' <fallback>", templates.success("https://x/y"));
+
+        assert_eq!(
+            reply.text,
+            "Using 'en' &lt;fallback&gt;\n\n<a href=\"https://x/y\">View transcript</a>"
+        );
+        assert_eq!(reply.parse_mode, Some(ParseMode::Html));
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let templates =
+            MessageTemplates::from_toml("success = \"html\"\nerror = \"markdownv2\"").unwrap();
+        assert_eq!(templates.success, ReplyFormat::Html);
+        assert_eq!(templates.error, ReplyFormat::MarkdownV2);
+
+        assert!(MessageTemplates::from_toml("success = \"bold\"").is_err());
+        assert!(MessageTemplates::from_toml("footer = \"html\"").is_err());
+        assert!(MessageTemplates::from_toml("success = ").is_err());
+    }
+
+    #[test]
+    fn test_reload_updates_shared_templates() {
+        let shared: SharedTemplates = Arc::default();
+        let reader = shared.clone();
+
+        assert!(reload(&shared, "info = \"html\""));
+        assert_eq!(reader.read().unwrap().info, ReplyFormat::Html);
+
+        // A broken file leaves the last good templates in place
+        assert!(!reload(&shared, "info = "));
+        assert_eq!(reader.read().unwrap().info, ReplyFormat::Html);
+    }
+
+    #[test]
+    fn test_html_error_escapes_video_id() {
+        let reply = html_templates()
+            .error("Error fetching transcript: <script>alert(1)</script> is unavailable");
+
+        assert!(reply.text.starts_with("<b>Error:</b> "));
+        assert!(!reply.text.contains("<script>"));
+        assert!(reply.text.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    }
+
+    #[test]
+    fn test_markdown_error_escapes_specials() {
+        let templates = MessageTemplates {
+            error: ReplyFormat::MarkdownV2,
+            ..MessageTemplates::default()
+        };
+
+        assert_eq!(templates.error("bad_id.").text, "*Error:* bad\\_id\\.");
+    }
+}
@@ -1,51 +1,604 @@
-use ytranscript::{TranscriptConfig, YoutubeTranscript, YoutubeTranscriptError};
+use crate::formatter::normalize_text;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use ytranscript::{
+    TranscriptConfig, TranscriptResponse, YoutubeTranscript, YoutubeTranscriptError,
+};
+
+/// Source of raw transcripts. Handlers are generic over this so tests can
+/// swap in [`MockTranscriptFetcher`] instead of calling YouTube.
+pub trait TranscriptFetcher: Send + Sync + 'static {
+    /// Fetches the transcript with a ready-made `ytranscript` config, for
+    /// callers that need settings beyond the language
+    fn fetch_with_config(
+        &self,
+        video_id: &str,
+        config: TranscriptConfig,
+    ) -> impl Future<Output = Result<Vec<TranscriptResponse>, YoutubeTranscriptError>> + Send;
+
+    /// Fetches the transcript in exactly `lang`, without any fallback
+    fn fetch(
+        &self,
+        video_id: &str,
+        lang: &str,
+    ) -> impl Future<Output = Result<Vec<TranscriptResponse>, YoutubeTranscriptError>> + Send {
+        self.fetch_with_config(
+            video_id,
+            TranscriptConfig {
+                lang: Some(lang.to_string()),
+            },
+        )
+    }
+
+    /// Lists the language codes that have captions for the video
+    fn list_languages(
+        &self,
+        video_id: &str,
+    ) -> impl Future<Output = Result<Vec<String>, YoutubeTranscriptError>> + Send;
+
+    /// Forgets any stored copy of the transcript, for fetchers that keep one
+    fn evict(&self, _video_id: &str, _lang: &str) {}
+
+    /// The stored copy of the transcript, without fetching it
+    fn cached(&self, _video_id: &str, _lang: &str) -> Option<Vec<TranscriptResponse>> {
+        None
+    }
+
+    /// How many transcripts are stored, for fetchers that keep them
+    fn cache_len(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Fetches transcripts from YouTube through `ytranscript`.
+pub struct YoutubeTranscriptFetcher;
+
+impl YoutubeTranscriptFetcher {
+    /// The one place YouTube request settings are applied. ytranscript 0.1
+    /// builds its own HTTP client and takes no headers, so a configured user
+    /// agent cannot be sent yet; wire it into the request here once it can.
+    pub fn new(user_agent: Option<&str>) -> Self {
+        if let Some(user_agent) = user_agent {
+            log::warn!(
+                "YT_USER_AGENT={:?} is not applied: ytranscript does not accept custom headers",
+                user_agent
+            );
+        }
+        YoutubeTranscriptFetcher
+    }
+}
+
+impl TranscriptFetcher for YoutubeTranscriptFetcher {
+    async fn fetch_with_config(
+        &self,
+        video_id: &str,
+        config: TranscriptConfig,
+    ) -> Result<Vec<TranscriptResponse>, YoutubeTranscriptError> {
+        YoutubeTranscript::fetch_transcript(video_id, Some(config)).await
+    }
+
+    async fn list_languages(&self, video_id: &str) -> Result<Vec<String>, YoutubeTranscriptError> {
+        // ytranscript has no listing call, but asking for a language no track
+        // uses makes it report every available one
+        match self.fetch(video_id, "__tofuboi_list__").await {
+            Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(_, available, _)) => {
+                Ok(available)
+            }
+            Err(e) => Err(e),
+            Ok(_) => Ok(Vec::new()),
+        }
+    }
+}
 
 pub struct TranscriptService;
 
+/// Fallback languages [`TranscriptService::fetch`] tries after the
+/// requested one is missing, before giving up.
+pub const MAX_LANG_RETRIES: usize = 2;
+
+/// Why [`TranscriptService::fetch`] could not produce a transcript.
+#[derive(Debug)]
+pub enum FetchError {
+    Transcript(YoutubeTranscriptError),
+    /// The requested language was missing and every fallback failed too;
+    /// `source` is the error from the last one tried
+    FallbackFailed {
+        requested: String,
+        tried: Vec<String>,
+        source: YoutubeTranscriptError,
+    },
+    /// The requested language was missing and fallbacks were turned off
+    LanguageUnavailable {
+        requested: String,
+        available: Vec<String>,
+    },
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Transcript(e) => write!(f, "{}", e),
+            FetchError::FallbackFailed {
+                requested,
+                tried,
+                source,
+            } => write!(
+                f,
+                "Requested language '{}' unavailable; tried fallback {} which also failed: {}",
+                requested,
+                tried
+                    .iter()
+                    .map(|lang| format!("'{}'", lang))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                source
+            ),
+            FetchError::LanguageUnavailable {
+                requested,
+                available,
+            } if available.is_empty() => {
+                write!(f, "Requested language '{}' is not available", requested)
+            }
+            FetchError::LanguageUnavailable {
+                requested,
+                available,
+            } => write!(
+                f,
+                "Requested language '{}' is not available. Available languages: {}",
+                requested,
+                available.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl FetchError {
+    /// True when YouTube refused the request for being too frequent
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(
+            self,
+            FetchError::Transcript(YoutubeTranscriptError::TooManyRequests)
+                | FetchError::FallbackFailed {
+                    source: YoutubeTranscriptError::TooManyRequests,
+                    ..
+                }
+        )
+    }
+}
+
+/// How often and after how long a rate-limited fetch is tried again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (`YOUTUBE_MAX_RETRIES`)
+    pub max_retries: u32,
+    /// Wait before each retry (`YOUTUBE_RETRY_DELAY_SECS`)
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl From<YoutubeTranscriptError> for FetchError {
+    fn from(e: YoutubeTranscriptError) -> Self {
+        FetchError::Transcript(e)
+    }
+}
+
+/// A fetched transcript together with the language it was actually fetched in.
+#[derive(Clone)]
+pub struct FetchedTranscript {
+    pub entries: Vec<TranscriptResponse>,
+    pub lang: String,
+    /// Set when a fallback language had to be used
+    pub info: Option<String>,
+    /// Every language the video has captions in, filled in with `info`
+    pub available_langs: Vec<String>,
+}
+
+impl FetchedTranscript {
+    /// Whether there is nothing to show: no captions, or only captions that
+    /// are empty once decoded and trimmed, as auto-captions sometimes are.
+    pub fn is_blank(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|entry| normalize_text(&entry.text).trim().is_empty())
+    }
+
+    /// Names the track used, for confirming it when no fallback was needed.
+    pub fn resolved_note(&self) -> String {
+        format!("Transcript in {}", self.lang)
+    }
+}
+
+/// Largest start time difference, in seconds, at which two captions in
+/// different languages are treated as the same line.
+const BILINGUAL_TOLERANCE_SECS: f64 = 0.5;
+
+/// One line of a bilingual transcript. A text is empty when that language
+/// has no caption near `start`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BilingualEntry {
+    /// Start of the caption in seconds
+    pub start: f64,
+    pub text1: String,
+    pub text2: String,
+}
+
 impl TranscriptService {
-    pub async fn fetch(
+    /// Fetches `lang1` and `lang2` concurrently and pairs up their captions,
+    /// once `filter` has cleaned up each language's own with its code.
+    /// Both languages must exist; there is no fallback here.
+    pub async fn fetch_bilingual<T, F>(
+        fetcher: &T,
+        video_id: &str,
+        lang1: &str,
+        lang2: &str,
+        filter: F,
+    ) -> Result<Vec<BilingualEntry>, YoutubeTranscriptError>
+    where
+        T: TranscriptFetcher,
+        F: Fn(&mut Vec<TranscriptResponse>, &str),
+    {
+        let (first, second) = tokio::join!(
+            fetcher.fetch(video_id, lang1),
+            fetcher.fetch(video_id, lang2)
+        );
+        let (mut first, mut second) = (first?, second?);
+        sort_by_offset(&mut first);
+        sort_by_offset(&mut second);
+        filter(&mut first, lang1);
+        filter(&mut second, lang2);
+        Ok(align_bilingual(&first, &second))
+    }
+
+    /// Like [`Self::fetch`], but waits and tries again when YouTube
+    /// rate-limits the request. `on_retry` runs before each wait with its
+    /// length, so the user can be told what is going on. With `exact` a
+    /// missing language is an error rather than a reason to fall back.
+    pub async fn fetch_with_retry<T, F, Fut>(
+        fetcher: &T,
         video_id: &str,
         lang: &str,
-    ) -> Result<(Vec<ytranscript::TranscriptResponse>, Option<String>), YoutubeTranscriptError>
+        exact: bool,
+        policy: RetryPolicy,
+        mut on_retry: F,
+    ) -> Result<FetchedTranscript, FetchError>
+    where
+        T: TranscriptFetcher,
+        F: FnMut(Duration) -> Fut,
+        Fut: Future<Output = ()>,
     {
-        let config = TranscriptConfig {
-            lang: Some(lang.to_string()),
-        };
+        let max_lang_retries = if exact { 0 } else { MAX_LANG_RETRIES };
+        let mut retries = 0;
+        loop {
+            match Self::fetch_with_lang_retries(fetcher, video_id, lang, max_lang_retries).await {
+                Err(e) if e.is_rate_limited() && retries < policy.max_retries => {
+                    retries += 1;
+                    log::info!(
+                        "Rate limited fetching {}, retry {}/{} in {:?}",
+                        video_id,
+                        retries,
+                        policy.max_retries,
+                        policy.delay
+                    );
+                    on_retry(policy.delay).await;
+                    tokio::time::sleep(policy.delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
 
-        match YoutubeTranscript::fetch_transcript(video_id, Some(config)).await {
-            Ok(transcript) => Ok((transcript, None)),
+    /// Fetches once with a ready-made `ytranscript` config, for callers
+    /// that need settings beyond the language; every language-based fetch
+    /// ends up here. There is no fallback or retry. The entries come back
+    /// in time order.
+    pub async fn fetch_with_config<T: TranscriptFetcher>(
+        fetcher: &T,
+        video_id: &str,
+        config: TranscriptConfig,
+    ) -> Result<Vec<TranscriptResponse>, YoutubeTranscriptError> {
+        let mut entries = fetcher.fetch_with_config(video_id, config).await?;
+        sort_by_offset(&mut entries);
+        Ok(entries)
+    }
+
+    pub async fn fetch<T: TranscriptFetcher>(
+        fetcher: &T,
+        video_id: &str,
+        lang: &str,
+    ) -> Result<FetchedTranscript, FetchError> {
+        Self::fetch_with_lang_retries(fetcher, video_id, lang, MAX_LANG_RETRIES).await
+    }
+
+    /// Like [`Self::fetch`], but tries at most `max_lang_retries` fallback
+    /// languages when the requested one is missing. With none allowed the
+    /// error lists the languages the video does have.
+    pub async fn fetch_with_lang_retries<T: TranscriptFetcher>(
+        fetcher: &T,
+        video_id: &str,
+        lang: &str,
+        max_lang_retries: usize,
+    ) -> Result<FetchedTranscript, FetchError> {
+        match Self::fetch_lang(fetcher, video_id, lang).await {
+            Ok(entries) => Ok(FetchedTranscript {
+                entries,
+                lang: lang.to_string(),
+                info: None,
+                available_langs: Vec::new(),
+            }),
+            Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(_, available, _))
+                if max_lang_retries == 0 =>
+            {
+                Err(FetchError::LanguageUnavailable {
+                    requested: lang.to_string(),
+                    available,
+                })
+            }
             Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(
                 _,
                 available_langs,
                 video,
             )) => {
-                let fallback_lang =
-                    Self::select_fallback_language(&available_langs, &["en", "zh-HK", "zh-TW"]);
-                let new_config = TranscriptConfig {
-                    lang: Some(fallback_lang.clone()),
-                };
-                let transcript =
-                    YoutubeTranscript::fetch_transcript(&video, Some(new_config)).await?;
-                Ok((transcript, Some(format!("Requested language '{}' not available. Using fallback language '{}'. Available languages: {}", 
-                    lang, fallback_lang, available_langs.join(", ")))))
+                let candidates =
+                    Self::fallback_candidates(&available_langs, &["en", "zh-HK", "zh-TW"]);
+                let mut tried = Vec::new();
+                let mut last_error = None;
+                for (attempt, fallback_lang) in
+                    candidates.into_iter().take(max_lang_retries).enumerate()
+                {
+                    log::debug!(
+                        "Retry {}/{} for {}: trying '{}'",
+                        attempt + 1,
+                        max_lang_retries,
+                        video,
+                        fallback_lang
+                    );
+                    match Self::fetch_lang(fetcher, &video, &fallback_lang).await {
+                        Ok(entries) => {
+                            return Ok(FetchedTranscript {
+                                entries,
+                                info: Some(format!(
+                                    "Requested language '{}' not available. Using fallback language '{}'.",
+                                    lang, fallback_lang
+                                )),
+                                lang: fallback_lang,
+                                available_langs,
+                            })
+                        }
+                        Err(e) => {
+                            log::debug!("Fallback '{}' for {} failed: {}", fallback_lang, video, e);
+                            tried.push(fallback_lang);
+                            // Further attempts would only be throttled as well
+                            let throttled = matches!(e, YoutubeTranscriptError::TooManyRequests);
+                            last_error = Some(e);
+                            if throttled {
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(FetchError::FallbackFailed {
+                    requested: lang.to_string(),
+                    tried,
+                    // At least one fallback is always tried
+                    source: last_error.expect("a fallback language was tried"),
+                })
             }
-            Err(e) => Err(e),
+            Err(e) => Err(e.into()),
         }
     }
 
-    fn select_fallback_language(available_langs: &[String], preferred: &[&str]) -> String {
-        for &lang in preferred {
-            if available_langs.contains(&lang.to_string()) {
-                return lang.to_string();
+    /// Fetches exactly `lang`.
+    async fn fetch_lang<T: TranscriptFetcher>(
+        fetcher: &T,
+        video_id: &str,
+        lang: &str,
+    ) -> Result<Vec<TranscriptResponse>, YoutubeTranscriptError> {
+        let config = TranscriptConfig {
+            lang: Some(lang.to_string()),
+        };
+        Self::fetch_with_config(fetcher, video_id, config).await
+    }
+
+    /// Fallback languages in the order they should be tried: preferred ones,
+    /// then any Chinese variant, then the rest of the available languages.
+    fn fallback_candidates(available_langs: &[String], preferred: &[&str]) -> Vec<String> {
+        let mut candidates: Vec<String> = preferred
+            .iter()
+            .filter(|&&lang| available_langs.iter().any(|l| l == lang))
+            .map(|lang| lang.to_string())
+            .collect();
+        let chinese = available_langs.iter().filter(|l| l.starts_with("zh"));
+        for lang in chinese.chain(available_langs) {
+            if !candidates.contains(lang) {
+                candidates.push(lang.clone());
             }
         }
-        if let Some(lang) = available_langs.iter().find(|l| l.starts_with("zh")) {
-            return lang.clone();
+        if candidates.is_empty() {
+            candidates.push("en".to_string());
+        }
+        candidates
+    }
+}
+
+/// Puts entries in order of their start, so subtitle cues never go back in
+/// time even if YouTube sends them shuffled. The sort is stable, keeping
+/// entries that start together in the order they arrived.
+pub fn sort_by_offset(entries: &mut [TranscriptResponse]) {
+    entries.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+}
+
+/// Drops entries whose decoded text repeats the entry right before them, a
+/// YouTube auto-caption bug. Repeats further apart are kept.
+pub fn deduplicate_consecutive(transcript: Vec<TranscriptResponse>) -> Vec<TranscriptResponse> {
+    let mut previous: Option<String> = None;
+    transcript
+        .into_iter()
+        .filter(|entry| {
+            let text = normalize_text(&entry.text);
+            let repeated = previous.as_deref() == Some(text.as_str());
+            previous = Some(text);
+            !repeated
+        })
+        .collect()
+}
+
+/// Pairs each caption in `first` with the nearest unused caption in `second`
+/// that starts within [`BILINGUAL_TOLERANCE_SECS`]. Captions without a
+/// partner are kept with the other text left empty.
+fn align_bilingual(
+    first: &[TranscriptResponse],
+    second: &[TranscriptResponse],
+) -> Vec<BilingualEntry> {
+    let mut entries = Vec::with_capacity(first.len().max(second.len()));
+    let mut next = 0;
+
+    for entry in first {
+        // Captions of the second language too early to pair with this or
+        // any later caption have no partner
+        while next < second.len() && second[next].offset < entry.offset - BILINGUAL_TOLERANCE_SECS {
+            entries.push(BilingualEntry {
+                start: second[next].offset,
+                text1: String::new(),
+                text2: second[next].text.clone(),
+            });
+            next += 1;
+        }
+
+        let nearest = second[next..]
+            .iter()
+            .take_while(|other| other.offset <= entry.offset + BILINGUAL_TOLERANCE_SECS)
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                (a.offset - entry.offset)
+                    .abs()
+                    .total_cmp(&(b.offset - entry.offset).abs())
+            })
+            .map(|(i, _)| next + i);
+
+        let text2 = match nearest {
+            Some(matched) => {
+                // Anything skipped over on the way is unpaired
+                for skipped in &second[next..matched] {
+                    entries.push(BilingualEntry {
+                        start: skipped.offset,
+                        text1: String::new(),
+                        text2: skipped.text.clone(),
+                    });
+                }
+                next = matched + 1;
+                second[matched].text.clone()
+            }
+            None => String::new(),
+        };
+        entries.push(BilingualEntry {
+            start: entry.offset,
+            text1: entry.text.clone(),
+            text2,
+        });
+    }
+
+    entries.extend(second[next..].iter().map(|other| BilingualEntry {
+        start: other.offset,
+        text1: String::new(),
+        text2: other.text.clone(),
+    }));
+    entries.sort_by(|a, b| a.start.total_cmp(&b.start));
+    entries
+}
+
+/// Test fetcher that replays queued results in order and records every call.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockTranscriptFetcher {
+    responses: std::sync::Mutex<
+        std::collections::VecDeque<Result<Vec<TranscriptResponse>, YoutubeTranscriptError>>,
+    >,
+    languages: Vec<String>,
+    calls: std::sync::Mutex<Vec<(String, String)>>,
+    delay: Option<Duration>,
+}
+
+#[cfg(test)]
+impl MockTranscriptFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the result returned by the next `fetch` call
+    pub fn with_response(
+        self,
+        response: Result<Vec<TranscriptResponse>, YoutubeTranscriptError>,
+    ) -> Self {
+        self.responses.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_languages(mut self, languages: &[&str]) -> Self {
+        self.languages = languages.iter().map(|l| l.to_string()).collect();
+        self
+    }
+
+    /// Makes every `fetch` sleep this long before answering
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Returns the `(video_id, lang)` pairs passed to `fetch` so far
+    pub fn calls(&self) -> Vec<(String, String)> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl TranscriptFetcher for MockTranscriptFetcher {
+    async fn fetch_with_config(
+        &self,
+        video_id: &str,
+        config: TranscriptConfig,
+    ) -> Result<Vec<TranscriptResponse>, YoutubeTranscriptError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((video_id.to_string(), config.lang.unwrap_or_default()));
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
         }
-        available_langs
-            .first()
-            .cloned()
-            .unwrap_or_else(|| "en".to_string())
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| {
+                Err(YoutubeTranscriptError::TranscriptNotAvailable(
+                    video_id.to_string(),
+                ))
+            })
+    }
+
+    async fn list_languages(&self, _video_id: &str) -> Result<Vec<String>, YoutubeTranscriptError> {
+        Ok(self.languages.clone())
+    }
+}
+
+/// Builds a transcript entry for tests; offsets and durations are in seconds.
+#[cfg(test)]
+pub fn test_entry(text: &str, offset: f64, duration: f64) -> TranscriptResponse {
+    TranscriptResponse {
+        text: text.to_string(),
+        duration,
+        offset,
+        lang: "en".to_string(),
     }
 }
 
@@ -53,24 +606,444 @@ impl TranscriptService {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_exact_request_does_not_fall_back() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(
+                "fr".to_string(),
+                vec!["de".to_string(), "en".to_string()],
+                "abc123".to_string(),
+            )))
+            .with_response(Ok(vec![test_entry("hello", 0.0, 1.0)]));
+
+        let err = TranscriptService::fetch_with_retry(
+            &fetcher,
+            "abc123",
+            "fr",
+            true,
+            RetryPolicy::default(),
+            |_| async {},
+        )
+        .await
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "Requested language 'fr' is not available. Available languages: de, en"
+        );
+        // Nothing but the requested language was fetched
+        assert_eq!(fetcher.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_sorted_by_offset() {
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![
+            test_entry("third", 5.0, 1.0),
+            test_entry("first", 0.0, 1.0),
+            test_entry("second a", 2.0, 1.0),
+            test_entry("second b", 2.0, 1.0),
+        ]));
+
+        let fetched = TranscriptService::fetch(&fetcher, "abc123", "en")
+            .await
+            .unwrap();
+
+        let srt = crate::export::subtitles::format_srt(&fetched.entries);
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:01,000\nfirst\n\n\
+             2\n00:00:02,000 --> 00:00:03,000\nsecond a\n\n\
+             3\n00:00:02,000 --> 00:00:03,000\nsecond b\n\n\
+             4\n00:00:05,000 --> 00:00:06,000\nthird\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_whitespace_only_transcript_is_blank() {
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![
+            test_entry("", 0.0, 0.0),
+            test_entry("  \n ", 1.0, 0.0),
+        ]));
+        let fetched = TranscriptService::fetch(&fetcher, "abc123", "en")
+            .await
+            .unwrap();
+        assert!(fetched.is_blank());
+
+        let fetcher = MockTranscriptFetcher::new().with_response(Ok(vec![
+            test_entry("", 0.0, 0.0),
+            test_entry("hello", 1.0, 1.0),
+        ]));
+        let fetched = TranscriptService::fetch(&fetcher, "abc123", "en")
+            .await
+            .unwrap();
+        assert!(!fetched.is_blank());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_falls_back_to_available_language() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(
+                "fr".to_string(),
+                vec!["de".to_string(), "en".to_string()],
+                "abc123".to_string(),
+            )))
+            .with_response(Ok(vec![test_entry("hello", 0.0, 1.0)]));
+
+        let fetched = TranscriptService::fetch(&fetcher, "abc123", "fr")
+            .await
+            .unwrap();
+
+        assert_eq!(fetched.lang, "en");
+        assert_eq!(fetched.entries.len(), 1);
+        assert_eq!(
+            fetched.info.as_deref(),
+            Some("Requested language 'fr' not available. Using fallback language 'en'.")
+        );
+        assert_eq!(fetched.available_langs, vec!["de", "en"]);
+        assert_eq!(
+            fetcher.calls(),
+            vec![
+                ("abc123".to_string(), "fr".to_string()),
+                ("abc123".to_string(), "en".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_reports_failed_fallback() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(
+                "fr".to_string(),
+                vec!["en".to_string()],
+                "abc123".to_string(),
+            )))
+            .with_response(Err(YoutubeTranscriptError::TooManyRequests));
+
+        let err = TranscriptService::fetch(&fetcher, "abc123", "fr")
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Requested language 'fr' unavailable; tried fallback 'en' which also failed: {}",
+                YoutubeTranscriptError::TooManyRequests
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_tries_next_fallback() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(
+                "fr".to_string(),
+                vec!["de".to_string(), "en".to_string()],
+                "abc123".to_string(),
+            )))
+            .with_response(Err(YoutubeTranscriptError::TranscriptNotAvailable(
+                "abc123".to_string(),
+            )))
+            .with_response(Ok(vec![test_entry("hallo", 0.0, 1.0)]));
+
+        let fetched = TranscriptService::fetch(&fetcher, "abc123", "fr")
+            .await
+            .unwrap();
+
+        assert_eq!(fetched.lang, "de");
+        assert!(fetched
+            .info
+            .unwrap()
+            .contains("Using fallback language 'de'"));
+        assert_eq!(
+            fetcher.calls(),
+            vec![
+                ("abc123".to_string(), "fr".to_string()),
+                ("abc123".to_string(), "en".to_string()),
+                ("abc123".to_string(), "de".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_reports_every_failed_fallback() {
+        let unavailable = || {
+            Err(YoutubeTranscriptError::TranscriptNotAvailable(
+                "abc123".to_string(),
+            ))
+        };
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(
+                "fr".to_string(),
+                vec!["de".to_string(), "en".to_string()],
+                "abc123".to_string(),
+            )))
+            .with_response(unavailable())
+            .with_response(unavailable());
+
+        let err = TranscriptService::fetch(&fetcher, "abc123", "fr")
+            .await
+            .err()
+            .unwrap();
+
+        assert!(err
+            .to_string()
+            .starts_with("Requested language 'fr' unavailable; tried fallback 'en', 'de' which"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_with_config() {
+        let fetcher =
+            MockTranscriptFetcher::new().with_response(Ok(vec![test_entry("hallo", 0.0, 1.0)]));
+
+        let config = TranscriptConfig {
+            lang: Some("de".to_string()),
+        };
+        let entries = TranscriptService::fetch_with_config(&fetcher, "abc123", config)
+            .await
+            .unwrap();
+
+        // The config's language is the one asked of the fetcher
+        assert_eq!(
+            fetcher.calls(),
+            vec![("abc123".to_string(), "de".to_string())]
+        );
+        assert_eq!(entries[0].text, "hallo");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stops_after_max_lang_retries() {
+        let missing = |available: &[&str]| {
+            Err(YoutubeTranscriptError::TranscriptNotAvailableLanguage(
+                "fr".to_string(),
+                available.iter().map(|l| l.to_string()).collect(),
+                "abc123".to_string(),
+            ))
+        };
+        let langs = ["de", "en", "es", "it"];
+        let fetcher = || {
+            MockTranscriptFetcher::new()
+                .with_response(missing(&langs))
+                .with_response(missing(&langs))
+                .with_response(missing(&langs))
+                .with_response(Ok(vec![test_entry("ciao", 0.0, 1.0)]))
+        };
+
+        // Three languages rejected, the fourth accepted
+        let accepting = fetcher();
+        let fetched = TranscriptService::fetch_with_lang_retries(&accepting, "abc123", "fr", 3)
+            .await
+            .unwrap();
+        assert_eq!(fetched.lang, "es");
+        assert_eq!(accepting.calls().len(), 4);
+
+        let limited = fetcher();
+        let err = TranscriptService::fetch(&limited, "abc123", "fr")
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("tried fallback 'en', 'de' which"));
+        assert_eq!(limited.calls().len(), 1 + MAX_LANG_RETRIES);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_retries_when_rate_limited() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TooManyRequests))
+            .with_response(Err(YoutubeTranscriptError::TooManyRequests))
+            .with_response(Ok(vec![test_entry("finally", 0.0, 1.0)]));
+        let policy = RetryPolicy {
+            max_retries: 2,
+            delay: Duration::from_millis(1),
+        };
+        let mut notices = 0;
+
+        let fetched =
+            TranscriptService::fetch_with_retry(&fetcher, "abc123", "en", false, policy, |_| {
+                notices += 1;
+                async {}
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(fetched.entries[0].text, "finally");
+        assert_eq!(fetcher.calls().len(), 3);
+        assert_eq!(notices, 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gives_up_after_max_retries() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TooManyRequests))
+            .with_response(Err(YoutubeTranscriptError::TooManyRequests));
+        let policy = RetryPolicy {
+            max_retries: 1,
+            delay: Duration::from_millis(1),
+        };
+
+        let err = TranscriptService::fetch_with_retry(
+            &fetcher,
+            "abc123",
+            "en",
+            false,
+            policy,
+            |_| async {},
+        )
+        .await
+        .err()
+        .unwrap();
+
+        assert!(err.is_rate_limited());
+        assert_eq!(fetcher.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_missing_captions_are_not_retried() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Err(YoutubeTranscriptError::TranscriptDisabled(
+                "abc123".to_string(),
+            )))
+            .with_response(Ok(vec![test_entry("hello", 0.0, 1.0)]));
+        assert!(TranscriptService::fetch(&fetcher, "abc123", "en")
+            .await
+            .is_err());
+        assert_eq!(fetcher.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_deduplicate_consecutive() {
+        let transcript = vec![
+            test_entry("we&#39;re live", 0.0, 1.0),
+            test_entry("we're live", 1.0, 1.0),
+            test_entry("we're live", 2.0, 1.0),
+            test_entry("hello", 3.0, 1.0),
+            test_entry("we're live", 4.0, 1.0),
+        ];
+
+        let offsets: Vec<f64> = deduplicate_consecutive(transcript)
+            .iter()
+            .map(|e| e.offset)
+            .collect();
+
+        assert_eq!(offsets, vec![0.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_deduplicate_keeps_distinct_entries() {
+        let transcript = vec![
+            test_entry("one", 0.0, 1.0),
+            test_entry("two", 1.0, 1.0),
+            test_entry("one", 2.0, 1.0),
+        ];
+
+        assert_eq!(deduplicate_consecutive(transcript).len(), 3);
+    }
+
+    #[test]
+    fn test_align_bilingual_within_tolerance() {
+        let first = vec![test_entry("Hello", 0.0, 2.0), test_entry("World", 2.0, 2.0)];
+        let second = vec![test_entry("Hola", 0.3, 2.0), test_entry("Mundo", 1.8, 2.0)];
+
+        let aligned = align_bilingual(&first, &second);
+
+        assert_eq!(
+            aligned,
+            vec![
+                BilingualEntry {
+                    start: 0.0,
+                    text1: "Hello".to_string(),
+                    text2: "Hola".to_string(),
+                },
+                BilingualEntry {
+                    start: 2.0,
+                    text1: "World".to_string(),
+                    text2: "Mundo".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_align_bilingual_keeps_unpaired_captions() {
+        let first = vec![test_entry("One", 0.0, 1.0), test_entry("Two", 5.0, 1.0)];
+        let second = vec![
+            test_entry("Uno", 0.1, 1.0),
+            test_entry("extra", 2.5, 1.0),
+            test_entry("Dos", 5.2, 1.0),
+            test_entry("Tres", 9.0, 1.0),
+        ];
+
+        let pairs: Vec<(String, String)> = align_bilingual(&first, &second)
+            .into_iter()
+            .map(|e| (e.text1, e.text2))
+            .collect();
+
+        assert_eq!(
+            pairs,
+            vec![
+                ("One".to_string(), "Uno".to_string()),
+                (String::new(), "extra".to_string()),
+                ("Two".to_string(), "Dos".to_string()),
+                (String::new(), "Tres".to_string()),
+            ]
+        );
+
+        // A caption too far from any other is kept on its own
+        let aligned = align_bilingual(&[test_entry("Solo", 3.0, 1.0)], &[]);
+        assert_eq!(aligned[0].text2, "");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_bilingual() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Ok(vec![test_entry("Hello", 0.0, 1.0)]))
+            .with_response(Ok(vec![test_entry("Hola", 0.2, 1.0)]));
+
+        let entries = TranscriptService::fetch_bilingual(&fetcher, "abc123", "en", "es", |_, _| {})
+            .await
+            .unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].text2, "Hola");
+        assert_eq!(fetcher.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_bilingual_filters_each_language() {
+        let fetcher = MockTranscriptFetcher::new()
+            .with_response(Ok(vec![test_entry("Hello", 0.0, 1.0)]))
+            .with_response(Ok(vec![test_entry("Hola", 0.2, 1.0)]));
+
+        let entries =
+            TranscriptService::fetch_bilingual(&fetcher, "abc123", "en", "es", |entries, lang| {
+                entries[0].text = format!("{} ({})", entries[0].text, lang)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(entries[0].text1, "Hello (en)");
+        assert_eq!(entries[0].text2, "Hola (es)");
+    }
+
     #[test]
-    fn test_select_fallback_language() {
-        let available = vec!["en".to_string(), "es".to_string(), "zh-HK".to_string()];
+    fn test_fallback_candidates() {
+        let available = vec!["es".to_string(), "en".to_string(), "zh-HK".to_string()];
         assert_eq!(
-            TranscriptService::select_fallback_language(&available, &["fr", "en", "es"]),
-            "en"
+            TranscriptService::fallback_candidates(&available, &["fr", "en", "es"]),
+            vec!["en", "es", "zh-HK"]
         );
 
-        let available = vec!["es".to_string(), "zh-HK".to_string()];
+        let available = vec!["es".to_string(), "zh-TW".to_string()];
         assert_eq!(
-            TranscriptService::select_fallback_language(&available, &["fr", "en", "zh-HK"]),
-            "zh-HK"
+            TranscriptService::fallback_candidates(&available, &["fr", "en"]),
+            vec!["zh-TW", "es"]
         );
 
         let available: Vec<String> = vec![];
         assert_eq!(
-            TranscriptService::select_fallback_language(&available, &["fr", "en", "es"]),
-            "en"
+            TranscriptService::fallback_candidates(&available, &["fr", "en", "es"]),
+            vec!["en"]
         );
     }
 }
@@ -0,0 +1,209 @@
+use super::{Translation, TranslationBackend, TranslationError};
+use reqwest::{Client, StatusCode};
+use std::env;
+use url::form_urlencoded;
+
+/// Endpoint for DeepL API Free keys; Pro accounts set `DEEPL_API_URL`.
+const DEEPL_FREE_URL: &str = "https://api-free.deepl.com/v2/translate";
+
+/// DeepL's status for an exhausted character quota.
+const QUOTA_EXCEEDED: u16 = 456;
+
+/// Target languages DeepL accepts, in its own spelling.
+const DEEPL_TARGETS: &[&str] = &[
+    "AR", "BG", "CS", "DA", "DE", "EL", "EN-GB", "EN-US", "ES", "ET", "FI", "FR", "HU", "ID", "IT",
+    "JA", "KO", "LT", "LV", "NB", "NL", "PL", "PT-BR", "PT-PT", "RO", "RU", "SK", "SL", "SV", "TR",
+    "UK", "ZH", "ZH-HANS", "ZH-HANT",
+];
+
+/// Translates through the DeepL API.
+#[derive(Debug, Clone)]
+pub struct DeepLBackend {
+    url: String,
+    auth_key: String,
+}
+
+impl DeepLBackend {
+    pub fn new(url: &str, auth_key: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            auth_key: auth_key.to_string(),
+        }
+    }
+
+    /// `None` unless `DEEPL_API_KEY` is set. Uses the Free endpoint unless
+    /// `DEEPL_API_URL` names another.
+    pub fn from_env() -> Option<Self> {
+        let auth_key = env::var("DEEPL_API_KEY")
+            .ok()
+            .filter(|key| !key.trim().is_empty())?;
+        let url = env::var("DEEPL_API_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+            .unwrap_or_else(|| DEEPL_FREE_URL.to_string());
+        Some(Self::new(&url, &auth_key))
+    }
+}
+
+impl TranslationBackend for DeepLBackend {
+    async fn translate(
+        &self,
+        client: &Client,
+        text: &str,
+        target_lang: &str,
+    ) -> Result<Translation, TranslationError> {
+        let target = to_deepl_lang(target_lang)
+            .ok_or_else(|| TranslationError::UnsupportedLanguage(target_lang.to_string()))?;
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("auth_key", &self.auth_key)
+            .append_pair("text", text)
+            .append_pair("target_lang", &target)
+            .finish();
+
+        let response = client
+            .post(&self.url)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            .body(body)
+            .send()
+            .await?;
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(TranslationError::Unauthorized)
+            }
+            status if status.as_u16() == QUOTA_EXCEEDED => {
+                return Err(TranslationError::QuotaExceeded)
+            }
+            status => return Err(TranslationError::Api(format!("DeepL returned {}", status))),
+        }
+
+        let body: serde_json::Value = serde_json::from_str(&response.text().await?)
+            .map_err(|e| TranslationError::Api(format!("DeepL sent invalid JSON: {}", e)))?;
+        let translation = &body["translations"][0];
+        let text = translation["text"]
+            .as_str()
+            .ok_or_else(|| TranslationError::Api("DeepL returned no translation".to_string()))?;
+        Ok(Translation {
+            text: text.to_string(),
+            source_lang: translation["detected_source_language"]
+                .as_str()
+                .map(from_deepl_lang),
+        })
+    }
+}
+
+/// DeepL's spelling of a BCP-47 target, e.g. `zh` → `ZH`, `pt-br` →
+/// `PT-BR`. English and Portuguese need a variant, so the bare codes pick
+/// `EN-US` and `PT-BR`.
+fn to_deepl_lang(lang: &str) -> Option<String> {
+    let upper = lang.trim().replace('_', "-").to_uppercase();
+    let deepl = match upper.as_str() {
+        "EN" => "EN-US".to_string(),
+        "PT" => "PT-BR".to_string(),
+        "NO" | "NB-NO" => "NB".to_string(),
+        "ZH-CN" | "ZH-SG" => "ZH-HANS".to_string(),
+        "ZH-TW" | "ZH-HK" => "ZH-HANT".to_string(),
+        _ => upper,
+    };
+    DEEPL_TARGETS.contains(&deepl.as_str()).then_some(deepl)
+}
+
+/// The BCP-47 spelling of a DeepL code: `ZH` → `zh`, `EN-US` → `en-US`.
+fn from_deepl_lang(lang: &str) -> String {
+    match lang.split_once('-') {
+        Some((primary, rest)) if rest.len() == 2 => {
+            format!("{}-{}", primary.to_lowercase(), rest.to_uppercase())
+        }
+        // Script subtags are title case, e.g. zh-Hans
+        Some((primary, rest)) => {
+            let mut script = rest.to_lowercase();
+            script[..1].make_ascii_uppercase();
+            format!("{}-{}", primary.to_lowercase(), script)
+        }
+        None => lang.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+
+    fn backend(path: &str) -> DeepLBackend {
+        DeepLBackend::new(&format!("{}{}", mockito::server_url(), path), "secret")
+    }
+
+    #[test]
+    fn test_language_codes() {
+        assert_eq!(to_deepl_lang("zh").as_deref(), Some("ZH"));
+        assert_eq!(to_deepl_lang("zh-TW").as_deref(), Some("ZH-HANT"));
+        assert_eq!(to_deepl_lang("en").as_deref(), Some("EN-US"));
+        assert_eq!(to_deepl_lang("pt_br").as_deref(), Some("PT-BR"));
+        assert_eq!(to_deepl_lang("xx"), None);
+
+        assert_eq!(from_deepl_lang("ZH"), "zh");
+        assert_eq!(from_deepl_lang("EN-US"), "en-US");
+        assert_eq!(from_deepl_lang("ZH-HANS"), "zh-Hans");
+    }
+
+    #[tokio::test]
+    async fn test_translate() {
+        let service = mock("POST", "/deepl")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("auth_key".into(), "secret".into()),
+                Matcher::UrlEncoded("text".into(), "Hallo Welt".into()),
+                Matcher::UrlEncoded("target_lang".into(), "EN-US".into()),
+            ]))
+            .with_status(200)
+            .with_body(
+                r#"{"translations":[{"detected_source_language":"DE","text":"Hello world"}]}"#,
+            )
+            .create();
+
+        let translation = backend("/deepl")
+            .translate(&Client::new(), "Hallo Welt", "en")
+            .await
+            .unwrap();
+
+        assert_eq!(translation.text, "Hello world");
+        assert_eq!(translation.source_lang.as_deref(), Some("de"));
+        service.assert();
+    }
+
+    #[tokio::test]
+    async fn test_rejected_key() {
+        let _service = mock("POST", "/deepl-forbidden").with_status(403).create();
+
+        let result = backend("/deepl-forbidden")
+            .translate(&Client::new(), "Hallo", "en")
+            .await;
+
+        assert!(matches!(result, Err(TranslationError::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn test_quota_exceeded() {
+        let _service = mock("POST", "/deepl-quota").with_status(456).create();
+
+        let result = backend("/deepl-quota")
+            .translate(&Client::new(), "Hallo", "en")
+            .await;
+
+        assert!(matches!(result, Err(TranslationError::QuotaExceeded)));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_target() {
+        let result = backend("/deepl-unused")
+            .translate(&Client::new(), "Hallo", "tlh")
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(TranslationError::UnsupportedLanguage(lang)) if lang == "tlh"
+        ));
+    }
+}
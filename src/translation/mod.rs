@@ -0,0 +1,63 @@
+//! Machine translation of transcripts for `/translate`.
+
+pub mod deepl;
+
+use reqwest::Client;
+use std::fmt;
+use std::future::Future;
+
+/// Text in another language, with the source language the service detected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Translation {
+    pub text: String,
+    /// BCP-47 code, when the service reports one
+    pub source_lang: Option<String>,
+}
+
+/// Why a [`TranslationBackend`] could not translate.
+#[derive(Debug)]
+pub enum TranslationError {
+    /// The service rejected the API key
+    Unauthorized,
+    /// The account's character quota is used up
+    QuotaExceeded,
+    /// The service cannot translate into this language
+    UnsupportedLanguage(String),
+    Http(reqwest::Error),
+    /// Any other failure the service reported
+    Api(String),
+}
+
+impl fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslationError::Unauthorized => write!(f, "translation API key was rejected"),
+            TranslationError::QuotaExceeded => write!(f, "translation quota exceeded"),
+            TranslationError::UnsupportedLanguage(lang) => {
+                write!(f, "cannot translate into '{}'", lang)
+            }
+            TranslationError::Http(e) => write!(f, "{}", e),
+            TranslationError::Api(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TranslationError {}
+
+impl From<reqwest::Error> for TranslationError {
+    fn from(e: reqwest::Error) -> Self {
+        TranslationError::Http(e)
+    }
+}
+
+/// A machine translation service.
+pub trait TranslationBackend {
+    /// Translates `text` into `target_lang`, a BCP-47 code such as `de` or
+    /// `pt-BR`.
+    fn translate(
+        &self,
+        client: &Client,
+        text: &str,
+        target_lang: &str,
+    ) -> impl Future<Output = Result<Translation, TranslationError>> + Send;
+}
@@ -0,0 +1,1086 @@
+use crate::config::{env_flag, ConfigError};
+use crate::formatter;
+use crate::transcript::{TranscriptFetcher, TranscriptService};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use reqwest::{Body, Client, RequestBuilder};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use url::form_urlencoded;
+
+mod catbox;
+
+pub use catbox::CatboxBackend;
+
+type UploadResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Target size of each chunk sent by the streaming upload path.
+const STREAM_CHUNK_SIZE: usize = 16 * 1024;
+
+/// First line of a compressed paste, telling readers how to recover the text.
+pub const COMPRESSED_HEADER: &str = "# gzip+base64 encoded, decode with: base64 -d | gunzip";
+
+/// Placed between the transcript and a configured paste footer.
+const FOOTER_SEPARATOR: &str = "\n\n---\n";
+
+/// Paste title used when `PASTE_NAME_TEMPLATE` is unset.
+const DEFAULT_PASTE_NAME_TEMPLATE: &str = "{video_id} transcript ({lang})";
+
+/// Name of the file holding the transcript inside a gist.
+const GIST_FILE_NAME: &str = "transcript.txt";
+
+/// Where transcripts are uploaded, from `PASTE_BACKEND`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasteService {
+    /// Pastebin, the default. Pastes cannot be edited, so every upload makes
+    /// a new one
+    Pastebin,
+    /// Private GitHub gists (`PASTE_BACKEND=gist`). Uploading the same video
+    /// to the same chat again updates the earlier gist, keeping its URL
+    Gist,
+    /// Text files on catbox.moe (`PASTE_BACKEND=catbox`), owned by the
+    /// account in `CATBOX_USERHASH` when set
+    Catbox,
+}
+
+impl PasteService {
+    pub fn from_env() -> Self {
+        match env::var("PASTE_BACKEND").as_deref() {
+            Ok("gist") => PasteService::Gist,
+            Ok("catbox") => PasteService::Catbox,
+            _ => PasteService::Pastebin,
+        }
+    }
+}
+
+/// IDs of the gists made for each chat and video, so re-fetching a
+/// livestream updates one gist instead of scattering its transcript.
+#[derive(Debug, Default)]
+pub struct GistIds {
+    ids: Mutex<HashMap<(i64, String), String>>,
+}
+
+impl GistIds {
+    pub fn get(&self, chat_id: i64, video_id: &str) -> Option<String> {
+        self.ids
+            .lock()
+            .unwrap()
+            .get(&(chat_id, video_id.to_string()))
+            .cloned()
+    }
+
+    pub fn insert(&self, chat_id: i64, video_id: &str, gist_id: String) {
+        self.ids
+            .lock()
+            .unwrap()
+            .insert((chat_id, video_id.to_string()), gist_id);
+    }
+}
+
+/// The earlier upload of a video to a chat, which the Gist backend updates.
+pub struct PasteSlot<'a> {
+    pub gists: &'a GistIds,
+    pub chat_id: i64,
+    pub video_id: &'a str,
+}
+
+/// A finished upload.
+pub struct Paste {
+    pub url: String,
+    /// The content was uploaded gzip+base64 encoded
+    pub compressed: bool,
+}
+
+/// Builds the HTTP client shared by every upload backend, so connections are
+/// kept alive and reused across requests. `proxy` routes all of its traffic
+/// through that URL; without it reqwest still honours `HTTPS_PROXY` and
+/// `HTTP_PROXY`.
+pub fn build_http_client(proxy: Option<&str>) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .pool_max_idle_per_host(10)
+        .tcp_keepalive(Duration::from_secs(30));
+    if let Some(proxy) = proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| format!("Invalid OUTBOUND_PROXY {:?}: {}", proxy, e))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// Title of the paste holding a transcript, from `PASTE_NAME_TEMPLATE` with
+/// `{video_id}` and `{lang}` filled in.
+pub fn paste_title(video_id: &str, lang: &str) -> String {
+    let template = env::var("PASTE_NAME_TEMPLATE").ok();
+    paste_title_with(template.as_deref(), video_id, lang)
+}
+
+/// Like [`paste_title`], from `template`, with the default for a missing or
+/// blank one.
+pub fn paste_title_with(template: Option<&str>, video_id: &str, lang: &str) -> String {
+    template
+        .filter(|template| !template.trim().is_empty())
+        .unwrap_or(DEFAULT_PASTE_NAME_TEMPLATE)
+        .replace("{video_id}", video_id)
+        .replace("{lang}", lang)
+}
+
+/// Uploads content titled `title` with the backend configured in the
+/// environment, always as a new paste.
+pub async fn upload_from_env(
+    client: Arc<Client>,
+    content: &str,
+    title: &str,
+) -> UploadResult<String> {
+    match PasteService::from_env() {
+        PasteService::Pastebin => {
+            PastebinBackend::from_env(client)?
+                .with_title(Some(title.to_string()))
+                .upload(content)
+                .await
+        }
+        PasteService::Gist => Ok(GistBackend::from_env(client)?
+            .create(title, content)
+            .await?
+            .url),
+        PasteService::Catbox => CatboxBackend::from_env(client).upload(content).await,
+    }
+}
+
+/// Like [`upload_from_env`], but streams the lines to Pastebin instead of
+/// requiring the whole transcript as one string. With the Gist backend the
+/// gist already made for `slot` is updated instead of creating another.
+pub async fn upload_lines_from_env<I>(
+    client: Arc<Client>,
+    lines: I,
+    title: &str,
+    slot: Option<PasteSlot<'_>>,
+) -> UploadResult<Paste>
+where
+    I: Iterator<Item = String> + Send + 'static,
+{
+    let service = PasteService::from_env();
+    if service == PasteService::Pastebin {
+        let backend = PastebinBackend::from_env(client)?.with_title(Some(title.to_string()));
+        let url = backend.upload_stream(text_stream(lines)).await?;
+        return Ok(Paste {
+            url,
+            compressed: backend.compress,
+        });
+    }
+
+    // Gists are sent as JSON and Catbox files as one form part, so the text
+    // is joined up front
+    let content = lines.collect::<Vec<_>>().join("\n");
+    let url = match service {
+        PasteService::Gist => {
+            GistBackend::from_env(client)?
+                .upsert(slot, title, &content)
+                .await?
+        }
+        _ => CatboxBackend::from_env(client).upload(&content).await?,
+    };
+    Ok(Paste {
+        url,
+        compressed: false,
+    })
+}
+
+/// Turns formatted lines into a stream of newline-joined byte chunks of
+/// roughly [`STREAM_CHUNK_SIZE`], built only as the upload reads them.
+pub fn text_stream<I>(lines: I) -> impl Stream<Item = Bytes> + Send + 'static
+where
+    I: Iterator<Item = String> + Send + 'static,
+{
+    let chunks = formatter::chunk_lines(lines, STREAM_CHUNK_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            if i == 0 {
+                Bytes::from(chunk)
+            } else {
+                Bytes::from(format!("\n{}", chunk))
+            }
+        });
+    stream::iter(chunks)
+}
+
+/// Reads `reader` in fixed-size chunks without loading it all at once.
+fn read_chunks<R>(reader: R) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+{
+    stream::try_unfold(reader, |mut reader| async move {
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buf.truncate(read);
+        Ok(Some((Bytes::from(buf), reader)))
+    })
+}
+
+/// Gzips `raw` and base64-encodes it below [`COMPRESSED_HEADER`].
+fn compress_transcript(raw: &[u8]) -> io::Result<String> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(raw)?;
+    let gzipped = encoder.finish()?;
+    Ok(format!("{}\n{}", COMPRESSED_HEADER, BASE64.encode(gzipped)))
+}
+
+/// Fetches a transcript and uploads it as plain text, returning the paste URL
+/// or an error whose message can be shown to the user.
+pub async fn fetch_and_upload<T: TranscriptFetcher>(
+    fetcher: &T,
+    client: Arc<Client>,
+    video_id: &str,
+    lang: &str,
+) -> UploadResult<String> {
+    let fetched = TranscriptService::fetch(fetcher, video_id, lang)
+        .await
+        .map_err(|e| format!("Error fetching transcript: {}", e))?;
+    if fetched.is_blank() {
+        return Err("Transcript could not be retrieved or is empty.".into());
+    }
+
+    let title = paste_title(video_id, &fetched.lang);
+    upload_from_env(client, &formatter::format_plain(&fetched.entries), &title)
+        .await
+        .map_err(|e| UploadError(e).into())
+}
+
+/// A failed upload, keeping the backend's error as its source so a missing
+/// setting can still be told apart.
+#[derive(Debug)]
+pub struct UploadError(pub Box<dyn std::error::Error + Send + Sync>);
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error uploading transcript: {}", self.0)
+    }
+}
+
+impl std::error::Error for UploadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.0.as_ref())
+    }
+}
+
+pub struct PastebinBackend {
+    client: Arc<Client>,
+    api_key: String,
+    user_agent: String,
+    compress: bool,
+    footer: Option<String>,
+    title: Option<String>,
+}
+
+impl PastebinBackend {
+    pub fn new(client: Arc<Client>, api_key: String, user_agent: String) -> Self {
+        Self {
+            client,
+            api_key,
+            user_agent,
+            compress: false,
+            footer: None,
+            title: None,
+        }
+    }
+
+    /// Uploads gzip+base64 encoded content instead of plain text
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Appends `footer` below a separator at the end of every paste
+    pub fn with_footer(mut self, footer: Option<String>) -> Self {
+        self.footer = footer;
+        self
+    }
+
+    /// Names the paste `title` (`api_paste_name`) instead of leaving it untitled
+    pub fn with_title(mut self, title: Option<String>) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// Creates a backend from `PASTEBIN_KEY`, `UPLOAD_USER_AGENT`,
+    /// `PASTEBIN_COMPRESS` and `PASTE_FOOTER`
+    pub fn from_env(client: Arc<Client>) -> UploadResult<Self> {
+        // Get API key from environment variable
+        let api_key = match env::var("PASTEBIN_KEY") {
+            Ok(key) => key,
+            Err(_) => return Err(ConfigError::missing("PASTEBIN_KEY").into()),
+        };
+
+        // Define a user agent, getting it from env var or using a default
+        let user_agent =
+            env::var("UPLOAD_USER_AGENT").unwrap_or_else(|_| "tofuboi/1.0".to_string());
+
+        let footer = env::var("PASTE_FOOTER")
+            .ok()
+            .filter(|footer| !footer.trim().is_empty());
+
+        Ok(Self::new(client, api_key, user_agent)
+            .with_compression(env_flag("PASTEBIN_COMPRESS"))
+            .with_footer(footer))
+    }
+
+    /// Uploads content to Pastebin and returns the resulting URL
+    pub async fn upload(&self, content: &str) -> UploadResult<String> {
+        self.upload_reader(io::Cursor::new(content.to_owned()))
+            .await
+    }
+
+    /// Uploads everything read from `reader`, encoding it as it is sent
+    pub async fn upload_reader<R>(&self, reader: R) -> UploadResult<String>
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        self.upload_body(read_chunks(reader)).await
+    }
+
+    /// Uploads a stream of content chunks, encoding each as it is sent
+    pub async fn upload_stream<S>(&self, chunks: S) -> UploadResult<String>
+    where
+        S: Stream<Item = Bytes> + Send + 'static,
+    {
+        self.upload_body(chunks.map(Ok)).await
+    }
+
+    async fn upload_body<S>(&self, chunks: S) -> UploadResult<String>
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        // Added before compression so the footer is part of the paste size
+        let footer = self
+            .footer
+            .as_ref()
+            .map(|footer| Ok(Bytes::from(format!("{}{}", FOOTER_SEPARATOR, footer))));
+        let chunks = chunks.chain(stream::iter(footer));
+
+        if !self.compress {
+            return self.send_chunks(chunks).await;
+        }
+
+        // gzip needs the whole text, so compressed uploads are buffered
+        let raw = chunks
+            .try_fold(Vec::new(), |mut raw, chunk| async move {
+                raw.extend_from_slice(&chunk);
+                Ok(raw)
+            })
+            .await?;
+        let packed = Bytes::from(compress_transcript(&raw)?);
+        self.send_chunks(stream::iter([Ok(packed)])).await
+    }
+
+    async fn send_chunks<S>(&self, chunks: S) -> UploadResult<String>
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        // The paste content goes last so it can be appended chunk by chunk
+        // Scoped because the serializer is not Send and must not live across awaits
+        let prefix = {
+            let mut form = form_urlencoded::Serializer::new(String::new());
+            form.append_pair("api_dev_key", &self.api_key)
+                .append_pair("api_option", "paste");
+            if let Some(title) = &self.title {
+                form.append_pair("api_paste_name", title);
+            }
+            form.finish() + "&api_paste_code="
+        };
+        let encoded = chunks.map(|chunk| {
+            chunk.map(|bytes| {
+                Bytes::from(form_urlencoded::byte_serialize(&bytes).collect::<String>())
+            })
+        });
+        let body = stream::once(async move { Ok(Bytes::from(prefix)) }).chain(encoded);
+
+        // Send request to Pastebin with the required parameters
+        let response = self.request().body(Body::wrap_stream(body)).send().await?;
+        Self::paste_url(response).await
+    }
+
+    fn request(&self) -> RequestBuilder {
+        // Use mockito server URL in tests, otherwise use the real Pastebin URL
+        #[cfg(test)]
+        let upload_url = {
+            use mockito;
+            mockito::server_url()
+        };
+
+        #[cfg(not(test))]
+        let upload_url = "https://pastebin.com/api/api_post.php".to_string();
+
+        self.client
+            .post(upload_url)
+            .header(reqwest::header::USER_AGENT, &self.user_agent)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+    }
+
+    async fn paste_url(response: reqwest::Response) -> UploadResult<String> {
+        if !response.status().is_success() {
+            // Get the status code and response body for the error message
+            let status = response.status();
+            let error_body = lossy_body(response)
+                .await
+                .unwrap_or_else(|_| "Failed to read response body".to_string());
+            return Err(match parse_pastebin_error(&error_body) {
+                PastebinApiError::Unknown(_) => format!(
+                    "Upload failed with status: {}, response: {}",
+                    status, error_body
+                )
+                .into(),
+                known => known.into(),
+            });
+        }
+
+        // Get the URL from the response body
+        let status = response.status();
+        let url = lossy_body(response).await?.trim().to_string();
+        // Some API errors come back with a 200 status
+        if url.starts_with(BAD_REQUEST_PREFIX) {
+            return Err(parse_pastebin_error(&url).into());
+        }
+        if url.contains(char::REPLACEMENT_CHARACTER) {
+            return Err(format!(
+                "Upload returned status: {} with a malformed response: {}",
+                status, url
+            )
+            .into());
+        }
+
+        // Replace standard URL with raw URL
+        let raw_url = url.replace("https://pastebin.com/", "https://pastebin.com/raw/");
+
+        Ok(raw_url)
+    }
+}
+
+/// The response body as text, with any bytes that are not valid UTF-8
+/// replaced, so a garbled error page still shows its status and content.
+async fn lossy_body(response: reqwest::Response) -> reqwest::Result<String> {
+    let bytes = response.bytes().await?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Start of every error Pastebin's API answers with.
+const BAD_REQUEST_PREFIX: &str = "Bad API request";
+
+/// An error reported by the Pastebin API, shown to users as what to do
+/// about it rather than Pastebin's wording.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PastebinApiError {
+    InvalidApiKey,
+    InvalidApiOption,
+    IpBlocked,
+    UnlistedPasteLimit,
+    PrivatePasteLimit,
+    EmptyPaste,
+    PasteTooLarge,
+    InvalidExpireDate,
+    InvalidPasteFormat,
+    /// Anything else, with the body as Pastebin sent it
+    Unknown(String),
+}
+
+impl fmt::Display for PastebinApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PastebinApiError::InvalidApiKey => {
+                write!(f, "Pastebin API key is invalid — contact the bot operator.")
+            }
+            PastebinApiError::InvalidApiOption
+            | PastebinApiError::InvalidExpireDate
+            | PastebinApiError::InvalidPasteFormat => write!(
+                f,
+                "The bot sent Pastebin a request it rejected — contact the bot operator."
+            ),
+            PastebinApiError::IpBlocked => write!(
+                f,
+                "Pastebin has blocked the bot's IP address — contact the bot operator."
+            ),
+            PastebinApiError::UnlistedPasteLimit | PastebinApiError::PrivatePasteLimit => write!(
+                f,
+                "The bot's Pastebin account has reached its paste limit. Please try again later."
+            ),
+            PastebinApiError::EmptyPaste => write!(f, "The transcript is empty."),
+            PastebinApiError::PasteTooLarge => write!(
+                f,
+                "The transcript is too large for Pastebin. Try a shorter range=."
+            ),
+            PastebinApiError::Unknown(body) => write!(f, "Pastebin rejected the upload: {}", body),
+        }
+    }
+}
+
+impl std::error::Error for PastebinApiError {}
+
+/// Recognises the errors Pastebin documents, such as `Bad API request,
+/// invalid api_dev_key`.
+pub fn parse_pastebin_error(body: &str) -> PastebinApiError {
+    let body = body.trim();
+    let Some(reason) = body
+        .strip_prefix(BAD_REQUEST_PREFIX)
+        .map(|reason| reason.trim_start_matches(',').trim())
+    else {
+        return PastebinApiError::Unknown(body.to_string());
+    };
+
+    match reason {
+        "invalid api_dev_key" => PastebinApiError::InvalidApiKey,
+        "invalid api_option" => PastebinApiError::InvalidApiOption,
+        "IP blocked" => PastebinApiError::IpBlocked,
+        "api_paste_code was empty" => PastebinApiError::EmptyPaste,
+        "maximum paste file size exceeded" => PastebinApiError::PasteTooLarge,
+        "invalid api_expire_date" => PastebinApiError::InvalidExpireDate,
+        "invalid api_paste_format" => PastebinApiError::InvalidPasteFormat,
+        // The limits name the account's allowance, e.g. "maximum number of 25"
+        limit if limit.starts_with("maximum number of") && limit.contains("unlisted pastes") => {
+            PastebinApiError::UnlistedPasteLimit
+        }
+        limit if limit.starts_with("maximum number of") && limit.contains("private pastes") => {
+            PastebinApiError::PrivatePasteLimit
+        }
+        _ => PastebinApiError::Unknown(body.to_string()),
+    }
+}
+
+/// A gist made or updated by [`GistBackend`].
+#[derive(Debug, PartialEq)]
+pub struct Gist {
+    pub id: String,
+    pub url: String,
+}
+
+/// Uploads transcripts as private GitHub gists, which unlike Pastebin pastes
+/// can be edited in place.
+pub struct GistBackend {
+    client: Arc<Client>,
+    token: String,
+    user_agent: String,
+}
+
+impl GistBackend {
+    pub fn new(client: Arc<Client>, token: String, user_agent: String) -> Self {
+        Self {
+            client,
+            token,
+            user_agent,
+        }
+    }
+
+    /// Creates a backend from `GITHUB_TOKEN` and `UPLOAD_USER_AGENT`
+    pub fn from_env(client: Arc<Client>) -> UploadResult<Self> {
+        let token = match env::var("GITHUB_TOKEN") {
+            Ok(token) => token,
+            Err(_) => return Err(ConfigError::missing("GITHUB_TOKEN").into()),
+        };
+        let user_agent =
+            env::var("UPLOAD_USER_AGENT").unwrap_or_else(|_| "tofuboi/1.0".to_string());
+        Ok(Self::new(client, token, user_agent))
+    }
+
+    /// Creates a new private gist
+    pub async fn create(&self, title: &str, content: &str) -> UploadResult<Gist> {
+        let url = format!("{}/gists", Self::api_url());
+        let body = serde_json::json!({
+            "description": title,
+            "public": false,
+            "files": { GIST_FILE_NAME: { "content": content } },
+        });
+        Self::gist(self.request(self.client.post(url), &body).send().await?).await
+    }
+
+    /// Replaces the text of gist `id`, keeping its URL
+    pub async fn update(&self, id: &str, title: &str, content: &str) -> UploadResult<Gist> {
+        let url = format!("{}/gists/{}", Self::api_url(), id);
+        let body = serde_json::json!({
+            "description": title,
+            "files": { GIST_FILE_NAME: { "content": content } },
+        });
+        Self::gist(self.request(self.client.patch(url), &body).send().await?).await
+    }
+
+    /// Updates the gist made earlier for `slot`, or creates one and
+    /// remembers it. Returns the gist's URL.
+    pub async fn upsert(
+        &self,
+        slot: Option<PasteSlot<'_>>,
+        title: &str,
+        content: &str,
+    ) -> UploadResult<String> {
+        let Some(slot) = slot else {
+            return Ok(self.create(title, content).await?.url);
+        };
+
+        if let Some(id) = slot.gists.get(slot.chat_id, slot.video_id) {
+            match self.update(&id, title, content).await {
+                Ok(gist) => return Ok(gist.url),
+                // Deleted on GitHub since, so start a new one
+                Err(e) => log::warn!("Failed to update gist {}, creating a new one: {}", id, e),
+            }
+        }
+        let gist = self.create(title, content).await?;
+        slot.gists.insert(slot.chat_id, slot.video_id, gist.id);
+        Ok(gist.url)
+    }
+
+    fn api_url() -> String {
+        // Use mockito server URL in tests, otherwise the real GitHub API
+        #[cfg(test)]
+        let api_url = mockito::server_url();
+
+        #[cfg(not(test))]
+        let api_url = "https://api.github.com".to_string();
+
+        api_url
+    }
+
+    fn request(&self, request: RequestBuilder, body: &serde_json::Value) -> RequestBuilder {
+        request
+            .bearer_auth(&self.token)
+            .header(reqwest::header::USER_AGENT, &self.user_agent)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+    }
+
+    async fn gist(response: reqwest::Response) -> UploadResult<Gist> {
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_body = lossy_body(response)
+                .await
+                .unwrap_or_else(|_| "Failed to read response body".to_string());
+            return Err(format!(
+                "Upload failed with status: {}, response: {}",
+                status, error_body
+            )
+            .into());
+        }
+
+        let body: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        match (body["id"].as_str(), body["html_url"].as_str()) {
+            (Some(id), Some(url)) => Ok(Gist {
+                id: id.to_string(),
+                url: url.to_string(),
+            }),
+            _ => Err("GitHub returned a gist without an id or URL".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+
+    #[tokio::test]
+    async fn test_backends_share_client() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "shared client".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/shared")
+            .expect(2)
+            .create();
+
+        let client = Arc::new(build_http_client(None).unwrap());
+        let first = PastebinBackend::new(client.clone(), "key".into(), "tofuboi/1.0".into());
+        let second = PastebinBackend::new(client.clone(), "key".into(), "tofuboi/1.0".into());
+
+        // Both backends hold the same client rather than building their own
+        assert_eq!(Arc::strong_count(&client), 3);
+
+        for backend in [&first, &second] {
+            assert_eq!(
+                backend.upload("shared client").await.unwrap(),
+                "https://pastebin.com/raw/shared"
+            );
+        }
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_upload_stream_encodes_chunks() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("api_dev_key".into(), "key".into()),
+                Matcher::UrlEncoded("api_paste_code".into(), "streamed & encoded\nüber".into()),
+            ]))
+            .with_status(200)
+            .with_body("https://pastebin.com/streamed")
+            .create();
+
+        let backend =
+            PastebinBackend::new(Arc::new(Client::new()), "key".into(), "tofuboi/1.0".into());
+        let lines = vec!["streamed & encoded".to_string(), "über".to_string()];
+
+        assert_eq!(
+            backend
+                .upload_stream(text_stream(lines.into_iter()))
+                .await
+                .unwrap(),
+            "https://pastebin.com/raw/streamed"
+        );
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let text = "uh so today today we we talk about bread\n".repeat(50);
+        let packed = compress_transcript(text.as_bytes()).unwrap();
+
+        let (header, encoded) = packed.split_once('\n').unwrap();
+        assert_eq!(header, COMPRESSED_HEADER);
+        assert!(encoded.len() < text.len());
+
+        let gzipped = BASE64.decode(encoded).unwrap();
+        let mut unpacked = String::new();
+        GzDecoder::new(&gzipped[..])
+            .read_to_string(&mut unpacked)
+            .unwrap();
+        assert_eq!(unpacked, text);
+    }
+
+    #[test]
+    fn test_outbound_proxy() {
+        assert!(build_http_client(Some("http://proxy.internal:3128")).is_ok());
+        assert!(build_http_client(Some("http://[::1]:3128")).is_ok());
+
+        let err = build_http_client(Some("http://[::1")).unwrap_err();
+        assert!(err.starts_with("Invalid OUTBOUND_PROXY \"http://[::1\""));
+    }
+
+    #[tokio::test]
+    async fn test_footer_is_appended() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "with footer\n\n---\nGenerated by tofuboi — https://t.me/mybot".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/footer")
+            .create();
+
+        let backend =
+            PastebinBackend::new(Arc::new(Client::new()), "key".into(), "tofuboi/1.0".into())
+                .with_footer(Some("Generated by tofuboi — https://t.me/mybot".into()));
+
+        assert_eq!(
+            backend.upload("with footer").await.unwrap(),
+            "https://pastebin.com/raw/footer"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prefix_and_suffix_are_uploaded() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "Shared under CC BY\nhello\nworld\nSource: youtu.be/abc123".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/wrapped")
+            .create();
+
+        let backend =
+            PastebinBackend::new(Arc::new(Client::new()), "key".into(), "tofuboi/1.0".into());
+        let lines = vec!["hello".to_string(), "world".to_string()].into_iter();
+        let wrapped = formatter::wrap_lines(lines, "Shared under CC BY", "Source: youtu.be/abc123");
+
+        assert_eq!(
+            backend.upload_stream(text_stream(wrapped)).await.unwrap(),
+            "https://pastebin.com/raw/wrapped"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_paste_is_titled() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(
+                    "api_paste_name".into(),
+                    "HQoJMIgNdjo transcript (es)".into(),
+                ),
+                Matcher::UrlEncoded("api_paste_code".into(), "titled".into()),
+            ]))
+            .with_status(200)
+            .with_body("https://pastebin.com/titled")
+            .create();
+
+        let client = Arc::new(Client::new());
+        let backend = PastebinBackend::new(client, "key".into(), "tofuboi/1.0".into())
+            .with_title(Some(paste_title("HQoJMIgNdjo", "es")));
+
+        assert_eq!(
+            backend.upload("titled").await.unwrap(),
+            "https://pastebin.com/raw/titled"
+        );
+    }
+
+    #[test]
+    fn test_paste_title_template() {
+        assert_eq!(
+            paste_title_with(None, "abc123", "en"),
+            "abc123 transcript (en)"
+        );
+        assert_eq!(
+            paste_title_with(Some(" "), "abc123", "en"),
+            "abc123 transcript (en)"
+        );
+        assert_eq!(
+            paste_title_with(Some("[{lang}] {video_id}"), "abc123", "de"),
+            "[de] abc123"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gist_is_updated_for_same_chat_and_video() {
+        let created = mock("POST", "/gists")
+            .match_header("authorization", "Bearer token")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"public": false, "files": {"transcript.txt": {"content": "first hour"}}}"#
+                    .into(),
+            ))
+            .with_status(201)
+            .with_body(r#"{"id": "g1", "html_url": "https://gist.github.com/g1"}"#)
+            .expect(1)
+            .create();
+        let updated = mock("PATCH", "/gists/g1")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"files": {"transcript.txt": {"content": "first hour\nsecond hour"}}}"#.into(),
+            ))
+            .with_status(200)
+            .with_body(r#"{"id": "g1", "html_url": "https://gist.github.com/g1"}"#)
+            .expect(1)
+            .create();
+
+        let backend = GistBackend::new(
+            Arc::new(Client::new()),
+            "token".into(),
+            "tofuboi/1.0".into(),
+        );
+        let gists = GistIds::default();
+        let slot = || PasteSlot {
+            gists: &gists,
+            chat_id: 42,
+            video_id: "live123",
+        };
+
+        let first = backend
+            .upsert(Some(slot()), "live123 transcript (en)", "first hour")
+            .await
+            .unwrap();
+        let second = backend
+            .upsert(
+                Some(slot()),
+                "live123 transcript (en)",
+                "first hour\nsecond hour",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(first, "https://gist.github.com/g1");
+        assert_eq!(second, first);
+        assert_eq!(gists.get(42, "live123").as_deref(), Some("g1"));
+        assert_eq!(gists.get(7, "live123"), None);
+        created.assert();
+        updated.assert();
+    }
+
+    #[tokio::test]
+    async fn test_upload_reader() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "from a reader".into(),
+            ))
+            .with_status(200)
+            .with_body("https://pastebin.com/reader")
+            .create();
+
+        let backend =
+            PastebinBackend::new(Arc::new(Client::new()), "key".into(), "tofuboi/1.0".into());
+
+        assert_eq!(
+            backend.upload_reader(&b"from a reader"[..]).await.unwrap(),
+            "https://pastebin.com/raw/reader"
+        );
+    }
+
+    /// Compares the largest buffer each path holds for a long transcript.
+    /// Run with `cargo test bench_ -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_streaming_peak_buffer() {
+        let lines = || (0..200_000).map(|i| format!("caption line number {}", i));
+
+        let buffered = lines().collect::<Vec<_>>().join("\n").len();
+        let streamed = futures::executor::block_on(
+            text_stream(lines()).fold(0, |peak, chunk| async move { peak.max(chunk.len()) }),
+        );
+
+        println!(
+            "buffered: {} bytes, streamed peak chunk: {} bytes",
+            buffered, streamed
+        );
+        assert!(streamed <= STREAM_CHUNK_SIZE + 1);
+        assert!(streamed * 100 < buffered);
+    }
+
+    #[tokio::test]
+    async fn test_upload_reports_api_failure() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "bad key".into(),
+            ))
+            .with_status(500)
+            .with_body("Bad API request, invalid api_dev_key")
+            .create();
+
+        let backend =
+            PastebinBackend::new(Arc::new(Client::new()), "key".into(), "tofuboi/1.0".into());
+        let err = backend.upload("bad key").await.unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Pastebin API key is invalid — contact the bot operator."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8_error_page_keeps_status() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "garbled error".into(),
+            ))
+            .with_status(502)
+            .with_body(b"Bad gateway \xff\xfe")
+            .create();
+
+        let backend =
+            PastebinBackend::new(Arc::new(Client::new()), "key".into(), "tofuboi/1.0".into());
+        let err = backend.upload("garbled error").await.unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Upload failed with status: 502 Bad Gateway, response: Bad gateway \u{fffd}\u{fffd}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8_with_ok_status() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "garbled url".into(),
+            ))
+            .with_status(200)
+            .with_body(b"https://pastebin.com/\xff")
+            .create();
+
+        let backend =
+            PastebinBackend::new(Arc::new(Client::new()), "key".into(), "tofuboi/1.0".into());
+        let err = backend.upload("garbled url").await.unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("status: 200 OK with a malformed response"));
+    }
+
+    #[tokio::test]
+    async fn test_api_error_with_ok_status() {
+        let _m = mock("POST", "/")
+            .match_body(Matcher::UrlEncoded(
+                "api_paste_code".into(),
+                "over the limit".into(),
+            ))
+            .with_status(200)
+            .with_body(
+                "Bad API request, maximum number of 25 unlisted pastes for your free account",
+            )
+            .create();
+
+        let backend =
+            PastebinBackend::new(Arc::new(Client::new()), "key".into(), "tofuboi/1.0".into());
+        let err = backend.upload("over the limit").await.unwrap_err();
+
+        assert!(err.to_string().contains("paste limit"));
+    }
+
+    #[test]
+    fn test_parse_pastebin_error() {
+        let cases = [
+            (
+                "Bad API request, invalid api_dev_key",
+                PastebinApiError::InvalidApiKey,
+            ),
+            (
+                "Bad API request, invalid api_option",
+                PastebinApiError::InvalidApiOption,
+            ),
+            ("Bad API request, IP blocked", PastebinApiError::IpBlocked),
+            (
+                "Bad API request, maximum number of 25 unlisted pastes for your free account",
+                PastebinApiError::UnlistedPasteLimit,
+            ),
+            (
+                "Bad API request, maximum number of 10 private pastes for your free account",
+                PastebinApiError::PrivatePasteLimit,
+            ),
+            (
+                "Bad API request, api_paste_code was empty",
+                PastebinApiError::EmptyPaste,
+            ),
+            (
+                "Bad API request, maximum paste file size exceeded",
+                PastebinApiError::PasteTooLarge,
+            ),
+            (
+                "Bad API request, invalid api_expire_date",
+                PastebinApiError::InvalidExpireDate,
+            ),
+            (
+                "Bad API request, invalid api_paste_format",
+                PastebinApiError::InvalidPasteFormat,
+            ),
+            (
+                "Bad API request, invalid api_user_key",
+                PastebinApiError::Unknown("Bad API request, invalid api_user_key".into()),
+            ),
+            (
+                "Post limit, maximum pastes per 24h reached",
+                PastebinApiError::Unknown("Post limit, maximum pastes per 24h reached".into()),
+            ),
+        ];
+
+        for (body, expected) in cases {
+            assert_eq!(parse_pastebin_error(body), expected, "{}", body);
+        }
+        assert_eq!(
+            PastebinApiError::InvalidApiKey.to_string(),
+            "Pastebin API key is invalid — contact the bot operator."
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use super::{lossy_body, UploadResult};
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+use std::env;
+use std::sync::Arc;
+
+/// Name the transcript is uploaded under; Catbox keeps only its extension.
+const FILE_NAME: &str = "transcript.txt";
+
+/// Uploads transcripts as files on catbox.moe, which answers with the file's
+/// URL as plain text.
+pub struct CatboxBackend {
+    client: Arc<Client>,
+    userhash: Option<String>,
+}
+
+impl CatboxBackend {
+    pub fn new(client: Arc<Client>, userhash: Option<String>) -> Self {
+        Self { client, userhash }
+    }
+
+    /// Creates a backend from `CATBOX_USERHASH`. Without it uploads are
+    /// anonymous; with it they belong to that account and can be managed
+    /// from it
+    pub fn from_env(client: Arc<Client>) -> Self {
+        let userhash = env::var("CATBOX_USERHASH")
+            .ok()
+            .filter(|hash| !hash.trim().is_empty());
+        Self::new(client, userhash)
+    }
+
+    /// Uploads content as a text file and returns its URL
+    pub async fn upload(&self, content: &str) -> UploadResult<String> {
+        let mut form = Form::new().text("reqtype", "fileupload");
+        if let Some(userhash) = &self.userhash {
+            form = form.text("userhash", userhash.clone());
+        }
+        let file = Part::bytes(content.as_bytes().to_vec())
+            .file_name(FILE_NAME)
+            .mime_str("text/plain")?;
+        form = form.part("fileToUpload", file);
+
+        let response = self
+            .client
+            .post(Self::api_url())
+            .multipart(form)
+            .send()
+            .await?;
+        let status = response.status();
+        let body = lossy_body(response)
+            .await
+            .unwrap_or_else(|_| "Failed to read response body".to_string());
+        if !status.is_success() {
+            return Err(
+                format!("Upload failed with status: {}, response: {}", status, body).into(),
+            );
+        }
+
+        let url = body.trim();
+        if url.starts_with("https://") || url.starts_with("http://") {
+            Ok(url.to_string())
+        } else {
+            Err(format!("Catbox returned no URL: {}", url).into())
+        }
+    }
+
+    fn api_url() -> String {
+        // Use mockito server URL in tests, otherwise the real Catbox API
+        #[cfg(test)]
+        let api_url = format!("{}/user/api.php", mockito::server_url());
+
+        #[cfg(not(test))]
+        let api_url = "https://catbox.moe/user/api.php".to_string();
+
+        api_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+
+    #[tokio::test]
+    async fn test_anonymous_upload() {
+        // The file part follows `reqtype` directly, so no userhash was sent
+        let service = mock("POST", "/user/api.php")
+            .match_header(
+                "content-type",
+                Matcher::Regex("^multipart/form-data; boundary=".into()),
+            )
+            .match_body(Matcher::Regex(
+                "name=\"reqtype\"\r\n\r\nfileupload\r\n--[^\r]+\r\n\
+                 Content-Disposition: form-data; name=\"fileToUpload\"; filename=\"transcript.txt\"\r\n\
+                 Content-Type: text/plain\r\n\r\n\\[00:00\\] anonymous\r\n"
+                    .into(),
+            ))
+            .with_status(200)
+            .with_body("https://files.catbox.moe/anon01.txt\n")
+            .create();
+
+        let url = CatboxBackend::new(Arc::new(Client::new()), None)
+            .upload("[00:00] anonymous")
+            .await
+            .unwrap();
+
+        assert_eq!(url, "https://files.catbox.moe/anon01.txt");
+        service.assert();
+    }
+
+    #[tokio::test]
+    async fn test_upload_with_userhash() {
+        let service = mock("POST", "/user/api.php")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::Regex("name=\"reqtype\"\r\n\r\nfileupload\r\n".into()),
+                Matcher::Regex("name=\"userhash\"\r\n\r\nhash123\r\n".into()),
+                Matcher::Regex("filename=\"transcript.txt\"".into()),
+                Matcher::Regex("\\[00:00\\] owned".into()),
+            ]))
+            .with_status(200)
+            .with_body("https://files.catbox.moe/owned1.txt")
+            .create();
+
+        let url = CatboxBackend::new(Arc::new(Client::new()), Some("hash123".to_string()))
+            .upload("[00:00] owned")
+            .await
+            .unwrap();
+
+        assert_eq!(url, "https://files.catbox.moe/owned1.txt");
+        service.assert();
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_reported() {
+        let _service = mock("POST", "/user/api.php")
+            .match_body(Matcher::Regex("\\[00:00\\] rejected".into()))
+            .with_status(412)
+            .with_body("File too large")
+            .create();
+
+        let error = CatboxBackend::new(Arc::new(Client::new()), None)
+            .upload("[00:00] rejected")
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("File too large"));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::args::parse_timestamp;
+use url::Url;
+
+/// Length of every YouTube video ID.
+const VIDEO_ID_LEN: usize = 11;
+
+/// Path prefixes that are followed directly by a video ID.
+const ID_PATH_KINDS: [&str; 4] = ["shorts", "embed", "live", "v"];
+
+fn is_video_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Returns true when `s` has the shape of a bare YouTube video ID.
+pub fn looks_like_video_id(s: &str) -> bool {
+    s.len() == VIDEO_ID_LEN && s.chars().all(is_video_id_char)
+}
+
+/// Turns user input into a video ID. YouTube URLs (`watch?v=`, `youtu.be/`,
+/// `/shorts/`, `/embed/`, `/live/`) are tried first, then the bare-ID shape;
+/// anything else yields `None`.
+pub fn parse_video_input(input: &str) -> Option<String> {
+    let input = input.trim();
+    video_id_from_url(input).or_else(|| looks_like_video_id(input).then(|| input.to_string()))
+}
+
+/// Reads the `t=` start time of a YouTube link, in milliseconds.
+pub fn parse_start_offset(input: &str) -> Option<u64> {
+    let url = parse_url(input.trim())?;
+    let (_, t) = url.query_pairs().find(|(key, _)| key == "t")?;
+    parse_timestamp(&t)
+}
+
+fn parse_url(input: &str) -> Option<Url> {
+    // Links pasted without a scheme are still links
+    Url::parse(input)
+        .or_else(|_| Url::parse(&format!("https://{}", input)))
+        .ok()
+}
+
+fn video_id_from_url(input: &str) -> Option<String> {
+    let url = parse_url(input)?;
+    let host = url.host_str()?;
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let host = host.strip_prefix("m.").unwrap_or(host);
+
+    let mut segments = url.path_segments()?;
+    let candidate = match host {
+        "youtu.be" => segments.next()?.to_string(),
+        "youtube.com" | "music.youtube.com" | "youtube-nocookie.com" => {
+            if url.path() == "/watch" {
+                url.query_pairs()
+                    .find(|(key, _)| key == "v")
+                    .map(|(_, value)| value.into_owned())?
+            } else {
+                let kind = segments.next()?;
+                if !ID_PATH_KINDS.contains(&kind) {
+                    return None;
+                }
+                segments.next()?.to_string()
+            }
+        }
+        _ => return None,
+    };
+
+    looks_like_video_id(&candidate).then_some(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_video_input_urls() {
+        for input in [
+            "https://www.youtube.com/watch?v=HQoJMIgNdjo",
+            "http://youtube.com/watch?v=HQoJMIgNdjo",
+            "https://youtube.com/watch?feature=share&v=HQoJMIgNdjo&t=10",
+            "https://m.youtube.com/watch?v=HQoJMIgNdjo",
+            "https://music.youtube.com/watch?v=HQoJMIgNdjo&list=RDAMVM",
+            "https://youtu.be/HQoJMIgNdjo",
+            "https://youtu.be/HQoJMIgNdjo?t=42",
+            "youtu.be/HQoJMIgNdjo",
+            "https://www.youtube.com/shorts/HQoJMIgNdjo",
+            "https://m.youtube.com/shorts/HQoJMIgNdjo?feature=share",
+            "https://www.youtube.com/embed/HQoJMIgNdjo",
+            "https://www.youtube-nocookie.com/embed/HQoJMIgNdjo?start=5",
+            "youtube.com/embed/HQoJMIgNdjo",
+            "https://www.youtube.com/live/HQoJMIgNdjo",
+            "https://www.youtube.com/v/HQoJMIgNdjo",
+            "  https://youtu.be/HQoJMIgNdjo  ",
+        ] {
+            assert_eq!(
+                parse_video_input(input).as_deref(),
+                Some("HQoJMIgNdjo"),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_start_offset() {
+        assert_eq!(
+            parse_start_offset("https://youtu.be/HQoJMIgNdjo?t=120"),
+            Some(120_000)
+        );
+        assert_eq!(
+            parse_start_offset("https://www.youtube.com/watch?v=HQoJMIgNdjo&t=2m0s"),
+            Some(120_000)
+        );
+        assert_eq!(
+            parse_start_offset("youtu.be/HQoJMIgNdjo?t=1h2m3s"),
+            Some(3_723_000)
+        );
+        assert_eq!(parse_start_offset("https://youtu.be/HQoJMIgNdjo"), None);
+        assert_eq!(parse_start_offset("HQoJMIgNdjo"), None);
+    }
+
+    #[test]
+    fn test_parse_video_input_bare_ids() {
+        for input in ["HQoJMIgNdjo", "dQw4w9WgXcQ", "a-b_c-d_e-f", " HQoJMIgNdjo "] {
+            assert_eq!(
+                parse_video_input(input).as_deref(),
+                Some(input.trim()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_video_input_rejects_garbage() {
+        for input in [
+            "",
+            "hello",
+            "HQoJMIgNdj",
+            "HQoJMIgNdjoo",
+            "HQoJMIgN!jo",
+            "https://example.com/watch?v=HQoJMIgNdjo",
+            "https://www.youtube.com/watch?list=PL123",
+            "https://www.youtube.com/watch?v=short",
+            "https://www.youtube.com/channel/HQoJMIgNdjo",
+            "https://www.youtube.com/",
+            "https://youtu.be/",
+            "not a url at all",
+        ] {
+            assert_eq!(parse_video_input(input), None, "{}", input);
+        }
+    }
+}
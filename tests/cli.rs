@@ -0,0 +1,43 @@
+use assert_cmd::Command;
+
+#[test]
+#[ignore = "fetches a real transcript from YouTube"]
+fn test_dry_run_prints_instead_of_uploading() {
+    let output = Command::cargo_bin("tofuboi")
+        .unwrap()
+        .args(["fetch", "HQoJMIgNdjo", "--dry-run"])
+        .env_remove("PASTEBIN_KEY")
+        .env_remove("TELOXIDE_TOKEN")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    let header = lines.next().unwrap();
+    assert!(header.starts_with("[DRY RUN] Would upload "));
+    assert!(header.ends_with(" bytes to Pastebin"));
+    assert!(lines.any(|line| !line.trim().is_empty()));
+}
+
+#[test]
+#[ignore = "fetches a real transcript from YouTube"]
+fn test_spans_are_exported_to_otlp_collector() {
+    let collector = mockito::mock("POST", "/v1/traces")
+        .with_status(200)
+        .expect_at_least(1)
+        .create();
+
+    let output = Command::cargo_bin("tofuboi")
+        .unwrap()
+        .args(["fetch", "HQoJMIgNdjo", "--dry-run"])
+        .env("OTEL_EXPORTER_OTLP_ENDPOINT", mockito::server_url())
+        .env_remove("PASTEBIN_KEY")
+        .env_remove("TELOXIDE_TOKEN")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    // The exporter flushes before the process exits
+    collector.assert();
+}